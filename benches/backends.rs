@@ -0,0 +1,110 @@
+use std::fs;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use state_machine_compiler_rust::bench::{self, Backend, BenchOptions};
+use state_machine_compiler_rust::cconverter::ToC;
+use state_machine_compiler_rust::interpreter::Machine;
+use state_machine_compiler_rust::lexer::Lexer;
+use state_machine_compiler_rust::llvmconverter::ToLlvmIr;
+use state_machine_compiler_rust::parser::{ParseTree, Parser};
+
+const FIXTURES: [&str; 2] = ["example", "01_state_machine"];
+
+const STEPS: usize = 1_000_000;
+
+fn fixture(name: &str) -> ParseTree {
+    let path = format!("{}/examples/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+    let code = fs::read_to_string(&path).expect("Failed to read the example machine");
+    let lexer = Lexer::new(&code);
+    let mut parser = Parser::new(lexer);
+    parser.program();
+    parser.tree
+}
+
+fn interpreter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interpreter");
+    for name in FIXTURES {
+        let tree = fixture(name);
+        let mut reference = Machine::new(&tree);
+        reference.run(STEPS);
+        group.throughput(Throughput::Elements(reference.steps as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut machine = Machine::new(&tree);
+                machine.run(STEPS);
+                machine.steps
+            })
+        });
+    }
+    group.finish();
+}
+
+fn codegen(c: &mut Criterion) {
+    let mut group = c.benchmark_group("codegen");
+    for name in FIXTURES {
+        let tree = fixture(name);
+        group.bench_with_input(BenchmarkId::new("rust", name), &tree, |b, tree| {
+            b.iter(|| tree.to_rust_code())
+        });
+        group.bench_with_input(BenchmarkId::new("rust_library", name), &tree, |b, tree| {
+            b.iter(|| tree.to_rust_library())
+        });
+        group.bench_with_input(BenchmarkId::new("c", name), &tree, |b, tree| {
+            b.iter(|| tree.to_c())
+        });
+        group.bench_with_input(BenchmarkId::new("llvm", name), &tree, |b, tree| {
+            b.iter(|| tree.to_llvm_ir())
+        });
+    }
+    group.finish();
+}
+
+// The built programs, skipped when rustc, cc or clang is missing
+fn native(c: &mut Criterion) {
+    let mut group = c.benchmark_group("native");
+    group.sample_size(10);
+    for name in FIXTURES {
+        let tree = fixture(name);
+        let mut reference = Machine::new(&tree);
+        reference.run(STEPS);
+        let tape_len = reference.tape.len();
+        group.throughput(Throughput::Elements(reference.steps as u64));
+
+        let options = BenchOptions {
+            steps: STEPS,
+            work_dir: std::env::temp_dir()
+                .join("state_machine_criterion")
+                .join(name),
+            ..Default::default()
+        };
+        for backend in [
+            Backend::Rust,
+            Backend::C,
+            Backend::Llvm,
+            Backend::LlvmThreaded,
+        ] {
+            let program = match bench::compile(&tree, backend, &options) {
+                Ok(program) => program,
+                Err(e) => {
+                    eprintln!("Skipping {} for {}: {}", backend.as_str(), name, e);
+                    continue;
+                }
+            };
+            group.bench_function(BenchmarkId::new(backend.as_str(), name), |b| {
+                // A run shorter than the noise of starting the program
+                // measures as zero, which criterion rejects
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| program.run(&[], STEPS, tape_len).unwrap().0)
+                        .sum::<Duration>()
+                        .max(Duration::from_nanos(iters))
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, interpreter, codegen, native);
+criterion_main!(benches);
@@ -0,0 +1,207 @@
+use std::fmt;
+
+use crate::parser::ParseTree;
+
+// Directed graph of the declared states, with an edge for every transition
+// that can fire. Transitions shadowed by an earlier one, or going to an
+// undeclared state, do not add an edge.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StateGraph {
+    // Successors of every state, indexed like ParseTree::states
+    pub edges: Vec<Vec<usize>>,
+}
+
+impl StateGraph {
+    pub fn new(tree: &ParseTree) -> Self {
+        let mut edges = vec![Vec::new(); tree.states.len()];
+        for (from, state) in tree.states.iter().enumerate() {
+            for symbol in &tree.symbols {
+                let Some(index) = tree.transition_for(state, symbol) else {
+                    continue;
+                };
+                let final_state = &tree.transitions[index].final_state;
+                if let Some(to) = tree.states.iter().position(|s| s == final_state) {
+                    if !edges[from].contains(&to) {
+                        edges[from].push(to);
+                    }
+                }
+            }
+        }
+        StateGraph { edges }
+    }
+
+    // States reachable from `start`, including itself
+    pub fn reachable(&self, start: usize) -> Vec<bool> {
+        let mut seen = vec![false; self.edges.len()];
+        let mut stack = vec![start];
+        seen[start] = true;
+        while let Some(state) = stack.pop() {
+            for &next in &self.edges[state] {
+                if !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        seen
+    }
+
+    // Strongly connected components with Tarjan's algorithm. States in a
+    // component and the components themselves are sorted by state index.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: vec![None; self.edges.len()],
+            low_link: vec![0; self.edges.len()],
+            on_stack: vec![false; self.edges.len()],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        };
+        for state in 0..self.edges.len() {
+            if tarjan.index[state].is_none() {
+                tarjan.visit(state);
+            }
+        }
+        let mut components = tarjan.components;
+        for component in components.iter_mut() {
+            component.sort();
+        }
+        components.sort();
+        components
+    }
+}
+
+struct Tarjan<'a> {
+    graph: &'a StateGraph,
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, state: usize) {
+        self.index[state] = Some(self.next_index);
+        self.low_link[state] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(state);
+        self.on_stack[state] = true;
+
+        for &next in &self.graph.edges[state] {
+            match self.index[next] {
+                None => {
+                    self.visit(next);
+                    self.low_link[state] = self.low_link[state].min(self.low_link[next]);
+                }
+                Some(index) if self.on_stack[next] => {
+                    self.low_link[state] = self.low_link[state].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_link[state]) == self.index[state] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == state {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+// Result of the lint analysis, states and symbols are given by name
+#[derive(Debug, PartialEq, Clone)]
+pub struct Analysis {
+    // States which can never be entered from the initial state
+    pub unreachable_states: Vec<String>,
+    // States without any outgoing transition, the machine halts there
+    pub halting_states: Vec<String>,
+    // (state, symbol) pairs the machine halts on, for states which are not
+    // halting states
+    pub missing_transitions: Vec<(String, String)>,
+    pub components: Vec<Vec<String>>,
+}
+
+impl Analysis {
+    pub fn new(tree: &ParseTree) -> Self {
+        let graph = StateGraph::new(tree);
+        let reachable = match tree.states.iter().position(|s| s == &tree.initial_state) {
+            Some(initial_state) => graph.reachable(initial_state),
+            None => vec![false; tree.states.len()],
+        };
+
+        let mut unreachable_states = Vec::new();
+        let mut halting_states = Vec::new();
+        let mut missing_transitions = Vec::new();
+        for (index, state) in tree.states.iter().enumerate() {
+            if !reachable[index] {
+                unreachable_states.push(state.clone());
+            }
+            let missing: Vec<&String> = tree
+                .symbols
+                .iter()
+                .filter(|symbol| tree.transition_for(state, symbol).is_none())
+                .collect();
+            if missing.len() == tree.symbols.len() {
+                halting_states.push(state.clone());
+            } else {
+                for symbol in missing {
+                    missing_transitions.push((state.clone(), symbol.clone()));
+                }
+            }
+        }
+
+        let components = graph
+            .strongly_connected_components()
+            .iter()
+            .map(|component| {
+                component
+                    .iter()
+                    .map(|&state| tree.states[state].clone())
+                    .collect()
+            })
+            .collect();
+
+        Analysis {
+            unreachable_states,
+            halting_states,
+            missing_transitions,
+            components,
+        }
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |names: &[String]| {
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        };
+        writeln!(f, "Unreachable states: {}", list(&self.unreachable_states))?;
+        writeln!(f, "Halting states: {}", list(&self.halting_states))?;
+        if self.missing_transitions.is_empty() {
+            writeln!(f, "Missing transitions: none")?;
+        } else {
+            writeln!(f, "Missing transitions:")?;
+            for (state, symbol) in &self.missing_transitions {
+                writeln!(f, "  {} reading {}", state, symbol)?;
+            }
+        }
+        writeln!(f, "Strongly connected components:")?;
+        for component in &self.components {
+            writeln!(f, "  {{{}}}", component.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use crate::interpreter::{Machine, StepResult};
+use crate::parser::{DotHighlight, DotOptions, ToDot};
+
+// One dot graph per step of a run, with the active state and the transition
+// applied from it highlighted. The last frame shows the state the machine
+// halted in, or the one reached after `max_steps` steps.
+pub fn dot_frames(machine: &mut Machine, max_steps: usize, options: &DotOptions) -> Vec<String> {
+    let mut frames = Vec::new();
+    loop {
+        let state = machine.state_name().to_string();
+        let step = machine.steps;
+        let symbol = machine.symbol_name().to_string();
+        let (transition, caption) = if frames.len() == max_steps {
+            (None, format!("Step {}: {} (stopped)", step, state))
+        } else {
+            match machine.step() {
+                StepResult::Fired(index) => (
+                    Some(index),
+                    format!("Step {}: {} reading {}", step, state, symbol),
+                ),
+                StepResult::Halted(_) => (None, format!("Step {}: {} (halted)", step, state)),
+            }
+        };
+        frames.push(machine.tree.to_dot_with(&DotOptions {
+            highlight: Some(DotHighlight {
+                state,
+                transition,
+                caption,
+            }),
+            ..options.clone()
+        }));
+        if transition.is_none() {
+            return frames;
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::cconverter::{CCodegenOptions, ToC};
+use crate::interpreter::Machine;
+use crate::llvmconverter::{LlvmCodegenOptions, LlvmDispatch, LlvmOptLevel, ToLlvmIr};
+use crate::parser::ParseTree;
+
+// A way of running a machine
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Backend {
+    // The reference interpreter, in this process
+    Interpreter,
+    // The generated Rust library, built with rustc
+    Rust,
+    // The generated C library, built with cc
+    C,
+    // The generated LLVM IR, built with clang
+    Llvm,
+    // The same with a block per state instead of a switch on every step
+    LlvmThreaded,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 5] = [
+        Backend::Interpreter,
+        Backend::Rust,
+        Backend::C,
+        Backend::Llvm,
+        Backend::LlvmThreaded,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::Interpreter => "interpreter",
+            Backend::Rust => "rust",
+            Backend::C => "c",
+            Backend::Llvm => "llvm",
+            Backend::LlvmThreaded => "llvm-threaded",
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Backend::ALL
+            .into_iter()
+            .find(|backend| backend.as_str() == s)
+            .ok_or(format!("Unknown backend: {}", s))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BenchOptions {
+    // Steps after which a run is stopped if the machine has not halted
+    pub steps: usize,
+    // Optimizations of the LLVM IR and of clang
+    pub opt_level: LlvmOptLevel,
+    // Where the generated code and the programs are written
+    pub work_dir: PathBuf,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            steps: 1_000_000,
+            opt_level: LlvmOptLevel::O2,
+            work_dir: std::env::temp_dir().join("state_machine_bench"),
+        }
+    }
+}
+
+// What was measured for one backend
+#[derive(Debug, PartialEq, Clone)]
+pub struct Measurement {
+    pub backend: Backend,
+    // Generating and building the program
+    pub compile_time: Duration,
+    // The run alone, without starting the program
+    pub run_time: Duration,
+    pub steps: usize,
+}
+
+impl Measurement {
+    // None when the run was too short to be measured
+    pub fn steps_per_second(&self) -> Option<f64> {
+        (!self.run_time.is_zero()).then(|| self.steps as f64 / self.run_time.as_secs_f64())
+    }
+}
+
+// A machine built by a backend, ready to be run
+#[derive(Debug, Clone)]
+pub struct Program<'a> {
+    tree: &'a ParseTree,
+    backend: Backend,
+    // The executable, every backend but the interpreter has one
+    path: Option<PathBuf>,
+}
+
+// Generate the code of `backend` and build it
+pub fn compile<'a>(
+    tree: &'a ParseTree,
+    backend: Backend,
+    options: &BenchOptions,
+) -> Result<Program<'a>, String> {
+    let path = match backend {
+        Backend::Interpreter => {
+            // Resolving the transitions is all the interpreter prepares
+            Machine::new(tree);
+            None
+        }
+        Backend::Rust => {
+            let source = write_source(options, "bench_rust.rs", &rust_harness(tree))?;
+            let program = options.work_dir.join("bench_rust");
+            run_compiler(
+                Command::new("rustc")
+                    .args(["--edition", "2021", "-O", "-A", "warnings", "-o"])
+                    .arg(&program)
+                    .arg(source),
+            )?;
+            Some(program)
+        }
+        Backend::C => {
+            let source = write_source(options, "bench_c.c", &c_harness(tree))?;
+            let program = options.work_dir.join("bench_c");
+            run_compiler(
+                Command::new("cc")
+                    .args(["-std=c99", "-O2", "-o"])
+                    .arg(&program)
+                    .arg(source),
+            )?;
+            Some(program)
+        }
+        Backend::Llvm | Backend::LlvmThreaded => {
+            let (dispatch, name) = match backend {
+                Backend::Llvm => (LlvmDispatch::Switch, "bench_llvm"),
+                _ => (LlvmDispatch::Threaded, "bench_llvm_threaded"),
+            };
+            let llvm_ir = tree.try_to_llvm_ir_with(&LlvmCodegenOptions {
+                opt_level: options.opt_level,
+                dispatch,
+                ..Default::default()
+            })?;
+            let source = write_source(options, &format!("{}.ll", name), &llvm_ir)?;
+            let program = options.work_dir.join(name);
+            run_compiler(
+                Command::new("clang")
+                    .arg(format!("-O{}", options.opt_level as u8))
+                    .arg("-o")
+                    .arg(&program)
+                    .arg(source),
+            )?;
+            Some(program)
+        }
+    };
+    Ok(Program {
+        tree,
+        backend,
+        path,
+    })
+}
+
+fn write_source(options: &BenchOptions, name: &str, code: &str) -> Result<PathBuf, String> {
+    let path = options.work_dir.join(name);
+    fs::create_dir_all(&options.work_dir)
+        .and_then(|_| fs::write(&path, code))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+fn run_compiler(command: &mut Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+impl Program<'_> {
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    // Run from `tape` for at most `steps` steps on a tape of `tape_len` cells,
+    // which must hold every cell the head visits. Returns the time of the run
+    // alone and the final tape.
+    pub fn run(
+        &self,
+        tape: &[usize],
+        steps: usize,
+        tape_len: usize,
+    ) -> Result<(Duration, String), String> {
+        let Some(path) = &self.path else {
+            let mut machine = Machine::with_tape(self.tree, tape.to_vec());
+            let start = Instant::now();
+            machine.run(steps);
+            return Ok((start.elapsed(), machine.tape_string()));
+        };
+        // Starting the program is measured by a run of no step
+        let (startup_time, _) = self.execute(path, tape, 0, tape_len)?;
+        let (elapsed, final_tape) = self.execute(path, tape, steps, tape_len)?;
+        Ok((elapsed.saturating_sub(startup_time), final_tape))
+    }
+
+    fn execute(
+        &self,
+        path: &Path,
+        tape: &[usize],
+        steps: usize,
+        tape_len: usize,
+    ) -> Result<(Duration, String), String> {
+        let names = tape
+            .iter()
+            .map(|&symbol| self.tree.symbols[symbol].as_str())
+            .collect::<Vec<&str>>();
+        let mut command = Command::new(path);
+        match self.backend {
+            Backend::Llvm | Backend::LlvmThreaded => {
+                if names.iter().any(|name| name.len() != 1) {
+                    return Err(
+                        "The LLVM program only reads tapes of one character symbols".to_string()
+                    );
+                }
+                command
+                    .args(["--steps", &steps.to_string()])
+                    .args(["--tape-len", &tape_len.to_string()]);
+                if !names.is_empty() {
+                    command.arg(names.concat());
+                }
+            }
+            _ => {
+                command
+                    .arg(steps.to_string())
+                    .arg(tape_len.to_string())
+                    .args(&names);
+            }
+        }
+        let start = Instant::now();
+        let output = command
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", path.display(), e))?;
+        let elapsed = start.elapsed();
+        if !output.status.success() {
+            return Err(format!(
+                "{} stopped with {}: {}",
+                path.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        // The LLVM program prints the whole tape above a separator, the
+        // harnesses print it first
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines = stdout.lines().collect::<Vec<&str>>();
+        let tape_line = match self.backend {
+            Backend::Llvm | Backend::LlvmThreaded => lines
+                .iter()
+                .position(|line| *line == "=========")
+                .and_then(|i| i.checked_sub(1)),
+            _ => Some(0),
+        };
+        let final_tape = tape_line
+            .and_then(|i| lines.get(i))
+            .unwrap_or(&"")
+            .to_string();
+        Ok((elapsed, final_tape))
+    }
+}
+
+// Compile and run `tree` with every backend, from `tape` for at most
+// `options.steps` steps. A backend fails when it cannot be built or its
+// final tape differs from the one of the interpreter.
+pub fn bench(
+    tree: &ParseTree,
+    tape: &[usize],
+    backends: &[Backend],
+    options: &BenchOptions,
+) -> Vec<(Backend, Result<Measurement, String>)> {
+    let mut reference = Machine::with_tape(tree, tape.to_vec());
+    reference.run(options.steps);
+
+    backends
+        .iter()
+        .map(|&backend| (backend, measure(tree, tape, backend, options, &reference)))
+        .collect()
+}
+
+fn measure(
+    tree: &ParseTree,
+    tape: &[usize],
+    backend: Backend,
+    options: &BenchOptions,
+    reference: &Machine,
+) -> Result<Measurement, String> {
+    let start = Instant::now();
+    let program = compile(tree, backend, options)?;
+    let compile_time = start.elapsed();
+    let (run_time, final_tape) = program.run(tape, options.steps, reference.tape.len())?;
+    let expected_tape = reference.tape_string();
+    if final_tape != expected_tape {
+        return Err(format!(
+            "Final tape {} instead of {}",
+            final_tape, expected_tape
+        ));
+    }
+    Ok(Measurement {
+        backend,
+        compile_time,
+        run_time,
+        steps: reference.steps,
+    })
+}
+
+// One line per backend:
+//
+//     backend          compile time        steps       run time      steps/s
+//     interpreter           0.02 ms      1000000       21.43 ms       46.7 M
+pub fn report(results: &[(Backend, Result<Measurement, String>)]) -> String {
+    let mut report = format!(
+        "{:<14} {:>14} {:>12} {:>14} {:>12}\n",
+        "backend", "compile time", "steps", "run time", "steps/s"
+    );
+    for (backend, result) in results {
+        match result {
+            Ok(measurement) => report.push_str(&format!(
+                "{:<14} {:>11.2} ms {:>12} {:>11.2} ms {:>12}\n",
+                backend.as_str(),
+                measurement.compile_time.as_secs_f64() * 1000.0,
+                measurement.steps,
+                measurement.run_time.as_secs_f64() * 1000.0,
+                measurement
+                    .steps_per_second()
+                    .map(|steps_per_second| format!("{:.1} M", steps_per_second / 1e6))
+                    .unwrap_or("-".to_string())
+            )),
+            Err(e) => report.push_str(&format!("{:<14} {}\n", backend.as_str(), e)),
+        }
+    }
+    report
+}
+
+// The generated Rust library with a main taking
+// `<steps> <tape length> <symbol>...` and printing the final tape. The state,
+// head and steps follow so that the compiler cannot skip the run.
+fn rust_harness(tree: &ParseTree) -> String {
+    let mut code = tree.to_rust_library();
+    code.push_str("\nfn main() {\n");
+    code.push_str("    let args: Vec<String> = std::env::args().collect();\n");
+    code.push_str("    let steps: usize = args[1].parse().unwrap();\n");
+    code.push_str("    let tape = args[3..]\n");
+    code.push_str("        .iter()\n");
+    code.push_str("        .map(|name| TapeMachineSymbol::from_name(name).unwrap())\n");
+    code.push_str("        .collect();\n");
+    code.push_str("    let mut machine = TapeMachine::new(tape);\n");
+    code.push_str("    machine.run(steps);\n");
+    code.push_str("    let tape: String = machine.tape().iter().map(|s| s.as_str()).collect();\n");
+    code.push_str("    println!(\"{}\", tape);\n");
+    code.push_str(
+        "    println!(\"{:?} {} {}\", machine.state(), machine.head(), machine.steps());\n",
+    );
+    code.push_str("}\n");
+    code
+}
+
+// Same as `rust_harness` for the generated C library, the tape has a fixed
+// length and is filled with blanks
+fn c_harness(tree: &ParseTree) -> String {
+    let mut code = tree.to_c_with(&CCodegenOptions {
+        header_only: true,
+        ..Default::default()
+    });
+    code.push_str("\nint main(int argc, char **argv) {\n");
+    code.push_str("    size_t steps = strtoul(argv[1], NULL, 10);\n");
+    code.push_str("    size_t len = strtoul(argv[2], NULL, 10);\n");
+    code.push_str("    TapeMachineSymbol *tape = malloc(len * sizeof(TapeMachineSymbol));\n");
+    code.push_str("    for (size_t i = 0; i < len; i++) {\n");
+    code.push_str("        tape[i] = SYMBOL_X;\n");
+    code.push_str("    }\n");
+    code.push_str("    for (int i = 3; i < argc; i++) {\n");
+    code.push_str(&format!(
+        "        for (int symbol = 0; symbol < {}; symbol++) {{\n",
+        tree.symbols.len()
+    ));
+    code.push_str("            if (strcmp(argv[i], SYMBOL_NAMES[symbol]) == 0) {\n");
+    code.push_str("                tape[i - 3] = (TapeMachineSymbol)symbol;\n");
+    code.push_str("            }\n");
+    code.push_str("        }\n");
+    code.push_str("    }\n");
+    code.push_str("    TapeMachine machine;\n");
+    code.push_str("    tape_machine_init(&machine, tape, len);\n");
+    code.push_str("    tape_machine_run(&machine, steps);\n");
+    code.push_str("    for (size_t i = 0; i < len; i++) {\n");
+    code.push_str("        printf(\"%s\", SYMBOL_NAMES[tape[i]]);\n");
+    code.push_str("    }\n");
+    code.push_str("    printf(\"\\n%s %zu %zu\\n\", STATE_NAMES[machine.state], machine.index, machine.steps);\n");
+    code.push_str("    free(tape);\n");
+    code.push_str("    return 0;\n");
+    code.push_str("}\n");
+    code
+}
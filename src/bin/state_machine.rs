@@ -1,6 +1,6 @@
-use std::fmt;
 use std::io;
 
+#[allow(non_camel_case_types)]
 #[derive(Debug, PartialEq, Eq)]
 enum TapeMachineState {
     b,
@@ -57,104 +57,174 @@ impl<'a> TapeMachine<'a> {
     fn l(&mut self) {
         self.index -= 1;
     }
-}
 
-fn main() {
-    println!("Enter the number of steps:");
-    let mut steps_input = String::new();
-    io::stdin().read_line(&mut steps_input).unwrap();
-    let steps: usize = steps_input.trim().parse().unwrap();
+    fn trace(&self, step: usize, level: TraceLevel, compact: bool) {
+        if level == TraceLevel::None {
+            return;
+        }
+        let symbol = &self.result[self.index];
+        if compact {
+            print!("{}\t{:?}\t{}\t{}", step, self.state, self.index, symbol.as_str());
+            if level == TraceLevel::Full {
+                let tape: String = self.result.iter().map(|x| x.as_str()).collect();
+                print!("\t{}", tape);
+            }
+            println!();
+        } else {
+            println!("Step: {} State: {:?} Symbol: {:?}", step, self.state, symbol);
+            if level == TraceLevel::Full {
+                let tape: String = self.result.iter().map(|x| x.as_str()).collect();
+                let offset: usize = self.result[..self.index].iter().map(|x| x.as_str().len()).sum();
+                println!("{}", tape);
+                println!("{}^", " ".repeat(offset));
+            }
+        }
+    }
+}
 
-    println!("Enter the total tape length:");
-    let mut tape_length_input = String::new();
-    io::stdin().read_line(&mut tape_length_input).unwrap();
-    let max_len: usize = tape_length_input.trim().parse().unwrap();
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+enum TraceLevel {
+    None,
+    Transitions,
+    Full,
+}
 
-    let mut result = vec![TapeMachineSymbol::SymbolX; max_len];
-    let mut tape_machine = TapeMachine::new(&TapeMachineState::b, &mut result);
+fn trace_settings() -> (TraceLevel, bool) {
+    let level = match std::env::var("TAPE_MACHINE_TRACE").as_deref() {
+        Ok("none") => TraceLevel::None,
+        Ok("transitions") => TraceLevel::Transitions,
+        Ok("full") => TraceLevel::Full,
+        _ => TraceLevel::Transitions,
+    };
+    let compact = match std::env::var("TAPE_MACHINE_TRACE_FORMAT").as_deref() {
+        Ok("compact") => true,
+        Ok("human") => false,
+        _ => false,
+    };
+    (level, compact)
+}
 
-    for i in 0..steps {
-        println!("Step: {} State: {:?} Symbol: {:?}",
-            i, tape_machine.state, tape_machine.result[tape_machine.index]);
-
-        match (tape_machine.state, &tape_machine.result[tape_machine.index]) {
-            (TapeMachineState::o, TapeMachineSymbol::Symbol1) =>{
-                tape_machine.r();
-                tape_machine.p(TapeMachineSymbol::Symbolx);
-                tape_machine.l();
-                tape_machine.l();
-                tape_machine.l();
-                tape_machine.state = &TapeMachineState::o;
+// Apply the transition for the current state and symbol
+fn step(tape_machine: &mut TapeMachine, trace_level: TraceLevel, compact_trace: bool) {
+    match (tape_machine.state, &tape_machine.result[tape_machine.index]) {
+        (TapeMachineState::o, TapeMachineSymbol::Symbol1) =>{
+            tape_machine.r();
+            tape_machine.p(TapeMachineSymbol::Symbolx);
+            tape_machine.l();
+            tape_machine.l();
+            tape_machine.l();
+            tape_machine.state = &TapeMachineState::o;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::o);
             }
-            (TapeMachineState::o, TapeMachineSymbol::Symbol0) =>{
-                // X means do nothing
-                tape_machine.state = &TapeMachineState::q;
+        }
+        (TapeMachineState::o, TapeMachineSymbol::Symbol0) =>{
+            // X means do nothing
+            tape_machine.state = &TapeMachineState::q;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::q);
             }
-            (TapeMachineState::q, TapeMachineSymbol::Symbol0 | TapeMachineSymbol::Symbol1) =>{
-                tape_machine.r();
-                tape_machine.r();
-                tape_machine.state = &TapeMachineState::q;
+        }
+        (TapeMachineState::q, TapeMachineSymbol::Symbol0 | TapeMachineSymbol::Symbol1) =>{
+            tape_machine.r();
+            tape_machine.r();
+            tape_machine.state = &TapeMachineState::q;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::q);
             }
-            (TapeMachineState::q, TapeMachineSymbol::SymbolX) =>{
-                tape_machine.p(TapeMachineSymbol::Symbol1);
-                tape_machine.l();
-                tape_machine.state = &TapeMachineState::p;
+        }
+        (TapeMachineState::q, TapeMachineSymbol::SymbolX) =>{
+            tape_machine.p(TapeMachineSymbol::Symbol1);
+            tape_machine.l();
+            tape_machine.state = &TapeMachineState::p;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::p);
             }
-            (TapeMachineState::p, TapeMachineSymbol::Symbolx) =>{
-                tape_machine.p(TapeMachineSymbol::SymbolX);
-                tape_machine.r();
-                tape_machine.state = &TapeMachineState::q;
+        }
+        (TapeMachineState::p, TapeMachineSymbol::Symbolx) =>{
+            tape_machine.p(TapeMachineSymbol::SymbolX);
+            tape_machine.r();
+            tape_machine.state = &TapeMachineState::q;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::q);
             }
-            (TapeMachineState::p, TapeMachineSymbol::Symbole) =>{
-                tape_machine.r();
-                tape_machine.state = &TapeMachineState::f;
+        }
+        (TapeMachineState::p, TapeMachineSymbol::Symbole) =>{
+            tape_machine.r();
+            tape_machine.state = &TapeMachineState::f;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::f);
             }
-            (TapeMachineState::p, TapeMachineSymbol::SymbolX) =>{
-                tape_machine.l();
-                tape_machine.l();
-                tape_machine.state = &TapeMachineState::p;
+        }
+        (TapeMachineState::p, TapeMachineSymbol::SymbolX) =>{
+            tape_machine.l();
+            tape_machine.l();
+            tape_machine.state = &TapeMachineState::p;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::p);
             }
-            (TapeMachineState::f, TapeMachineSymbol::SymbolX) =>{
-                tape_machine.p(TapeMachineSymbol::Symbol0);
-                tape_machine.l();
-                tape_machine.l();
-                tape_machine.state = &TapeMachineState::o;
+        }
+        (TapeMachineState::f, TapeMachineSymbol::SymbolX) =>{
+            tape_machine.p(TapeMachineSymbol::Symbol0);
+            tape_machine.l();
+            tape_machine.l();
+            tape_machine.state = &TapeMachineState::o;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::o);
             }
-            (TapeMachineState::b, _) =>{
-                tape_machine.p(TapeMachineSymbol::Symbole);
-                tape_machine.r();
-                tape_machine.p(TapeMachineSymbol::Symbole);
-                tape_machine.r();
-                tape_machine.p(TapeMachineSymbol::Symbol0);
-                tape_machine.r();
-                tape_machine.r();
-                tape_machine.p(TapeMachineSymbol::Symbol0);
-                tape_machine.l();
-                tape_machine.l();
-                tape_machine.state = &TapeMachineState::o;
+        }
+        (TapeMachineState::b, _) =>{
+            tape_machine.p(TapeMachineSymbol::Symbole);
+            tape_machine.r();
+            tape_machine.p(TapeMachineSymbol::Symbole);
+            tape_machine.r();
+            tape_machine.p(TapeMachineSymbol::Symbol0);
+            tape_machine.r();
+            tape_machine.r();
+            tape_machine.p(TapeMachineSymbol::Symbol0);
+            tape_machine.l();
+            tape_machine.l();
+            tape_machine.state = &TapeMachineState::o;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::o);
             }
-            (TapeMachineState::f, _) =>{
-                tape_machine.r();
-                tape_machine.r();
-                tape_machine.state = &TapeMachineState::f;
+        }
+        (TapeMachineState::f, _) =>{
+            tape_machine.r();
+            tape_machine.r();
+            tape_machine.state = &TapeMachineState::f;
+            if trace_level >= TraceLevel::Transitions && !compact_trace {
                 println!("Final State: {:?}", TapeMachineState::f);
             }
-            (_, _) => {
-                println!("State: {:?} Index: {:?} Symbol: {:?}", tape_machine.state, tape_machine.index, tape_machine.result[tape_machine.index]);
-                let binary_result: String = tape_machine.result.iter().map(|x| x.as_str()).collect();
-                println!("{}", binary_result);
-                panic!("Invalid state reached");
-            }
         }
+        (_, _) => {
+            println!("State: {:?} Index: {:?} Symbol: {:?}", tape_machine.state, tape_machine.index, tape_machine.result[tape_machine.index]);
+            let binary_result: String = tape_machine.result.iter().map(|x| x.as_str()).collect();
+            println!("{}", binary_result);
+            panic!("Invalid state reached");
+        }
+    }
+}
+
+fn main() {
+    println!("Enter the number of steps:");
+    let mut steps_input = String::new();
+    io::stdin().read_line(&mut steps_input).unwrap();
+    let steps: usize = steps_input.trim().parse().unwrap();
+
+    println!("Enter the total tape length:");
+    let mut tape_length_input = String::new();
+    io::stdin().read_line(&mut tape_length_input).unwrap();
+    let max_len: usize = tape_length_input.trim().parse().unwrap();
+
+    let mut result = vec![TapeMachineSymbol::SymbolX; max_len];
+    let mut tape_machine = TapeMachine::new(&TapeMachineState::b, &mut result);
+
+    let (trace_level, compact_trace) = trace_settings();
+    for i in 0..steps {
+        tape_machine.trace(i, trace_level, compact_trace);
+
+        step(&mut tape_machine, trace_level, compact_trace);
     }
 
     let binary_result: String = tape_machine.result.iter().map(|x| x.as_str()).collect();
@@ -1,3 +1,6 @@
+// Hand-expanded sketch of the macros, not every helper is used
+#![allow(dead_code, unused_macros)]
+
 #[macro_export]
 macro_rules! states {
     ( $( $x: ident),* ) => {
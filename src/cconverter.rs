@@ -0,0 +1,320 @@
+use crate::escape::quote_list;
+use crate::parser::{Condition, ParseTree, TransitionStep};
+use crate::trace::{TraceFormat, TraceLevel, TraceOptions, TRACE_ENV_VAR, TRACE_FORMAT_ENV_VAR};
+
+// Options for the generated C code
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CCodegenOptions {
+    pub trace: TraceOptions,
+    // Only emit the header-only library, without the program around it
+    pub header_only: bool,
+}
+
+// Trait for converting to a C99 program, or a header-only library
+pub trait ToC {
+    fn to_c(&self) -> String {
+        self.to_c_with(&CCodegenOptions::default())
+    }
+    fn to_c_with(&self, options: &CCodegenOptions) -> String;
+}
+
+impl ToC for ParseTree {
+    fn to_c_with(&self, options: &CCodegenOptions) -> String {
+        if options.header_only {
+            let mut code = String::new();
+            code.push_str("#ifndef TAPE_MACHINE_H\n#define TAPE_MACHINE_H\n\n");
+            code.push_str(&c_library(self));
+            code.push_str("\n#endif\n");
+            return code;
+        }
+
+        let mut code = c_library(self);
+        code.push_str(&c_trace(&options.trace));
+        code.push_str(&c_main());
+        code
+    }
+}
+
+// Types and functions shared by the program and the header-only library:
+//
+//     TapeMachineSymbol tape[100] = {SYMBOL_X};
+//     TapeMachine machine;
+//     tape_machine_init(&machine, tape, 100);
+//     int halt = tape_machine_run(&machine, 1000);
+//
+// Like the generated Rust program the head may move past the ends of the tape,
+// reading or printing there stops the machine with TAPE_MACHINE_OUT_OF_TAPE.
+fn c_library(tree: &ParseTree) -> String {
+    let mut code = String::new();
+    code.push_str(
+        "#include <stddef.h>\n#include <stdio.h>\n#include <stdlib.h>\n#include <string.h>\n\n",
+    );
+
+    code.push_str("typedef enum {\n");
+    for state in &tree.states {
+        code.push_str(&format!("    STATE_{},\n", state));
+    }
+    code.push_str("} TapeMachineState;\n\n");
+    code.push_str("static const char *const STATE_NAMES[] = {");
+    code.push_str(&quote_list(&tree.states));
+    code.push_str("};\n\n");
+
+    code.push_str("typedef enum {\n");
+    for symbol in &tree.symbols {
+        code.push_str(&format!("    SYMBOL_{},\n", symbol));
+    }
+    code.push_str("} TapeMachineSymbol;\n\n");
+    code.push_str("static const char *const SYMBOL_NAMES[] = {");
+    code.push_str(&quote_list(&tree.symbols));
+    code.push_str("};\n\n");
+
+    code.push_str("#define TAPE_MACHINE_OK 0\n");
+    code.push_str("#define TAPE_MACHINE_NO_TRANSITION 1\n");
+    code.push_str("#define TAPE_MACHINE_OUT_OF_TAPE 2\n\n");
+
+    code.push_str("typedef struct {\n");
+    code.push_str("    TapeMachineState state;\n");
+    code.push_str("    TapeMachineSymbol *tape;\n");
+    code.push_str("    size_t len;\n");
+    code.push_str("    size_t index;\n");
+    code.push_str("    size_t steps;\n");
+    code.push_str("} TapeMachine;\n\n");
+
+    code.push_str("static inline void tape_machine_init(TapeMachine *machine, TapeMachineSymbol *tape, size_t len) {\n");
+    code.push_str(&format!(
+        "    machine->state = STATE_{};\n",
+        tree.initial_state
+    ));
+    code.push_str("    machine->tape = tape;\n");
+    code.push_str("    machine->len = len;\n");
+    code.push_str("    machine->index = 0;\n");
+    code.push_str("    machine->steps = 0;\n");
+    code.push_str("}\n\n");
+
+    // The head wraps around below 0 and is then past the end of the tape
+    code.push_str(
+        "static inline int tape_machine_p(TapeMachine *machine, TapeMachineSymbol symbol) {\n",
+    );
+    code.push_str("    if (machine->index >= machine->len) {\n");
+    code.push_str("        return TAPE_MACHINE_OUT_OF_TAPE;\n");
+    code.push_str("    }\n");
+    code.push_str("    machine->tape[machine->index] = symbol;\n");
+    code.push_str("    return TAPE_MACHINE_OK;\n");
+    code.push_str("}\n\n");
+
+    code.push_str("// Apply one transition, returns TAPE_MACHINE_OK or why the machine stopped\n");
+    code.push_str("static inline int tape_machine_step(TapeMachine *machine) {\n");
+    code.push_str("    if (machine->index >= machine->len) {\n");
+    code.push_str("        return TAPE_MACHINE_OUT_OF_TAPE;\n");
+    code.push_str("    }\n");
+    code.push_str("    TapeMachineSymbol symbol = machine->tape[machine->index];\n");
+    code.push_str("    (void)symbol;\n");
+    code.push_str("    switch (machine->state) {\n");
+    for state in &tree.states {
+        code.push_str(&format!("    case STATE_{}:\n", state));
+        code.push_str("        switch (symbol) {\n");
+        let mut has_default = false;
+        // The star transition takes the symbols left, last like a match arm
+        for (index, symbols) in tree.transitions_from(state) {
+            let transition = &tree.transitions[index];
+            if transition.condition == Condition::Star {
+                code.push_str("        default:\n");
+                has_default = true;
+            } else {
+                for symbol in symbols {
+                    code.push_str(&format!("        case SYMBOL_{}:\n", tree.symbols[symbol]));
+                }
+            }
+            for step in &transition.steps {
+                match step {
+                    TransitionStep::R => code.push_str("            machine->index += 1;\n"),
+                    TransitionStep::L => code.push_str("            machine->index -= 1;\n"),
+                    TransitionStep::X => {}
+                    TransitionStep::P(symbol) => code.push_str(&format!(
+                        "            if (tape_machine_p(machine, SYMBOL_{}) != TAPE_MACHINE_OK) return TAPE_MACHINE_OUT_OF_TAPE;\n",
+                        symbol
+                    )),
+                }
+            }
+            code.push_str(&format!(
+                "            machine->state = STATE_{};\n",
+                transition.final_state
+            ));
+            code.push_str("            break;\n");
+        }
+        if !has_default {
+            code.push_str("        default:\n");
+            code.push_str("            return TAPE_MACHINE_NO_TRANSITION;\n");
+        }
+        code.push_str("        }\n");
+        code.push_str("        break;\n");
+    }
+    code.push_str("    }\n");
+    code.push_str("    machine->steps += 1;\n");
+    code.push_str("    return TAPE_MACHINE_OK;\n");
+    code.push_str("}\n\n");
+
+    code.push_str("// Run until the machine stops or `max_steps` steps have been applied\n");
+    code.push_str("static inline int tape_machine_run(TapeMachine *machine, size_t max_steps) {\n");
+    code.push_str("    for (size_t i = 0; i < max_steps; i++) {\n");
+    code.push_str("        int result = tape_machine_step(machine);\n");
+    code.push_str("        if (result != TAPE_MACHINE_OK) {\n");
+    code.push_str("            return result;\n");
+    code.push_str("        }\n");
+    code.push_str("    }\n");
+    code.push_str("    return TAPE_MACHINE_OK;\n");
+    code.push_str("}\n");
+    code
+}
+
+// Trace of every step, same output as the generated Rust program
+fn c_trace(trace: &TraceOptions) -> String {
+    let mut code = String::new();
+    code.push_str("\nenum { TRACE_NONE, TRACE_TRANSITIONS, TRACE_FULL };\n\n");
+
+    code.push_str("static void trace_settings(int *level, int *compact) {\n");
+    code.push_str(&format!(
+        "    *level = {};\n",
+        match trace.level {
+            TraceLevel::None => "TRACE_NONE",
+            TraceLevel::Transitions => "TRACE_TRANSITIONS",
+            TraceLevel::Full => "TRACE_FULL",
+        }
+    ));
+    code.push_str(&format!(
+        "    *compact = {};\n",
+        (trace.format == TraceFormat::Compact) as u8
+    ));
+    code.push_str(&format!(
+        "    const char *env_level = getenv(\"{}\");\n",
+        TRACE_ENV_VAR
+    ));
+    code.push_str("    if (env_level != NULL) {\n");
+    code.push_str("        if (strcmp(env_level, \"none\") == 0) *level = TRACE_NONE;\n");
+    code.push_str(
+        "        else if (strcmp(env_level, \"transitions\") == 0) *level = TRACE_TRANSITIONS;\n",
+    );
+    code.push_str("        else if (strcmp(env_level, \"full\") == 0) *level = TRACE_FULL;\n");
+    code.push_str("    }\n");
+    code.push_str(&format!(
+        "    const char *env_format = getenv(\"{}\");\n",
+        TRACE_FORMAT_ENV_VAR
+    ));
+    code.push_str("    if (env_format != NULL) {\n");
+    code.push_str("        if (strcmp(env_format, \"compact\") == 0) *compact = 1;\n");
+    code.push_str("        else if (strcmp(env_format, \"human\") == 0) *compact = 0;\n");
+    code.push_str("    }\n");
+    code.push_str("}\n\n");
+
+    code.push_str("static void print_tape(const TapeMachine *machine) {\n");
+    code.push_str("    for (size_t i = 0; i < machine->len; i++) {\n");
+    code.push_str("        fputs(SYMBOL_NAMES[machine->tape[i]], stdout);\n");
+    code.push_str("    }\n");
+    code.push_str("}\n\n");
+
+    code.push_str(
+        "static void trace(const TapeMachine *machine, size_t step, int level, int compact) {\n",
+    );
+    code.push_str("    if (level == TRACE_NONE) {\n");
+    code.push_str("        return;\n");
+    code.push_str("    }\n");
+    code.push_str("    const char *state = STATE_NAMES[machine->state];\n");
+    code.push_str("    const char *symbol = SYMBOL_NAMES[machine->tape[machine->index]];\n");
+    code.push_str("    if (compact) {\n");
+    code.push_str(
+        "        printf(\"%zu\\t%s\\t%zu\\t%s\", step, state, machine->index, symbol);\n",
+    );
+    code.push_str("        if (level == TRACE_FULL) {\n");
+    code.push_str("            putchar('\\t');\n");
+    code.push_str("            print_tape(machine);\n");
+    code.push_str("        }\n");
+    code.push_str("        putchar('\\n');\n");
+    code.push_str("    } else {\n");
+    code.push_str(
+        "        printf(\"Step: %zu State: %s Symbol: Symbol%s\\n\", step, state, symbol);\n",
+    );
+    code.push_str("        if (level == TRACE_FULL) {\n");
+    code.push_str("            size_t offset = 0;\n");
+    code.push_str("            for (size_t i = 0; i < machine->index; i++) {\n");
+    code.push_str("                offset += strlen(SYMBOL_NAMES[machine->tape[i]]);\n");
+    code.push_str("            }\n");
+    code.push_str("            print_tape(machine);\n");
+    code.push_str("            printf(\"\\n%*s^\\n\", (int)offset, \"\");\n");
+    code.push_str("        }\n");
+    code.push_str("    }\n");
+    code.push_str("}\n\n");
+    code
+}
+
+// Reads the number of steps and the tape length from stdin like the
+// generated Rust program, and prints the same tape at the end
+fn c_main() -> String {
+    let mut code = String::new();
+    code.push_str("static void out_of_tape(const TapeMachine *machine) {\n");
+    code.push_str(
+        "    fprintf(stderr, \"Head out of the tape at index %zu\\n\", machine->index);\n",
+    );
+    code.push_str("    exit(101);\n");
+    code.push_str("}\n\n");
+
+    code.push_str("int main(void) {\n");
+    code.push_str("    size_t steps, max_len;\n");
+    code.push_str("    printf(\"Enter the number of steps:\\n\");\n");
+    code.push_str("    if (scanf(\"%zu\", &steps) != 1) {\n");
+    code.push_str("        fprintf(stderr, \"Invalid number of steps\\n\");\n");
+    code.push_str("        return 101;\n");
+    code.push_str("    }\n");
+    code.push_str("    printf(\"Enter the total tape length:\\n\");\n");
+    code.push_str("    if (scanf(\"%zu\", &max_len) != 1) {\n");
+    code.push_str("        fprintf(stderr, \"Invalid tape length\\n\");\n");
+    code.push_str("        return 101;\n");
+    code.push_str("    }\n\n");
+
+    code.push_str("    TapeMachineSymbol *tape = malloc((max_len > 0 ? max_len : 1) * sizeof(TapeMachineSymbol));\n");
+    code.push_str("    if (tape == NULL) {\n");
+    code.push_str("        fprintf(stderr, \"Failed to allocate the tape\\n\");\n");
+    code.push_str("        return 101;\n");
+    code.push_str("    }\n");
+    code.push_str("    for (size_t i = 0; i < max_len; i++) {\n");
+    code.push_str("        tape[i] = SYMBOL_X;\n");
+    code.push_str("    }\n");
+    code.push_str("    TapeMachine machine;\n");
+    code.push_str("    tape_machine_init(&machine, tape, max_len);\n\n");
+
+    code.push_str("    int trace_level, compact_trace;\n");
+    code.push_str("    trace_settings(&trace_level, &compact_trace);\n");
+    code.push_str("    for (size_t i = 0; i < steps; i++) {\n");
+    code.push_str("        if (machine.index >= machine.len) {\n");
+    code.push_str("            out_of_tape(&machine);\n");
+    code.push_str("        }\n");
+    code.push_str("        trace(&machine, i, trace_level, compact_trace);\n");
+    code.push_str("        switch (tape_machine_step(&machine)) {\n");
+    code.push_str("        case TAPE_MACHINE_NO_TRANSITION:\n");
+    code.push_str("            printf(\"State: %s Index: %zu Symbol: Symbol%s\\n\", STATE_NAMES[machine.state], machine.index, SYMBOL_NAMES[machine.tape[machine.index]]);\n");
+    code.push_str("            print_tape(&machine);\n");
+    code.push_str("            putchar('\\n');\n");
+    code.push_str("            fflush(stdout);\n");
+    code.push_str("            fprintf(stderr, \"Invalid state reached\\n\");\n");
+    code.push_str("            return 101;\n");
+    code.push_str("        case TAPE_MACHINE_OUT_OF_TAPE:\n");
+    code.push_str("            out_of_tape(&machine);\n");
+    code.push_str("        }\n");
+    code.push_str("        if (trace_level >= TRACE_TRANSITIONS && !compact_trace) {\n");
+    code.push_str("            printf(\"Final State: %s\\n\", STATE_NAMES[machine.state]);\n");
+    code.push_str("        }\n");
+    code.push_str("    }\n\n");
+
+    code.push_str("    print_tape(&machine);\n");
+    code.push_str("    putchar('\\n');\n");
+    code.push_str("    printf(\"=========\\n\\n\");\n");
+    code.push_str("    for (size_t i = 0; i < machine.len; i++) {\n");
+    code.push_str("        if (machine.tape[i] != SYMBOL_X) {\n");
+    code.push_str("            fputs(SYMBOL_NAMES[machine.tape[i]], stdout);\n");
+    code.push_str("        }\n");
+    code.push_str("    }\n");
+    code.push_str("    putchar('\\n');\n");
+    code.push_str("    free(tape);\n");
+    code.push_str("    return 0;\n");
+    code.push_str("}\n");
+    code
+}
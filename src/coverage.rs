@@ -0,0 +1,72 @@
+use crate::interpreter::{Halt, Machine, StepResult};
+use crate::parser::ParseTree;
+
+// Number of times every transition was applied, over one or more runs
+#[derive(Debug, PartialEq, Clone)]
+pub struct Coverage {
+    // Indexed like ParseTree::transitions
+    pub hits: Vec<usize>,
+}
+
+impl Coverage {
+    pub fn new(tree: &ParseTree) -> Self {
+        Coverage {
+            hits: vec![0; tree.transitions.len()],
+        }
+    }
+
+    pub fn record(&mut self, result: StepResult) {
+        if let StepResult::Fired(index) = result {
+            self.hits[index] += 1;
+        }
+    }
+
+    // Run the machine like Machine::run, counting every transition applied
+    pub fn run(&mut self, machine: &mut Machine, max_steps: usize) -> Option<Halt> {
+        for _ in 0..max_steps {
+            match machine.step() {
+                StepResult::Halted(halt) => return Some(halt),
+                result => self.record(result),
+            }
+        }
+        None
+    }
+
+    pub fn covered(&self) -> usize {
+        self.hits.iter().filter(|&&hits| hits > 0).count()
+    }
+
+    // Indices of the transitions which were never applied
+    pub fn uncovered(&self) -> Vec<usize> {
+        (0..self.hits.len())
+            .filter(|&i| self.hits[i] == 0)
+            .collect()
+    }
+
+    pub fn percentage(&self) -> f64 {
+        if self.hits.is_empty() {
+            return 100.0;
+        }
+        100.0 * self.covered() as f64 / self.hits.len() as f64
+    }
+
+    pub fn report(&self, tree: &ParseTree) -> String {
+        let mut report = format!(
+            "Transition coverage: {}/{} ({:.1}%)\n",
+            self.covered(),
+            self.hits.len(),
+            self.percentage()
+        );
+        for (transition, hits) in tree.transitions.iter().zip(&self.hits) {
+            report.push_str(&format!("{:>10}  {}\n", hits, transition));
+        }
+        let uncovered = self.uncovered();
+        if !uncovered.is_empty() {
+            report.push_str("Never applied:\n");
+            for index in uncovered {
+                report.push_str(&format!("  {}\n", tree.transitions[index]));
+            }
+        }
+        report
+    }
+}
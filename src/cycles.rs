@@ -0,0 +1,267 @@
+use std::fmt;
+
+use crate::interpreter::{Action, Halt, Machine, StepResult};
+
+// Why a machine provably never halts
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NonHalting {
+    // The configuration of step `start` comes back every `length` steps
+    Cycle {
+        start: usize,
+        length: usize,
+    },
+    // From step `start` the machine does the same every `length` steps,
+    // `shift` cells further right on a tape which is blank there
+    TranslatedCycle {
+        start: usize,
+        length: usize,
+        shift: usize,
+    },
+}
+
+impl NonHalting {
+    pub fn length(&self) -> usize {
+        match *self {
+            NonHalting::Cycle { length, .. } | NonHalting::TranslatedCycle { length, .. } => length,
+        }
+    }
+}
+
+impl fmt::Display for NonHalting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            NonHalting::Cycle { start, length } => write!(
+                f,
+                "the configuration of step {} comes back every {} steps",
+                start, length
+            ),
+            NonHalting::TranslatedCycle {
+                start,
+                length,
+                shift,
+            } => write!(
+                f,
+                "from step {} the machine repeats every {} steps {} cells further right",
+                start, length, shift
+            ),
+        }
+    }
+}
+
+// How a run with cycle detection ended
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Verdict {
+    Halted(Halt),
+    NonHalting(NonHalting),
+    // Stopped after the maximum number of steps without a proof either way
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+struct Saved {
+    state: usize,
+    head: usize,
+    hash: u64,
+    tape: Vec<usize>,
+    steps: usize,
+}
+
+// Watches the steps of a machine for a configuration coming back, or for the
+// machine repeating itself further right at the end of the tape. Both use
+// Brent's algorithm: a configuration is saved after 1, 2, 4, 8... steps (or
+// new rightmost cells), and every later one is compared to it, so a cycle is
+// found within a few times its length once the machine is in it.
+#[derive(Debug, Clone)]
+pub struct CycleDetector {
+    blank: usize,
+    symbols: usize,
+    // Hash of the non blank cells of the tape, updated on every print
+    hash: u64,
+    saved: Saved,
+    power: usize,
+    since_saved: usize,
+    // Cells from there on were blank on the initial tape
+    blank_from: usize,
+    saved_record: Option<Saved>,
+    record_power: usize,
+    records_since_saved: usize,
+    // Leftmost cell visited since `saved_record`
+    min_head: usize,
+}
+
+impl CycleDetector {
+    pub fn new(machine: &Machine) -> Self {
+        let blank = machine.blank();
+        let symbols = machine.tree.symbols.len();
+        let hash = machine
+            .tape
+            .iter()
+            .enumerate()
+            .map(|(cell, &symbol)| cell_hash(symbols, blank, cell, symbol))
+            .fold(0, |hash, cell| hash ^ cell);
+        let blank_from = machine
+            .tape
+            .iter()
+            .rposition(|&symbol| symbol != blank)
+            .map_or(0, |cell| cell + 1);
+        let mut detector = CycleDetector {
+            blank,
+            symbols,
+            hash,
+            saved: Saved {
+                state: 0,
+                head: 0,
+                hash: 0,
+                tape: Vec::new(),
+                steps: 0,
+            },
+            power: 1,
+            since_saved: 0,
+            blank_from,
+            saved_record: None,
+            record_power: 1,
+            records_since_saved: 0,
+            min_head: machine.head,
+        };
+        detector.saved = detector.save(machine);
+        detector
+    }
+
+    // Apply one step of `machine`, and tell whether it provably never halts
+    pub fn step(&mut self, machine: &mut Machine) -> (StepResult, Option<NonHalting>) {
+        // Cells printed by the step, with the symbol they held
+        let mut printed = Vec::new();
+        let mut min_head = machine.head;
+        if let Some(index) = machine.next_transition() {
+            let mut head = machine.head as isize;
+            for action in machine.actions(index) {
+                match action {
+                    Action::R => head += 1,
+                    Action::L => head -= 1,
+                    Action::P(_) if head >= 0 => {
+                        let cell = head as usize;
+                        printed.push((cell, machine.tape.get(cell).copied().unwrap_or(self.blank)))
+                    }
+                    Action::P(_) => {}
+                }
+                min_head = min_head.min(head.max(0) as usize);
+            }
+        }
+        let tape_len = machine.tape.len();
+        let result = machine.step();
+        if let StepResult::Halted(_) = result {
+            return (result, None);
+        }
+
+        // The same cell may be printed several times in one step
+        printed.sort_unstable();
+        printed.dedup_by_key(|(cell, _)| *cell);
+        for (cell, old) in printed {
+            self.hash ^= cell_hash(self.symbols, self.blank, cell, old)
+                ^ cell_hash(self.symbols, self.blank, cell, machine.tape[cell]);
+        }
+        self.min_head = self.min_head.min(min_head);
+
+        let non_halting = self.check_cycle(machine).or_else(|| {
+            // Nothing right of the head was ever visited, so it is all blank
+            let record = machine.tape.len() > tape_len
+                && machine.head + 1 == machine.tape.len()
+                && machine.head >= self.blank_from;
+            record.then(|| self.check_record(machine)).flatten()
+        });
+        (result, non_halting)
+    }
+
+    // Run the machine like Machine::run until it halts, provably never halts
+    // or `max_steps` steps have been applied
+    pub fn run(&mut self, machine: &mut Machine, max_steps: usize) -> Verdict {
+        for _ in 0..max_steps {
+            match self.step(machine) {
+                (StepResult::Halted(halt), _) => return Verdict::Halted(halt),
+                (_, Some(non_halting)) => return Verdict::NonHalting(non_halting),
+                _ => {}
+            }
+        }
+        Verdict::Unknown
+    }
+
+    fn save(&self, machine: &Machine) -> Saved {
+        Saved {
+            state: machine.state,
+            head: machine.head,
+            hash: self.hash,
+            tape: machine.tape.clone(),
+            steps: machine.steps,
+        }
+    }
+
+    fn check_cycle(&mut self, machine: &Machine) -> Option<NonHalting> {
+        let saved = &self.saved;
+        if saved.state == machine.state
+            && saved.head == machine.head
+            && saved.hash == self.hash
+            && same_cells(&saved.tape, &machine.tape, self.blank)
+        {
+            return Some(NonHalting::Cycle {
+                start: saved.steps,
+                length: machine.steps - saved.steps,
+            });
+        }
+        self.since_saved += 1;
+        if self.since_saved == self.power {
+            self.saved = self.save(machine);
+            self.power *= 2;
+            self.since_saved = 0;
+        }
+        None
+    }
+
+    // The head is on a new rightmost cell. The machine repeats itself if it
+    // was in the same state on an earlier rightmost cell, and the cells from
+    // the leftmost one visited since then up to the head are the same, moved
+    // by the distance between the two.
+    fn check_record(&mut self, machine: &Machine) -> Option<NonHalting> {
+        if let Some(saved) = &self.saved_record {
+            let shift = machine.head - saved.head;
+            if saved.state == machine.state
+                && saved.tape[self.min_head..] == machine.tape[self.min_head + shift..]
+            {
+                return Some(NonHalting::TranslatedCycle {
+                    start: saved.steps,
+                    length: machine.steps - saved.steps,
+                    shift,
+                });
+            }
+        }
+        self.records_since_saved += 1;
+        if self.saved_record.is_none() || self.records_since_saved == self.record_power {
+            self.saved_record = Some(self.save(machine));
+            self.min_head = machine.head;
+            self.record_power *= 2;
+            self.records_since_saved = 0;
+        }
+        None
+    }
+}
+
+// Tapes holding the same symbols, the shorter one followed by blanks
+fn same_cells(a: &[usize], b: &[usize], blank: usize) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    long[..short.len()] == *short && long[short.len()..].iter().all(|&s| s == blank)
+}
+
+// Blank cells leave the hash of a tape unchanged, so it does not depend on
+// how far the tape was extended
+fn cell_hash(symbols: usize, blank: usize, cell: usize, symbol: usize) -> u64 {
+    if symbol == blank {
+        return 0;
+    }
+    // splitmix64
+    let mut x = (cell as u64)
+        .wrapping_mul(symbols as u64)
+        .wrapping_add(symbol as u64)
+        .wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+
+use crate::interpreter::{Configuration, Halt, Machine, StepResult};
+use crate::parser::ParseTree;
+
+// Number of configurations kept for reverse stepping
+const HISTORY_LIMIT: usize = 10_000;
+
+const HELP: &str = "\
+Commands:
+  step [n]            (s)  apply the next n transitions
+  reverse [n]         (r)  undo the last n transitions
+  continue            (c)  run until a breakpoint or until the machine halts
+  break <state> [sym] (b)  stop when entering a state, or a state reading a symbol
+  delete <n>          (d)  remove breakpoint n
+  info                (i)  list breakpoints
+  print               (p)  show the current configuration
+  help                (h)  show this message
+  quit                (q)  exit the debugger
+An empty line repeats the last command.
+Commands are read one line at a time from stdin, without line editing or
+history, so a script can be piped in.";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Breakpoint {
+    State(usize),
+    StateSymbol(usize, usize),
+}
+
+// Interactive debugger stepping through a machine with the interpreter
+pub struct Debugger<'a> {
+    machine: Machine<'a>,
+    breakpoints: Vec<Breakpoint>,
+    history: VecDeque<Configuration>,
+    // Number of cells shown on each side of the head
+    window: usize,
+    // Steps after which `continue` gives up
+    max_steps: usize,
+    last_command: String,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(machine: Machine<'a>, window: usize, max_steps: usize) -> Self {
+        Debugger {
+            machine,
+            breakpoints: Vec::new(),
+            history: VecDeque::new(),
+            window,
+            max_steps,
+            last_command: String::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<'a> {
+        &self.machine
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    // Read commands from `input` until `quit` or end of input
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> std::io::Result<()> {
+        writeln!(output, "{}", self.view())?;
+        write!(output, "(tmdb) ")?;
+        output.flush()?;
+        for line in input.lines() {
+            match self.execute(&line?) {
+                Some(response) => writeln!(output, "{}", response)?,
+                None => return Ok(()),
+            }
+            write!(output, "(tmdb) ")?;
+            output.flush()?;
+        }
+        writeln!(output)?;
+        Ok(())
+    }
+
+    // Execute a single command, returning what to show, or None on quit
+    pub fn execute(&mut self, line: &str) -> Option<String> {
+        let line = if line.trim().is_empty() {
+            self.last_command.clone()
+        } else {
+            line.trim().to_string()
+        };
+        self.last_command = line.clone();
+
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let response = match command {
+            "" => String::new(),
+            "s" | "step" => match count_argument(&args) {
+                Ok(count) => self.step(count),
+                Err(e) => e,
+            },
+            "r" | "reverse" => match count_argument(&args) {
+                Ok(count) => self.reverse(count),
+                Err(e) => e,
+            },
+            "c" | "continue" => self.continue_run(),
+            "b" | "break" => self.add_breakpoint(&args),
+            "d" | "delete" => self.delete_breakpoint(&args),
+            "i" | "info" => self.list_breakpoints(),
+            "p" | "print" => self.view(),
+            "h" | "help" => HELP.to_string(),
+            "q" | "quit" => return None,
+            _ => format!(
+                "Unknown command: {}. Type `help` for a list of commands.",
+                command
+            ),
+        };
+        Some(response)
+    }
+
+    // Current step, state, the tape around the head and the next transition
+    pub fn view(&self) -> String {
+        let machine = &self.machine;
+        let first = machine.head.saturating_sub(self.window);
+        let last = machine.head + self.window;
+        let mut cells = Vec::new();
+        if first > 0 {
+            cells.push("..".to_string());
+        }
+        for cell in first..=last {
+            let symbol = machine.tape.get(cell).copied().unwrap_or(machine.blank());
+            let name = &machine.tree.symbols[symbol];
+            if cell == machine.head {
+                cells.push(format!("[{}]", name));
+            } else {
+                cells.push(name.to_string());
+            }
+        }
+        cells.push("..".to_string());
+
+        let next = match machine.next_transition() {
+            Some(index) => machine.tree.transitions[index].to_string(),
+            None => "none, the machine halts".to_string(),
+        };
+        format!(
+            "Step: {} State: {} Head: {}\nTape: {}\nNext: {}",
+            machine.steps,
+            machine.state_name(),
+            machine.head,
+            cells.join(" "),
+            next
+        )
+    }
+
+    // Apply one transition, recording the configuration before it
+    fn single_step(&mut self) -> StepResult {
+        let before = self.machine.configuration();
+        let result = self.machine.step();
+        if let StepResult::Fired(_) = result {
+            if self.history.len() == HISTORY_LIMIT {
+                self.history.pop_front();
+            }
+            self.history.push_back(before);
+        }
+        result
+    }
+
+    fn step(&mut self, count: usize) -> String {
+        for _ in 0..count {
+            if let StepResult::Halted(halt) = self.single_step() {
+                return format!("{}\n{}", self.halt_message(halt), self.view());
+            }
+        }
+        self.view()
+    }
+
+    fn reverse(&mut self, count: usize) -> String {
+        for _ in 0..count {
+            match self.history.pop_back() {
+                Some(configuration) => self.machine.restore(configuration),
+                None => return format!("No more history to reverse.\n{}", self.view()),
+            }
+        }
+        self.view()
+    }
+
+    fn continue_run(&mut self) -> String {
+        for _ in 0..self.max_steps {
+            if let StepResult::Halted(halt) = self.single_step() {
+                return format!("{}\n{}", self.halt_message(halt), self.view());
+            }
+            if let Some(number) = self.hit_breakpoint() {
+                return format!("Breakpoint {} hit.\n{}", number, self.view());
+            }
+        }
+        format!(
+            "Stopped after {} steps without reaching a breakpoint.\n{}",
+            self.max_steps,
+            self.view()
+        )
+    }
+
+    // Number (1-based) of the first breakpoint matching the current configuration
+    fn hit_breakpoint(&self) -> Option<usize> {
+        let state = self.machine.state;
+        let symbol = self.machine.symbol();
+        self.breakpoints
+            .iter()
+            .position(|breakpoint| match *breakpoint {
+                Breakpoint::State(s) => s == state,
+                Breakpoint::StateSymbol(s, sym) => s == state && sym == symbol,
+            })
+            .map(|i| i + 1)
+    }
+
+    fn add_breakpoint(&mut self, args: &[&str]) -> String {
+        let tree = self.machine.tree;
+        let state = match args.first() {
+            Some(name) => match tree.states.iter().position(|s| s == name) {
+                Some(state) => state,
+                None => return format!("State {} not defined.", name),
+            },
+            None => return "Usage: break <state> [symbol]".to_string(),
+        };
+        let breakpoint = match args.get(1) {
+            Some(name) => match tree.symbols.iter().position(|s| s == name) {
+                Some(symbol) => Breakpoint::StateSymbol(state, symbol),
+                None => return format!("Symbol {} not defined.", name),
+            },
+            None => Breakpoint::State(state),
+        };
+        self.breakpoints.push(breakpoint);
+        format!(
+            "Breakpoint {}: {}",
+            self.breakpoints.len(),
+            describe_breakpoint(tree, &breakpoint)
+        )
+    }
+
+    fn delete_breakpoint(&mut self, args: &[&str]) -> String {
+        match args.first().and_then(|n| n.parse::<usize>().ok()) {
+            Some(number) if number >= 1 && number <= self.breakpoints.len() => {
+                self.breakpoints.remove(number - 1);
+                format!("Deleted breakpoint {}.", number)
+            }
+            _ => "Usage: delete <breakpoint number>".to_string(),
+        }
+    }
+
+    fn list_breakpoints(&self) -> String {
+        if self.breakpoints.is_empty() {
+            return "No breakpoints.".to_string();
+        }
+        self.breakpoints
+            .iter()
+            .enumerate()
+            .map(|(i, breakpoint)| {
+                format!(
+                    "{}: {}",
+                    i + 1,
+                    describe_breakpoint(self.machine.tree, breakpoint)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn halt_message(&self, halt: Halt) -> String {
+        match halt {
+            Halt::NoTransition => format!(
+                "Machine halted: no transition for state {} and symbol {}.",
+                self.machine.state_name(),
+                self.machine.symbol_name()
+            ),
+            Halt::LeftEdge => {
+                "Machine halted: the head would move left of the first cell.".to_string()
+            }
+        }
+    }
+}
+
+fn describe_breakpoint(tree: &ParseTree, breakpoint: &Breakpoint) -> String {
+    match *breakpoint {
+        Breakpoint::State(state) => format!("state {}", tree.states[state]),
+        Breakpoint::StateSymbol(state, symbol) => format!(
+            "state {} reading {}",
+            tree.states[state], tree.symbols[symbol]
+        ),
+    }
+}
+
+fn count_argument(args: &[&str]) -> Result<usize, String> {
+    match args.first() {
+        Some(count) => count
+            .parse::<usize>()
+            .map_err(|_| format!("Expected a number of steps, got {}", count)),
+        None => Ok(1),
+    }
+}
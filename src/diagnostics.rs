@@ -0,0 +1,184 @@
+use std::str::FromStr;
+
+use crate::escape::json_escape;
+
+// Location of a token in the source, 1-based like rustc
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, len: usize) -> Self {
+        Span { line, column, len }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!("Unknown message format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: &str, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.to_string(),
+            span,
+            help: None,
+        }
+    }
+
+    pub fn warning(message: &str, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.to_string(),
+            span,
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: Option<String>) -> Self {
+        self.help = help;
+        self
+    }
+
+    // Render in the rustc style:
+    //
+    // error: Symbol Y not defined, So cannot be printed.
+    //  --> machine.txt:7:19
+    //   |
+    // 7 | A, 0 | 1, R-L-L-P(Y)-L, B
+    //   |                   ^
+    //   = help: did you mean symbol `1`?
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let line_number = self.span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let source_line = source.lines().nth(self.span.line - 1).unwrap_or("");
+
+        let mut out = format!("{}: {}\n", self.severity.as_str(), self.message);
+        out.push_str(&format!(
+            "{}--> {}:{}:{}\n",
+            gutter, file_name, self.span.line, self.span.column
+        ));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", line_number, source_line));
+        out.push_str(&format!(
+            "{} | {}{}\n",
+            gutter,
+            " ".repeat(self.span.column - 1),
+            "^".repeat(self.span.len.max(1))
+        ));
+        if let Some(help) = &self.help {
+            out.push_str(&format!("{} = help: {}\n", gutter, help));
+        }
+        out
+    }
+
+    // Render as a single line JSON object for tooling
+    pub fn to_json(&self, file_name: &str) -> String {
+        let help = match &self.help {
+            Some(help) => format!("\"{}\"", json_escape(help)),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"severity\":\"{}\",\"message\":\"{}\",\"file\":\"{}\",\"line\":{},\"column\":{},\"length\":{},\"help\":{}}}",
+            self.severity.as_str(),
+            json_escape(&self.message),
+            json_escape(file_name),
+            self.span.line,
+            self.span.column,
+            self.span.len,
+            help
+        )
+    }
+}
+
+// Where diagnostics are written to when running from the command line
+#[derive(Debug, PartialEq, Clone)]
+pub struct Emitter {
+    pub file_name: String,
+    pub format: MessageFormat,
+}
+
+impl Emitter {
+    pub fn new(file_name: &str, format: MessageFormat) -> Self {
+        Emitter {
+            file_name: file_name.to_string(),
+            format,
+        }
+    }
+
+    pub fn emit(&self, diagnostic: &Diagnostic, source: &str) {
+        match self.format {
+            MessageFormat::Human => eprintln!("{}", diagnostic.render(&self.file_name, source)),
+            MessageFormat::Json => eprintln!("{}", diagnostic.to_json(&self.file_name)),
+        }
+    }
+}
+
+// Levenshtein distance between two identifiers
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + substitution);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// Closest candidate to `name`, if any is close enough to be a likely typo
+pub fn closest_match<'a>(name: &str, candidates: &'a [String]) -> Option<&'a String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
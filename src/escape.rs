@@ -0,0 +1,92 @@
+// Escaping of state and symbol names for the generated code and documents
+
+// `text` as a double quoted string literal of C, Python or JavaScript
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// The names quoted and separated by commas: `"a", "b"`
+pub fn quote_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| quote(name))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// Escape a string to be used between double quotes in XML, SVG or GraphML
+pub fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Escape a string to be used between double quotes in a dot file
+pub fn dot_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Escape a string to be used between double quotes in JSON
+pub fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Escape the text of a Mermaid label or state description. `"`, `|` and `-`
+// end or split them otherwise, Mermaid renders the entity codes as the
+// characters.
+pub fn mermaid_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '#' => escaped.push_str("#35;"),
+            '"' => escaped.push_str("#quot;"),
+            '|' => escaped.push_str("#124;"),
+            '-' => escaped.push_str("#45;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Escape the text of a PlantUML label or state name with `~`, which makes
+// PlantUML take the next character as is instead of as markup
+pub fn plantuml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '~' | '"' | '|' | '-') {
+            escaped.push('~');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
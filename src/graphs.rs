@@ -0,0 +1,176 @@
+use std::str::FromStr;
+
+use crate::escape::{mermaid_escape, plantuml_escape, xml_escape};
+use crate::parser::ParseTree;
+
+// Format of the state diagram written next to the generated code
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    PlantUml,
+    GraphMl,
+}
+
+impl GraphFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::Mermaid => "mmd",
+            GraphFormat::PlantUml => "puml",
+            GraphFormat::GraphMl => "graphml",
+        }
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            "plantuml" => Ok(GraphFormat::PlantUml),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            _ => Err(format!("Unknown graph format: {}", s)),
+        }
+    }
+}
+
+// Names of the states of the Mermaid and PlantUML diagrams, with the
+// identifier they are drawn with: the name itself when it is a plain
+// identifier, otherwise `state_<n>` declared with the quoted name. States only
+// reached by a transition come after the declared ones.
+fn diagram_states(tree: &ParseTree) -> Vec<(&str, String)> {
+    let mut names: Vec<&str> = Vec::new();
+    let used = tree
+        .transitions
+        .iter()
+        .flat_map(|t| [t.initial_state.as_str(), t.final_state.as_str()]);
+    for name in tree.states.iter().map(|s| s.as_str()).chain(used) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                (name, name.to_string())
+            } else {
+                (name, format!("state_{}", index))
+            }
+        })
+        .collect()
+}
+
+fn diagram_id<'a>(states: &'a [(&str, String)], name: &str) -> &'a str {
+    states
+        .iter()
+        .find(|(state, _)| *state == name)
+        .map(|(_, id)| id.as_str())
+        .unwrap_or_default()
+}
+
+// Mermaid state diagram, rendered by most Markdown viewers
+pub trait ToMermaid {
+    fn to_mermaid(&self) -> String;
+}
+
+impl ToMermaid for ParseTree {
+    fn to_mermaid(&self) -> String {
+        let states = diagram_states(self);
+        let mut mermaid = String::from("stateDiagram-v2\n");
+        for (name, id) in &states {
+            if name != id {
+                mermaid.push_str(&format!(
+                    "    state \"{}\" as {}\n",
+                    mermaid_escape(name),
+                    id
+                ));
+            }
+        }
+        mermaid.push_str(&format!(
+            "    [*] --> {}\n",
+            diagram_id(&states, &self.initial_state)
+        ));
+        for transition in &self.transitions {
+            mermaid.push_str(&format!(
+                "    {} --> {} : {}\n",
+                diagram_id(&states, &transition.initial_state),
+                diagram_id(&states, &transition.final_state),
+                mermaid_escape(&transition.label())
+            ));
+        }
+        mermaid
+    }
+}
+
+pub trait ToPlantUml {
+    fn to_plantuml(&self) -> String;
+}
+
+impl ToPlantUml for ParseTree {
+    fn to_plantuml(&self) -> String {
+        let states = diagram_states(self);
+        let mut plantuml = String::from("@startuml\n");
+        for (name, id) in &states {
+            if name != id {
+                plantuml.push_str(&format!("state \"{}\" as {}\n", plantuml_escape(name), id));
+            } else if self.states.iter().any(|state| state == name) {
+                plantuml.push_str(&format!("state {}\n", id));
+            }
+        }
+        plantuml.push_str(&format!(
+            "[*] --> {}\n",
+            diagram_id(&states, &self.initial_state)
+        ));
+        for transition in &self.transitions {
+            plantuml.push_str(&format!(
+                "{} --> {} : {}\n",
+                diagram_id(&states, &transition.initial_state),
+                diagram_id(&states, &transition.final_state),
+                plantuml_escape(&transition.label())
+            ));
+        }
+        plantuml.push_str("@enduml\n");
+        plantuml
+    }
+}
+
+// GraphML for graph editors like yEd or Gephi, the labels are stored as
+// `label` data on the edges and the initial state is flagged on its node
+pub trait ToGraphMl {
+    fn to_graphml(&self) -> String;
+}
+
+impl ToGraphMl for ParseTree {
+    fn to_graphml(&self) -> String {
+        let mut graphml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">
+  <key id=\"initial\" for=\"node\" attr.name=\"initial\" attr.type=\"boolean\"/>
+  <key id=\"label\" for=\"edge\" attr.name=\"label\" attr.type=\"string\"/>
+  <graph id=\"state_machine\" edgedefault=\"directed\">
+",
+        );
+        for state in &self.states {
+            graphml.push_str(&format!(
+                "    <node id=\"{}\"><data key=\"initial\">{}</data></node>\n",
+                xml_escape(state),
+                state == &self.initial_state
+            ));
+        }
+        for (index, transition) in self.transitions.iter().enumerate() {
+            graphml.push_str(&format!(
+                "    <edge id=\"t{}\" source=\"{}\" target=\"{}\"><data key=\"label\">{}</data></edge>\n",
+                index,
+                xml_escape(&transition.initial_state),
+                xml_escape(&transition.final_state),
+                xml_escape(&transition.label())
+            ));
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+}
@@ -0,0 +1,264 @@
+use crate::parser::{ParseTree, TransitionStep};
+
+// Why the machine stopped
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Halt {
+    // No transition for the current state and symbol
+    NoTransition,
+    // The transition would move the head left of the first cell
+    LeftEdge,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StepResult {
+    // Index of the transition in ParseTree::transitions which was applied
+    Fired(usize),
+    Halted(Halt),
+}
+
+// Everything needed to resume a run: the tape starts at cell 0 and is
+// extended with blanks (X) to the right whenever the head moves past its end
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Configuration {
+    pub state: usize,
+    pub head: usize,
+    pub tape: Vec<usize>,
+    pub steps: usize,
+}
+
+// A transition step with the symbol resolved to its index
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Action {
+    R,
+    L,
+    P(usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct CompiledTransition {
+    actions: Vec<Action>,
+    final_state: usize,
+}
+
+// Reference interpreter for a ParseTree, following the semantics of the
+// generated code
+#[derive(Debug, Clone)]
+pub struct Machine<'a> {
+    pub tree: &'a ParseTree,
+    // Indexed like ParseTree::transitions, None if it uses an undeclared state
+    transitions: Vec<Option<CompiledTransition>>,
+    // state * symbols + symbol -> index of the transition to apply
+    table: Vec<Option<usize>>,
+    blank: usize,
+    pub state: usize,
+    pub head: usize,
+    pub tape: Vec<usize>,
+    pub steps: usize,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(tree: &'a ParseTree) -> Self {
+        Machine::with_tape(tree, Vec::new())
+    }
+
+    pub fn with_tape(tree: &'a ParseTree, mut tape: Vec<usize>) -> Self {
+        let state_index = |name: &str| tree.states.iter().position(|s| s == name);
+        let symbol_index = |name: &str| tree.symbols.iter().position(|s| s == name);
+        let blank = symbol_index("X").unwrap();
+
+        let transitions = tree
+            .transitions
+            .iter()
+            .map(|transition| {
+                let final_state = state_index(&transition.final_state)?;
+                let actions = transition
+                    .steps
+                    .iter()
+                    .filter_map(|step| match step {
+                        TransitionStep::R => Some(Action::R),
+                        TransitionStep::L => Some(Action::L),
+                        TransitionStep::X => None,
+                        TransitionStep::P(symbol) => symbol_index(symbol).map(Action::P),
+                    })
+                    .collect();
+                Some(CompiledTransition {
+                    actions,
+                    final_state,
+                })
+            })
+            .collect::<Vec<Option<CompiledTransition>>>();
+
+        let mut table = Vec::with_capacity(tree.states.len() * tree.symbols.len());
+        for state in &tree.states {
+            for symbol in &tree.symbols {
+                table.push(
+                    tree.transition_for(state, symbol)
+                        .filter(|&t| transitions[t].is_some()),
+                );
+            }
+        }
+
+        if tape.is_empty() {
+            tape.push(blank);
+        }
+        Machine {
+            tree,
+            transitions,
+            table,
+            blank,
+            state: state_index(&tree.initial_state).unwrap(),
+            head: 0,
+            tape,
+            steps: 0,
+        }
+    }
+
+    pub fn from_configuration(tree: &'a ParseTree, configuration: Configuration) -> Self {
+        let mut machine = Machine::new(tree);
+        machine.restore(configuration);
+        machine
+    }
+
+    pub fn configuration(&self) -> Configuration {
+        Configuration {
+            state: self.state,
+            head: self.head,
+            tape: self.tape.clone(),
+            steps: self.steps,
+        }
+    }
+
+    pub fn restore(&mut self, configuration: Configuration) {
+        self.state = configuration.state;
+        self.head = configuration.head;
+        self.tape = configuration.tape;
+        self.steps = configuration.steps;
+        if self.tape.len() <= self.head {
+            self.tape.resize(self.head + 1, self.blank);
+        }
+    }
+
+    pub fn blank(&self) -> usize {
+        self.blank
+    }
+
+    // Symbol under the head
+    pub fn symbol(&self) -> usize {
+        self.tape[self.head]
+    }
+
+    pub fn state_name(&self) -> &str {
+        &self.tree.states[self.state]
+    }
+
+    pub fn symbol_name(&self) -> &str {
+        &self.tree.symbols[self.symbol()]
+    }
+
+    // Index of the transition the next step will apply
+    pub fn next_transition(&self) -> Option<usize> {
+        self.transition_at(self.state, self.symbol())
+    }
+
+    pub fn transition_at(&self, state: usize, symbol: usize) -> Option<usize> {
+        self.table[state * self.tree.symbols.len() + symbol]
+    }
+
+    // Moves and prints of a transition returned by `transition_at`
+    pub(crate) fn actions(&self, index: usize) -> &[Action] {
+        &self.transitions[index].as_ref().unwrap().actions
+    }
+
+    pub(crate) fn final_state(&self, index: usize) -> usize {
+        self.transitions[index].as_ref().unwrap().final_state
+    }
+
+    // Apply one transition. The configuration is left untouched if the
+    // machine halts.
+    pub fn step(&mut self) -> StepResult {
+        let Some(index) = self.next_transition() else {
+            return StepResult::Halted(Halt::NoTransition);
+        };
+        let transition = self.transitions[index].as_ref().unwrap();
+
+        // Check the head never leaves the tape before changing anything
+        let mut head = self.head;
+        for action in &transition.actions {
+            match action {
+                Action::L if head == 0 => return StepResult::Halted(Halt::LeftEdge),
+                Action::L => head -= 1,
+                Action::R => head += 1,
+                Action::P(_) => {}
+            }
+        }
+
+        for action in &transition.actions {
+            match action {
+                Action::R => {
+                    self.head += 1;
+                    if self.head == self.tape.len() {
+                        self.tape.push(self.blank);
+                    }
+                }
+                Action::L => self.head -= 1,
+                Action::P(symbol) => self.tape[self.head] = *symbol,
+            }
+        }
+        self.state = transition.final_state;
+        self.steps += 1;
+        StepResult::Fired(index)
+    }
+
+    // Run until the machine halts or `max_steps` steps have been applied
+    pub fn run(&mut self, max_steps: usize) -> Option<Halt> {
+        for _ in 0..max_steps {
+            if let StepResult::Halted(halt) = self.step() {
+                return Some(halt);
+            }
+        }
+        None
+    }
+
+    // The whole tape, same as `binary_result` in the generated code
+    pub fn tape_string(&self) -> String {
+        self.tape
+            .iter()
+            .map(|&s| self.tree.symbols[s].as_str())
+            .collect()
+    }
+
+    // The tape without blanks, same as `clean_result` in the generated code
+    pub fn clean_tape_string(&self) -> String {
+        self.tape
+            .iter()
+            .filter(|&&s| s != self.blank)
+            .map(|&s| self.tree.symbols[s].as_str())
+            .collect()
+    }
+}
+
+// Parse an initial tape. Symbols are separated by commas, or given one
+// character each if every symbol of the machine is a single character.
+pub fn parse_tape(tree: &ParseTree, input: &str) -> Result<Vec<usize>, String> {
+    let names: Vec<String> = if input.contains(',') {
+        input.split(',').map(|s| s.trim().to_string()).collect()
+    } else if tree.symbols.iter().all(|s| s.chars().count() == 1) {
+        input
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_string())
+            .collect()
+    } else {
+        input.split_whitespace().map(|s| s.to_string()).collect()
+    };
+    names
+        .iter()
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            tree.symbols
+                .iter()
+                .position(|s| s == name)
+                .ok_or(format!("Symbol {} not defined.", name))
+        })
+        .collect()
+}
@@ -0,0 +1,160 @@
+use crate::escape::{quote, quote_list};
+use crate::parser::{Condition, ParseTree, TransitionStep};
+
+// Trait for converting to a self-contained JavaScript module with the same
+// `TapeMachine` API as `ParseTree::to_rust_library`:
+//
+//     import { TapeMachine } from "./tape_machine.mjs";
+//     const machine = new TapeMachine("0110");
+//     const halt = machine.run(1000);
+//     console.log(halt, machine.state, machine.tapeString());
+pub trait ToJavaScript {
+    fn to_javascript(&self) -> String;
+}
+
+impl ToJavaScript for ParseTree {
+    fn to_javascript(&self) -> String {
+        let mut code = String::new();
+        code.push_str("// Generated by state_machine_compiler_rust, do not edit\n\n");
+        code.push_str(&format!(
+            "export const STATES = Object.freeze([{}]);\n",
+            quote_list(&self.states)
+        ));
+        code.push_str(&format!(
+            "export const SYMBOLS = Object.freeze([{}]);\n",
+            quote_list(&self.symbols)
+        ));
+        code.push_str("export const BLANK = \"X\";\n\n");
+
+        code.push_str("// Why the machine stopped\n");
+        code.push_str("export const Halt = Object.freeze({\n");
+        code.push_str("  // No transition for the current state and symbol\n");
+        code.push_str("  NO_TRANSITION: \"NoTransition\",\n");
+        code.push_str("  // The transition would move the head left of the first cell\n");
+        code.push_str("  LEFT_EDGE: \"LeftEdge\",\n");
+        code.push_str("});\n\n");
+
+        code.push_str("export class TapeMachine {\n");
+        code.push_str("  #state;\n");
+        code.push_str("  #tape;\n");
+        code.push_str("  #head = 0;\n");
+        code.push_str("  #steps = 0;\n\n");
+        code.push_str("  // A string of one character symbols, or any iterable of symbols\n");
+        code.push_str("  constructor(tape = []) {\n");
+        code.push_str("    this.#tape = Array.from(tape);\n");
+        code.push_str("    if (this.#tape.length === 0) {\n");
+        code.push_str("      this.#tape.push(BLANK);\n");
+        code.push_str("    }\n");
+        code.push_str("    for (const symbol of this.#tape) {\n");
+        code.push_str("      if (!SYMBOLS.includes(symbol)) {\n");
+        code.push_str("        throw new Error(`Unknown symbol: ${symbol}`);\n");
+        code.push_str("      }\n");
+        code.push_str("    }\n");
+        code.push_str(&format!(
+            "    this.#state = {};\n",
+            quote(&self.initial_state)
+        ));
+        code.push_str("  }\n\n");
+
+        code.push_str("  get state() {\n");
+        code.push_str("    return this.#state;\n");
+        code.push_str("  }\n\n");
+        code.push_str("  get tape() {\n");
+        code.push_str("    return [...this.#tape];\n");
+        code.push_str("  }\n\n");
+        code.push_str("  get head() {\n");
+        code.push_str("    return this.#head;\n");
+        code.push_str("  }\n\n");
+        code.push_str("  get steps() {\n");
+        code.push_str("    return this.#steps;\n");
+        code.push_str("  }\n\n");
+        code.push_str("  tapeString() {\n");
+        code.push_str("    return this.#tape.join(\"\");\n");
+        code.push_str("  }\n\n");
+
+        code.push_str("  #p(symbol) {\n");
+        code.push_str("    this.#tape[this.#head] = symbol;\n");
+        code.push_str("  }\n\n");
+        code.push_str("  #r() {\n");
+        code.push_str("    this.#head += 1;\n");
+        code.push_str("    if (this.#head === this.#tape.length) {\n");
+        code.push_str("      this.#tape.push(BLANK);\n");
+        code.push_str("    }\n");
+        code.push_str("  }\n\n");
+        code.push_str("  #l() {\n");
+        code.push_str("    this.#head -= 1;\n");
+        code.push_str("  }\n\n");
+
+        code.push_str(
+            "  // Apply one transition and return null, or the Halt and change nothing\n",
+        );
+        code.push_str("  step() {\n");
+        code.push_str("    const state = this.#state;\n");
+        code.push_str("    const symbol = this.#tape[this.#head];\n");
+        code.push_str("    let next;\n");
+        let sorted_transitions = self.transitions_star_last();
+        for (index, transition) in sorted_transitions.iter().enumerate() {
+            let condition = match &transition.condition {
+                Condition::OR(symbols) => format!(
+                    " && ({})",
+                    symbols
+                        .iter()
+                        .map(|symbol| format!("symbol === {}", quote(symbol)))
+                        .collect::<Vec<String>>()
+                        .join(" || ")
+                ),
+                Condition::Star => String::new(),
+            };
+            code.push_str(&format!(
+                "    {}if (state === {}{}) {{\n",
+                if index == 0 { "" } else { "} else " },
+                quote(&transition.initial_state),
+                condition
+            ));
+            let left_reach = transition.left_reach();
+            if left_reach > 0 {
+                code.push_str(&format!("      if (this.#head < {}) {{\n", left_reach));
+                code.push_str("        return Halt.LEFT_EDGE;\n");
+                code.push_str("      }\n");
+            }
+            for step in &transition.steps {
+                match step {
+                    TransitionStep::R => code.push_str("      this.#r();\n"),
+                    TransitionStep::L => code.push_str("      this.#l();\n"),
+                    TransitionStep::X => {}
+                    TransitionStep::P(symbol) => {
+                        code.push_str(&format!("      this.#p({});\n", quote(symbol)))
+                    }
+                }
+            }
+            code.push_str(&format!(
+                "      next = {};\n",
+                quote(&transition.final_state)
+            ));
+        }
+        if sorted_transitions.is_empty() {
+            code.push_str("    return Halt.NO_TRANSITION;\n");
+        } else {
+            code.push_str("    } else {\n");
+            code.push_str("      return Halt.NO_TRANSITION;\n");
+            code.push_str("    }\n");
+            code.push_str("    this.#state = next;\n");
+            code.push_str("    this.#steps += 1;\n");
+            code.push_str("    return null;\n");
+        }
+        code.push_str("  }\n\n");
+
+        code.push_str("  // Run until the machine halts or `maxSteps` steps have been applied\n");
+        code.push_str("  run(maxSteps) {\n");
+        code.push_str("    for (let i = 0; i < maxSteps; i++) {\n");
+        code.push_str("      const halt = this.step();\n");
+        code.push_str("      if (halt !== null) {\n");
+        code.push_str("        return halt;\n");
+        code.push_str("      }\n");
+        code.push_str("    }\n");
+        code.push_str("    return null;\n");
+        code.push_str("  }\n");
+        code.push_str("}\n");
+        code
+    }
+}
@@ -1,4 +1,5 @@
-use log::{error, info};
+use crate::diagnostics::{Diagnostic, Emitter, Severity, Span};
+use log::{error, info, warn};
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -87,6 +88,13 @@ pub struct Lexer {
     source: Vec<char>,
     pub cur_char: char,
     cur_pos: usize,
+    // Position of cur_char
+    line: usize,
+    column: usize,
+    // Position of the last token returned by get_token
+    token_span: Span,
+    emitter: Option<Emitter>,
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl Lexer {
@@ -105,14 +113,51 @@ impl Lexer {
             source: source_chars,
             cur_char,
             cur_pos: 0,
+            line: 1,
+            column: 1,
+            token_span: Span::new(1, 1, 0),
+            emitter: None,
+            diagnostics: Vec::new(),
         }
     }
 
+    // Render diagnostics to stderr instead of only logging them
+    pub fn with_emitter(mut self, emitter: Emitter) -> Self {
+        self.emitter = Some(emitter);
+        self
+    }
+
+    // Span of the last token returned by get_token
+    pub fn span(&self) -> Span {
+        self.token_span
+    }
+
+    // Record a diagnostic and surface it through the emitter (or the log)
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        match &self.emitter {
+            Some(emitter) => {
+                let source: String = self.source.iter().collect();
+                emitter.emit(&diagnostic, &source);
+            }
+            None => match diagnostic.severity {
+                Severity::Error => error!("{}", diagnostic.message),
+                Severity::Warning => warn!("{}", diagnostic.message),
+            },
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
     pub fn cur_char(&self) -> char {
         self.source[self.cur_pos]
     }
 
     pub fn next_char(&mut self) {
+        if self.cur_pos < self.source.len() && self.source[self.cur_pos] == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         self.cur_pos += 1;
         if self.cur_pos >= self.source.len() {
             self.cur_char = '\0'; // EOF
@@ -129,11 +174,18 @@ impl Lexer {
         }
     }
 
-    pub fn abort(&self, message: &str) {
-        error!("Lexical Error: {}", message);
+    pub fn abort(&mut self, message: &str) {
+        self.error(message);
         panic!("Lexical Error: {}", message);
     }
 
+    // Report an error on the current character and give it back
+    fn error(&mut self, message: &str) -> Diagnostic {
+        let diagnostic = Diagnostic::error(message, Span::new(self.line, self.column, 1));
+        self.report(diagnostic.clone());
+        diagnostic
+    }
+
     fn skip_whitespace(&mut self) {
         while self.cur_char == ' ' || self.cur_char == '\t' || self.cur_char == '\r' {
             self.next_char();
@@ -149,54 +201,64 @@ impl Lexer {
     }
 
     pub fn get_token(&mut self) -> Option<Token> {
+        match self.try_get_token() {
+            Ok(token) => Some(token),
+            Err(diagnostic) => panic!("Lexical Error: {}", diagnostic.message),
+        }
+    }
+
+    // Same as `get_token`, with the error on an unknown character returned
+    // instead of panicking
+    pub fn try_get_token(&mut self) -> Result<Token, Diagnostic> {
         self.skip_whitespace();
         self.skip_comment();
+        let (start_line, start_column) = (self.line, self.column);
 
         let token = match self.cur_char {
-            '\n' => Some(Token {
+            '\n' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::NEWLINE,
-            }),
-            '|' => Some(Token {
+            },
+            '|' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::OR,
-            }),
-            '[' => Some(Token {
+            },
+            '[' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::LeftBracket,
-            }),
-            ']' => Some(Token {
+            },
+            ']' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::RightBracket,
-            }),
-            ',' => Some(Token {
+            },
+            ',' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::COMMA,
-            }),
-            '-' => Some(Token {
+            },
+            '-' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::DASH,
-            }),
-            '(' => Some(Token {
+            },
+            '(' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::LeftParen,
-            }),
-            ')' => Some(Token {
+            },
+            ')' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::RightParen,
-            }),
-            '*' => Some(Token {
+            },
+            '*' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::STAR,
-            }),
-            ':' => Some(Token {
+            },
+            ':' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::COLON,
-            }),
-            '\0' => Some(Token {
+            },
+            '\0' => Token {
                 text: self.cur_char.to_string(),
                 kind: TokenType::EOF,
-            }),
+            },
             _ if self.cur_char.is_alphanumeric() => {
                 let start_pos = self.cur_pos;
                 while self.peek().is_alphanumeric() {
@@ -204,23 +266,21 @@ impl Lexer {
                 }
                 let tok_text: String = self.source[start_pos..=self.cur_pos].iter().collect();
                 match Token::check_if_keyword(&tok_text) {
-                    Some(keyword) => Some(Token {
+                    Some(keyword) => Token {
                         text: tok_text,
                         kind: keyword,
-                    }),
-                    None => Some(Token {
+                    },
+                    None => Token {
                         text: tok_text,
                         kind: TokenType::IDENT,
-                    }),
+                    },
                 }
             }
-            _ => {
-                self.abort(&format!("Unknown token: {}", self.cur_char));
-                None
-            }
+            _ => return Err(self.error(&format!("Unknown token: {}", self.cur_char))),
         };
 
+        self.token_span = Span::new(start_line, start_column, token.text.chars().count());
         self.next_char();
-        token
+        Ok(token)
     }
 }
@@ -1,3 +1,23 @@
+pub mod diagnostics;
+pub mod escape;
 pub mod lexer;
 pub mod parser;
-pub mod llvmconverter;
\ No newline at end of file
+#[cfg(feature = "llvm")]
+pub mod llvmconverter;
+pub mod trace;
+pub mod interpreter;
+pub mod macromachine;
+pub mod snapshot;
+pub mod debugger;
+pub mod analysis;
+pub mod coverage;
+pub mod cycles;
+pub mod spacetime;
+pub mod graphs;
+pub mod animation;
+pub mod cconverter;
+pub mod wasmconverter;
+pub mod pythonconverter;
+pub mod jsconverter;
+#[cfg(feature = "llvm")]
+pub mod bench;
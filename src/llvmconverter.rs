@@ -1,16 +1,1005 @@
 #![allow(unused)]
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
 
 use crate::parser::{Condition, ParseTree, Transition, TransitionStep};
+use crate::trace::{TraceFormat, TraceLevel, TraceOptions, TRACE_ENV_VAR, TRACE_FORMAT_ENV_VAR};
 use inkwell::basic_block::BasicBlock;
+use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::targets::TargetTriple;
-use inkwell::values::{BasicValue, BasicValueEnum, FunctionValue, PointerValue};
-use inkwell::{AddressSpace, IntPredicate};
+use inkwell::debug_info::{
+    AsDIScope, DIFlags, DIFlagsConstants, DWARFEmissionKind, DWARFSourceLanguage,
+};
+use inkwell::module::{FlagBehavior, Module};
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::{
+    CodeModel, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple,
+};
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, GlobalValue, IntValue,
+    PointerValue,
+};
+use inkwell::{AddressSpace, IntPredicate, OptimizationLevel};
+
+// Version of the debug info metadata understood by LLVM
+const DEBUG_METADATA_VERSION: u64 = 3;
+// DWARF encoding of signed integers
+const DW_ATE_SIGNED: u32 = 0x05;
+
+// Symbols, steps, final state and source line of one transition
+type ProcessedTransition = (Vec<String>, Vec<TransitionStep>, String, usize);
+
+// Optimization pipeline run over the module before it is printed
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum LlvmOptLevel {
+    // The module is printed as built
+    #[default]
+    O0,
+    O1,
+    O2,
+    O3,
+}
+
+impl LlvmOptLevel {
+    // Pipeline of the new pass manager, same as `opt -passes=...`
+    pub fn passes(&self) -> &'static str {
+        match self {
+            LlvmOptLevel::O0 => "default<O0>",
+            LlvmOptLevel::O1 => "default<O1>",
+            LlvmOptLevel::O2 => "default<O2>",
+            LlvmOptLevel::O3 => "default<O3>",
+        }
+    }
+
+    fn optimization_level(&self) -> OptimizationLevel {
+        match self {
+            LlvmOptLevel::O0 => OptimizationLevel::None,
+            LlvmOptLevel::O1 => OptimizationLevel::Less,
+            LlvmOptLevel::O2 => OptimizationLevel::Default,
+            LlvmOptLevel::O3 => OptimizationLevel::Aggressive,
+        }
+    }
+}
+
+impl FromStr for LlvmOptLevel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(LlvmOptLevel::O0),
+            "1" => Ok(LlvmOptLevel::O1),
+            "2" => Ok(LlvmOptLevel::O2),
+            "3" => Ok(LlvmOptLevel::O3),
+            _ => Err(format!("Unknown optimization level: {}", s)),
+        }
+    }
+}
+
+// What the generated program prints while running
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum LlvmOutput {
+    // Only the prompts for the number of steps and the tape length
+    Silent,
+    // The tape and the cleaned tape once the machine stops, like the Rust program
+    #[default]
+    FinalTape,
+    // Every step as chosen by the trace options, then the final tape
+    Trace,
+}
+
+impl FromStr for LlvmOutput {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "silent" => Ok(LlvmOutput::Silent),
+            "final-tape" => Ok(LlvmOutput::FinalTape),
+            "trace" => Ok(LlvmOutput::Trace),
+            _ => Err(format!("Unknown output: {}", s)),
+        }
+    }
+}
+
+// How the generated program goes from one transition to the next
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum LlvmDispatch {
+    // One loop with a switch on `symbol * states + state`
+    #[default]
+    Switch,
+    // A block per state branching directly to the block of the next state,
+    // without a state variable
+    Threaded,
+}
+
+impl FromStr for LlvmDispatch {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "switch" => Ok(LlvmDispatch::Switch),
+            "threaded" => Ok(LlvmDispatch::Threaded),
+            _ => Err(format!("Unknown dispatch: {}", s)),
+        }
+    }
+}
+
+// Options for the generated LLVM IR
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LlvmCodegenOptions {
+    pub trace: TraceOptions,
+    pub opt_level: LlvmOptLevel,
+    pub output: LlvmOutput,
+    // Emit DWARF debug info with the case of every transition at its line
+    // in this machine file, so a native debugger can break on it
+    pub debug_info: Option<PathBuf>,
+    pub dispatch: LlvmDispatch,
+}
 
 // Trait for converting to LLVM IR
 pub trait ToLlvmIr {
-    fn to_llvm_ir(&self) -> String;
+    fn to_llvm_ir(&self) -> String {
+        self.to_llvm_ir_with(&LlvmCodegenOptions::default())
+    }
+    // Panics when the module fails verification or optimization
+    fn to_llvm_ir_with(&self, options: &LlvmCodegenOptions) -> String {
+        self.try_to_llvm_ir_with(options)
+            .unwrap_or_else(|e| panic!("Invalid LLVM IR: {}", e))
+    }
+    fn try_to_llvm_ir_with(&self, options: &LlvmCodegenOptions) -> Result<String, String>;
+}
+
+// Target machine for the module's triple, the optimizations depend on it
+fn target_machine(triple: &TargetTriple, opt_level: LlvmOptLevel) -> Result<TargetMachine, String> {
+    Target::initialize_native(&InitializationConfig::default())?;
+    let target = Target::from_triple(triple).map_err(|e| e.to_string())?;
+    target
+        .create_target_machine(
+            triple,
+            "generic",
+            "",
+            opt_level.optimization_level(),
+            RelocMode::Default,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| format!("No target machine for {}", triple))
+}
+
+// Build `void print_tape(ptr tape, i32 len)` which prints the name of every
+// symbol on the tape, one after the other. With a `skipped_symbol` it is
+// `print_clean_tape` and leaves that symbol out.
+fn build_print_tape_fn<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    printf_fn: FunctionValue<'ctx>,
+    symbol_names: GlobalValue<'ctx>,
+    total_symbols: u32,
+    skipped_symbol: Option<u32>,
+) -> FunctionValue<'ctx> {
+    let i32_type = context.i32_type();
+    let i8_type = context.i8_type();
+    let ptr_type = context.ptr_type(AddressSpace::default());
+    let print_tape_type = context
+        .void_type()
+        .fn_type(&[ptr_type.into(), i32_type.into()], false);
+    let name = if skipped_symbol.is_some() {
+        "print_clean_tape"
+    } else {
+        "print_tape"
+    };
+    let print_tape_fn = module.add_function(name, print_tape_type, None);
+    let tape_ptr = print_tape_fn.get_nth_param(0).unwrap().into_pointer_value();
+    let tape_len = print_tape_fn.get_nth_param(1).unwrap().into_int_value();
+
+    let entry = context.append_basic_block(print_tape_fn, "entry");
+    let cell_loop = context.append_basic_block(print_tape_fn, "cell_loop");
+    let cell_loop_body = context.append_basic_block(print_tape_fn, "cell_loop_body");
+    let print_symbol = context.append_basic_block(print_tape_fn, "print_symbol");
+    let next_cell = context.append_basic_block(print_tape_fn, "next_cell");
+    let cell_loop_end = context.append_basic_block(print_tape_fn, "cell_loop_end");
+
+    builder.position_at_end(entry);
+    let cell_index_ptr = builder.build_alloca(i32_type, "cell_index_ptr").unwrap();
+    builder.build_store(cell_index_ptr, i32_type.const_int(0, false));
+    let print_symbol_format = builder
+        .build_global_string_ptr("%s", "print_symbol_format")
+        .unwrap();
+    builder.build_unconditional_branch(cell_loop);
+
+    builder.position_at_end(cell_loop);
+    let cell_index = builder
+        .build_load(i32_type, cell_index_ptr, "cell_index")
+        .unwrap()
+        .into_int_value();
+    let cell_limit_cond = builder
+        .build_int_compare(IntPredicate::ULT, cell_index, tape_len, "cell_limit_cond")
+        .unwrap();
+    builder.build_conditional_branch(cell_limit_cond, cell_loop_body, cell_loop_end);
+
+    builder.position_at_end(cell_loop_body);
+    let symbol_index = load_tape_symbol_index(context, builder, tape_ptr, cell_index);
+    match skipped_symbol {
+        Some(skipped_symbol) => {
+            let is_skipped = builder
+                .build_int_compare(
+                    IntPredicate::EQ,
+                    symbol_index,
+                    i32_type.const_int(skipped_symbol as u64, false),
+                    "is_skipped",
+                )
+                .unwrap();
+            builder.build_conditional_branch(is_skipped, next_cell, print_symbol);
+        }
+        None => {
+            builder.build_unconditional_branch(print_symbol);
+        }
+    }
+
+    builder.position_at_end(print_symbol);
+    let symbol_name = load_name(context, builder, symbol_names, total_symbols, symbol_index);
+    builder.build_call(
+        printf_fn,
+        &[
+            print_symbol_format.as_pointer_value().into(),
+            symbol_name.into(),
+        ],
+        "print_symbol_call",
+    );
+    builder.build_unconditional_branch(next_cell);
+
+    builder.position_at_end(next_cell);
+    let next_cell_index = builder
+        .build_int_add(cell_index, i32_type.const_int(1, false), "next_cell_index")
+        .unwrap();
+    builder.build_store(cell_index_ptr, next_cell_index);
+    builder.build_unconditional_branch(cell_loop);
+
+    builder.position_at_end(cell_loop_end);
+    builder.build_return(None);
+    print_tape_fn
+}
+
+// Build `void print_head_marker(ptr tape, i32 head)` which prints a `^` under
+// the symbol at `head`, assuming the tape was printed with print_tape
+fn build_print_head_marker_fn<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    printf_fn: FunctionValue<'ctx>,
+    strlen_fn: FunctionValue<'ctx>,
+    symbol_names: GlobalValue<'ctx>,
+    total_symbols: u32,
+) -> FunctionValue<'ctx> {
+    let i32_type = context.i32_type();
+    let ptr_type = context.ptr_type(AddressSpace::default());
+    let print_marker_type = context
+        .void_type()
+        .fn_type(&[ptr_type.into(), i32_type.into()], false);
+    let print_marker_fn = module.add_function("print_head_marker", print_marker_type, None);
+    let tape_ptr = print_marker_fn
+        .get_nth_param(0)
+        .unwrap()
+        .into_pointer_value();
+    let head = print_marker_fn.get_nth_param(1).unwrap().into_int_value();
+
+    let entry = context.append_basic_block(print_marker_fn, "entry");
+    let cell_loop = context.append_basic_block(print_marker_fn, "cell_loop");
+    let cell_loop_body = context.append_basic_block(print_marker_fn, "cell_loop_body");
+    let cell_loop_end = context.append_basic_block(print_marker_fn, "cell_loop_end");
+
+    builder.position_at_end(entry);
+    let cell_index_ptr = builder.build_alloca(i32_type, "cell_index_ptr").unwrap();
+    builder.build_store(cell_index_ptr, i32_type.const_int(0, false));
+    // Pad with as many spaces as the printed symbol is wide
+    let padding_format = builder
+        .build_global_string_ptr("%*s", "padding_format")
+        .unwrap();
+    let empty_string = builder.build_global_string_ptr("", "empty_string").unwrap();
+    let marker_format = builder
+        .build_global_string_ptr("^\n", "marker_format")
+        .unwrap();
+    builder.build_unconditional_branch(cell_loop);
+
+    builder.position_at_end(cell_loop);
+    let cell_index = builder
+        .build_load(i32_type, cell_index_ptr, "cell_index")
+        .unwrap()
+        .into_int_value();
+    let cell_limit_cond = builder
+        .build_int_compare(IntPredicate::ULT, cell_index, head, "cell_limit_cond")
+        .unwrap();
+    builder.build_conditional_branch(cell_limit_cond, cell_loop_body, cell_loop_end);
+
+    builder.position_at_end(cell_loop_body);
+    let symbol_name = load_symbol_name(
+        context,
+        builder,
+        tape_ptr,
+        cell_index,
+        symbol_names,
+        total_symbols,
+    );
+    let symbol_width = builder
+        .build_call(strlen_fn, &[symbol_name.into()], "symbol_width")
+        .unwrap()
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+    let symbol_width = builder
+        .build_int_truncate(symbol_width, i32_type, "symbol_width_i32")
+        .unwrap();
+    builder.build_call(
+        printf_fn,
+        &[
+            padding_format.as_pointer_value().into(),
+            symbol_width.into(),
+            empty_string.as_pointer_value().into(),
+        ],
+        "print_padding_call",
+    );
+    let next_cell_index = builder
+        .build_int_add(cell_index, i32_type.const_int(1, false), "next_cell_index")
+        .unwrap();
+    builder.build_store(cell_index_ptr, next_cell_index);
+    builder.build_unconditional_branch(cell_loop);
+
+    builder.position_at_end(cell_loop_end);
+    builder.build_call(
+        printf_fn,
+        &[marker_format.as_pointer_value().into()],
+        "print_marker_call",
+    );
+    builder.build_return(None);
+    print_marker_fn
+}
+
+// Load the symbol index stored at `tape[index]`
+fn load_tape_symbol_index<'ctx>(
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    tape_ptr: PointerValue<'ctx>,
+    index: IntValue<'ctx>,
+) -> IntValue<'ctx> {
+    let cell_ptr = unsafe {
+        builder
+            .build_in_bounds_gep(context.i8_type(), tape_ptr, &[index], "cell_ptr")
+            .unwrap()
+    };
+    let symbol_index = builder
+        .build_load(context.i8_type(), cell_ptr, "cell_symbol_index")
+        .unwrap()
+        .into_int_value();
+    builder
+        .build_int_z_extend(symbol_index, context.i32_type(), "cell_symbol_index_i32")
+        .unwrap()
+}
+
+// Load the name of the entry `index` from a global array of names
+fn load_name<'ctx>(
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    names: GlobalValue<'ctx>,
+    total_names: u32,
+    index: IntValue<'ctx>,
+) -> PointerValue<'ctx> {
+    let ptr_type = context.ptr_type(AddressSpace::default());
+    let name_ptr = unsafe {
+        builder
+            .build_in_bounds_gep(
+                ptr_type.array_type(total_names),
+                names.as_pointer_value(),
+                &[context.i32_type().const_int(0, false), index],
+                "name_ptr",
+            )
+            .unwrap()
+    };
+    builder
+        .build_load(ptr_type, name_ptr, "name")
+        .unwrap()
+        .into_pointer_value()
+}
+
+// Load the name of the symbol stored at `tape[index]`
+fn load_symbol_name<'ctx>(
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    tape_ptr: PointerValue<'ctx>,
+    index: IntValue<'ctx>,
+    symbol_names: GlobalValue<'ctx>,
+    total_symbols: u32,
+) -> PointerValue<'ctx> {
+    let symbol_index = load_tape_symbol_index(context, builder, tape_ptr, index);
+    load_name(context, builder, symbol_names, total_symbols, symbol_index)
+}
+
+// Define a constant global array holding pointers to the given strings
+fn build_names_array<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    names: &[GlobalValue<'ctx>],
+    name: &str,
+) -> GlobalValue<'ctx> {
+    let ptr_type = context.ptr_type(AddressSpace::default());
+    let names_type = ptr_type.array_type(names.len() as u32);
+    let names_array = module.add_global(names_type, None, name);
+    let values = names
+        .iter()
+        .map(|n| n.as_pointer_value())
+        .collect::<Vec<PointerValue>>();
+    names_array.set_initializer(&ptr_type.const_array(&values));
+    names_array.set_constant(true);
+    names_array
+}
+
+// Pick the value whose name matches the environment variable `env_var`,
+// falling back to `default` when it is unset or holds an unknown name
+fn build_env_choice<'ctx>(
+    builder: &Builder<'ctx>,
+    getenv_fn: FunctionValue<'ctx>,
+    strcmp_fn: FunctionValue<'ctx>,
+    env_var: &str,
+    choices: &[(&str, u64)],
+    default: IntValue<'ctx>,
+    name: &str,
+) -> IntValue<'ctx> {
+    let env_var_name = builder
+        .build_global_string_ptr(env_var, &format!("{}_env_var", name))
+        .unwrap();
+    let env_value = builder
+        .build_call(
+            getenv_fn,
+            &[env_var_name.as_pointer_value().into()],
+            &format!("{}_env_value", name),
+        )
+        .unwrap()
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_pointer_value();
+    // strcmp must not be called with NULL, compare against "" instead
+    let is_unset = builder
+        .build_is_null(env_value, &format!("{}_is_unset", name))
+        .unwrap();
+    let empty_string = builder
+        .build_global_string_ptr("", &format!("{}_unset", name))
+        .unwrap();
+    let env_value = builder
+        .build_select(
+            is_unset,
+            empty_string.as_pointer_value(),
+            env_value,
+            &format!("{}_env_string", name),
+        )
+        .unwrap()
+        .into_pointer_value();
+
+    let mut value = default;
+    for (choice, choice_value) in choices {
+        let choice_name = builder
+            .build_global_string_ptr(choice, &format!("{}_{}", name, choice))
+            .unwrap();
+        let comparison = builder
+            .build_call(
+                strcmp_fn,
+                &[env_value.into(), choice_name.as_pointer_value().into()],
+                &format!("{}_strcmp", name),
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let is_choice = builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                comparison,
+                comparison.get_type().const_int(0, false),
+                &format!("{}_is_{}", name, choice),
+            )
+            .unwrap();
+        value = builder
+            .build_select(
+                is_choice,
+                default.get_type().const_int(*choice_value, false),
+                value,
+                name,
+            )
+            .unwrap()
+            .into_int_value();
+    }
+    value
+}
+
+// Build `i32 parse_args(i32 argc, ptr argv, ptr steps, ptr tape_len, ptr tape)`
+// which reads `[--steps N] [--tape-len N] [TAPE]`. The tape length defaults
+// to the length of the initial tape, which stays NULL when not given.
+// Returns 1 after printing the error and the usage on stderr.
+fn build_parse_args_fn<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    strcmp_fn: FunctionValue<'ctx>,
+    strlen_fn: FunctionValue<'ctx>,
+    sscanf_fn: FunctionValue<'ctx>,
+    dprintf_fn: FunctionValue<'ctx>,
+) -> FunctionValue<'ctx> {
+    let i32_type = context.i32_type();
+    let i8_type = context.i8_type();
+    let ptr_type = context.ptr_type(AddressSpace::default());
+    let parse_args_type = i32_type.fn_type(
+        &[
+            i32_type.into(),
+            ptr_type.into(),
+            ptr_type.into(),
+            ptr_type.into(),
+            ptr_type.into(),
+        ],
+        false,
+    );
+    let parse_args_fn = module.add_function("parse_args", parse_args_type, None);
+    let argc = parse_args_fn.get_nth_param(0).unwrap().into_int_value();
+    let argv = parse_args_fn.get_nth_param(1).unwrap().into_pointer_value();
+    let steps_ptr = parse_args_fn.get_nth_param(2).unwrap().into_pointer_value();
+    let tape_len_ptr = parse_args_fn.get_nth_param(3).unwrap().into_pointer_value();
+    let tape_ptr = parse_args_fn.get_nth_param(4).unwrap().into_pointer_value();
+    let stderr = i32_type.const_int(2, false);
+    let i32_1 = i32_type.const_int(1, false);
+
+    let entry = context.append_basic_block(parse_args_fn, "entry");
+    let arg_loop = context.append_basic_block(parse_args_fn, "arg_loop");
+    let arg_loop_body = context.append_basic_block(parse_args_fn, "arg_loop_body");
+    let arg_loop_end = context.append_basic_block(parse_args_fn, "arg_loop_end");
+    let usage_error = context.append_basic_block(parse_args_fn, "usage_error");
+
+    builder.position_at_end(entry);
+    let arg_index_ptr = builder.build_alloca(i32_type, "arg_index_ptr").unwrap();
+    // Any character after the number makes it invalid
+    let trailing_ptr = builder.build_alloca(i8_type, "trailing_ptr").unwrap();
+    builder.build_store(arg_index_ptr, i32_1);
+    builder.build_store(steps_ptr, i32_type.const_all_ones());
+    builder.build_store(tape_len_ptr, i32_type.const_all_ones());
+    builder.build_store(tape_ptr, ptr_type.const_null());
+    let number_format = builder
+        .build_global_string_ptr("%d%c", "number_format")
+        .unwrap();
+    builder.build_unconditional_branch(arg_loop);
+
+    builder.position_at_end(arg_loop);
+    let arg_index = builder
+        .build_load(i32_type, arg_index_ptr, "arg_index")
+        .unwrap()
+        .into_int_value();
+    let has_arg = builder
+        .build_int_compare(IntPredicate::SLT, arg_index, argc, "has_arg")
+        .unwrap();
+    builder.build_conditional_branch(has_arg, arg_loop_body, arg_loop_end);
+
+    let load_arg = |index: IntValue<'ctx>, name: &str| {
+        let arg_ptr = unsafe {
+            builder
+                .build_in_bounds_gep(ptr_type, argv, &[index], &format!("{}_ptr", name))
+                .unwrap()
+        };
+        builder
+            .build_load(ptr_type, arg_ptr, name)
+            .unwrap()
+            .into_pointer_value()
+    };
+    // Print the error on stderr, then the usage
+    let report = |message: &str, name: &str, args: &[PointerValue<'ctx>]| {
+        let format = builder
+            .build_global_string_ptr(message, &format!("{}_format", name))
+            .unwrap();
+        let mut call_args = vec![stderr.into(), format.as_pointer_value().into()];
+        call_args.extend(args.iter().map(|arg| BasicMetadataValueEnum::from(*arg)));
+        builder.build_call(dprintf_fn, &call_args, &format!("{}_call", name));
+        builder.build_unconditional_branch(usage_error);
+    };
+
+    builder.position_at_end(arg_loop_body);
+    let arg = load_arg(arg_index, "arg");
+    // --steps N and --tape-len N store a non negative number
+    for (option, value_ptr) in [("--steps", steps_ptr), ("--tape-len", tape_len_ptr)] {
+        let name = option.trim_start_matches('-').replace('-', "_");
+        let option_block = context.append_basic_block(parse_args_fn, &format!("{}_option", name));
+        let value_block = context.append_basic_block(parse_args_fn, &format!("{}_value", name));
+        let valid_value = context.append_basic_block(parse_args_fn, &format!("{}_valid", name));
+        let invalid_value = context.append_basic_block(parse_args_fn, &format!("{}_invalid", name));
+        let missing_value = context.append_basic_block(parse_args_fn, &format!("{}_missing", name));
+        let next_option = context.append_basic_block(parse_args_fn, &format!("not_{}", name));
+
+        let option_name = builder
+            .build_global_string_ptr(option, &format!("{}_option_name", name))
+            .unwrap();
+        let comparison = builder
+            .build_call(
+                strcmp_fn,
+                &[arg.into(), option_name.as_pointer_value().into()],
+                &format!("{}_strcmp", name),
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let is_option = builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                comparison,
+                i32_type.const_zero(),
+                &format!("is_{}", name),
+            )
+            .unwrap();
+        builder.build_conditional_branch(is_option, option_block, next_option);
+
+        builder.position_at_end(option_block);
+        let value_index = builder
+            .build_int_add(arg_index, i32_1, "value_index")
+            .unwrap();
+        let has_value = builder
+            .build_int_compare(IntPredicate::SLT, value_index, argc, "has_value")
+            .unwrap();
+        builder.build_conditional_branch(has_value, value_block, missing_value);
+
+        builder.position_at_end(value_block);
+        let value = load_arg(value_index, "value");
+        let scanned = builder
+            .build_call(
+                sscanf_fn,
+                &[
+                    value.into(),
+                    number_format.as_pointer_value().into(),
+                    value_ptr.into(),
+                    trailing_ptr.into(),
+                ],
+                "scanned",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let is_number = builder
+            .build_int_compare(IntPredicate::EQ, scanned, i32_1, "is_number")
+            .unwrap();
+        let number = builder
+            .build_load(i32_type, value_ptr, "number")
+            .unwrap()
+            .into_int_value();
+        let is_non_negative = builder
+            .build_int_compare(
+                IntPredicate::SGE,
+                number,
+                i32_type.const_zero(),
+                "is_non_negative",
+            )
+            .unwrap();
+        let is_valid = builder
+            .build_and(is_number, is_non_negative, "is_valid")
+            .unwrap();
+        builder.build_conditional_branch(is_valid, valid_value, invalid_value);
+
+        builder.position_at_end(valid_value);
+        let next_arg_index = builder
+            .build_int_add(value_index, i32_1, "next_arg_index")
+            .unwrap();
+        builder.build_store(arg_index_ptr, next_arg_index);
+        builder.build_unconditional_branch(arg_loop);
+
+        builder.position_at_end(invalid_value);
+        report(
+            "Invalid value for %s: %s\n",
+            &format!("{}_invalid", name),
+            &[arg, value],
+        );
+
+        builder.position_at_end(missing_value);
+        report(
+            "Missing value for %s\n",
+            &format!("{}_missing", name),
+            &[arg],
+        );
+
+        builder.position_at_end(next_option);
+    }
+
+    // Anything else is the initial tape, symbols never start with a dash
+    let tape_argument = context.append_basic_block(parse_args_fn, "tape_argument");
+    let unknown_option = context.append_basic_block(parse_args_fn, "unknown_option");
+    let store_tape = context.append_basic_block(parse_args_fn, "store_tape");
+    let extra_argument = context.append_basic_block(parse_args_fn, "extra_argument");
+    let first_char = builder
+        .build_load(i8_type, arg, "first_char")
+        .unwrap()
+        .into_int_value();
+    let is_option = builder
+        .build_int_compare(
+            IntPredicate::EQ,
+            first_char,
+            i8_type.const_int(b'-' as u64, false),
+            "is_option",
+        )
+        .unwrap();
+    builder.build_conditional_branch(is_option, unknown_option, tape_argument);
+
+    builder.position_at_end(unknown_option);
+    report("Unknown option %s\n", "unknown_option", &[arg]);
+
+    builder.position_at_end(tape_argument);
+    let tape = builder
+        .build_load(ptr_type, tape_ptr, "tape")
+        .unwrap()
+        .into_pointer_value();
+    let has_tape = builder.build_is_not_null(tape, "has_tape").unwrap();
+    builder.build_conditional_branch(has_tape, extra_argument, store_tape);
+
+    builder.position_at_end(extra_argument);
+    report("Unexpected argument %s\n", "extra_argument", &[arg]);
+
+    builder.position_at_end(store_tape);
+    builder.build_store(tape_ptr, arg);
+    let next_arg_index = builder
+        .build_int_add(arg_index, i32_1, "next_arg_index")
+        .unwrap();
+    builder.build_store(arg_index_ptr, next_arg_index);
+    builder.build_unconditional_branch(arg_loop);
+
+    // The number of steps is required, the tape length can come from the tape
+    let has_steps_block = context.append_basic_block(parse_args_fn, "has_steps");
+    let missing_steps = context.append_basic_block(parse_args_fn, "missing_steps");
+    let tape_len_from_tape = context.append_basic_block(parse_args_fn, "tape_len_from_tape");
+    let missing_tape_len = context.append_basic_block(parse_args_fn, "missing_tape_len");
+    let args_ok = context.append_basic_block(parse_args_fn, "args_ok");
+
+    builder.position_at_end(arg_loop_end);
+    let steps = builder
+        .build_load(i32_type, steps_ptr, "steps")
+        .unwrap()
+        .into_int_value();
+    let has_steps = builder
+        .build_int_compare(IntPredicate::SGE, steps, i32_type.const_zero(), "has_steps")
+        .unwrap();
+    builder.build_conditional_branch(has_steps, has_steps_block, missing_steps);
+
+    builder.position_at_end(missing_steps);
+    report("Missing --steps\n", "missing_steps", &[]);
+
+    builder.position_at_end(has_steps_block);
+    let tape_len = builder
+        .build_load(i32_type, tape_len_ptr, "tape_len")
+        .unwrap()
+        .into_int_value();
+    let has_tape_len = builder
+        .build_int_compare(
+            IntPredicate::SGE,
+            tape_len,
+            i32_type.const_zero(),
+            "has_tape_len",
+        )
+        .unwrap();
+    let tape = builder
+        .build_load(ptr_type, tape_ptr, "tape")
+        .unwrap()
+        .into_pointer_value();
+    let has_tape = builder.build_is_not_null(tape, "has_tape").unwrap();
+    let tape_len_ok = context.append_basic_block(parse_args_fn, "tape_len_ok");
+    builder.build_conditional_branch(has_tape_len, args_ok, tape_len_ok);
+
+    builder.position_at_end(tape_len_ok);
+    builder.build_conditional_branch(has_tape, tape_len_from_tape, missing_tape_len);
+
+    builder.position_at_end(missing_tape_len);
+    report(
+        "Missing --tape-len or an initial tape\n",
+        "missing_tape_len",
+        &[],
+    );
+
+    builder.position_at_end(tape_len_from_tape);
+    let initial_tape_len = builder
+        .build_call(strlen_fn, &[tape.into()], "initial_tape_len")
+        .unwrap()
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+    let initial_tape_len = builder
+        .build_int_truncate(initial_tape_len, i32_type, "initial_tape_len_i32")
+        .unwrap();
+    builder.build_store(tape_len_ptr, initial_tape_len);
+    builder.build_unconditional_branch(args_ok);
+
+    builder.position_at_end(args_ok);
+    builder.build_return(Some(&i32_type.const_zero()));
+
+    builder.position_at_end(usage_error);
+    let program_name = load_arg(i32_type.const_zero(), "program_name");
+    let usage_format = builder
+        .build_global_string_ptr(
+            "Usage: %s [--steps N] [--tape-len N] [TAPE]\n",
+            "usage_format",
+        )
+        .unwrap();
+    builder.build_call(
+        dprintf_fn,
+        &[
+            stderr.into(),
+            usage_format.as_pointer_value().into(),
+            program_name.into(),
+        ],
+        "usage_call",
+    );
+    builder.build_return(Some(&i32_1));
+    parse_args_fn
+}
+
+// Build `i32 load_tape(ptr tape, i32 len, ptr initial_tape)` which writes the
+// symbols named by the characters of `initial_tape` at the start of the tape.
+// Only one character symbols can be written this way. Returns 1 after
+// printing the error on stderr.
+fn build_load_tape_fn<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    strlen_fn: FunctionValue<'ctx>,
+    dprintf_fn: FunctionValue<'ctx>,
+    symbols: &[String],
+) -> FunctionValue<'ctx> {
+    let i32_type = context.i32_type();
+    let i8_type = context.i8_type();
+    let ptr_type = context.ptr_type(AddressSpace::default());
+    let load_tape_type =
+        i32_type.fn_type(&[ptr_type.into(), i32_type.into(), ptr_type.into()], false);
+    let load_tape_fn = module.add_function("load_tape", load_tape_type, None);
+    let tape_ptr = load_tape_fn.get_nth_param(0).unwrap().into_pointer_value();
+    let tape_len = load_tape_fn.get_nth_param(1).unwrap().into_int_value();
+    let initial_tape = load_tape_fn.get_nth_param(2).unwrap().into_pointer_value();
+    let stderr = i32_type.const_int(2, false);
+
+    // Symbol index of every character, 255 when no symbol is named by it
+    let mut char_symbols = [u8::MAX; 256];
+    for (index, symbol) in symbols.iter().enumerate() {
+        if let [byte] = symbol.as_bytes() {
+            char_symbols[*byte as usize] = index as u8;
+        }
+    }
+    let char_symbols_type = i8_type.array_type(256);
+    let char_symbols_global = module.add_global(char_symbols_type, None, "char_symbols");
+    char_symbols_global.set_initializer(
+        &i8_type.const_array(
+            &char_symbols
+                .iter()
+                .map(|index| i8_type.const_int(*index as u64, false))
+                .collect::<Vec<IntValue>>(),
+        ),
+    );
+    char_symbols_global.set_constant(true);
+
+    let entry = context.append_basic_block(load_tape_fn, "entry");
+    let too_long = context.append_basic_block(load_tape_fn, "too_long");
+    let cell_loop = context.append_basic_block(load_tape_fn, "cell_loop");
+    let cell_loop_body = context.append_basic_block(load_tape_fn, "cell_loop_body");
+    let unknown_symbol = context.append_basic_block(load_tape_fn, "unknown_symbol");
+    let store_symbol = context.append_basic_block(load_tape_fn, "store_symbol");
+    let cell_loop_end = context.append_basic_block(load_tape_fn, "cell_loop_end");
+
+    builder.position_at_end(entry);
+    let cell_index_ptr = builder.build_alloca(i32_type, "cell_index_ptr").unwrap();
+    builder.build_store(cell_index_ptr, i32_type.const_zero());
+    let initial_tape_len = builder
+        .build_call(strlen_fn, &[initial_tape.into()], "initial_tape_len")
+        .unwrap()
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+    let initial_tape_len = builder
+        .build_int_truncate(initial_tape_len, i32_type, "initial_tape_len_i32")
+        .unwrap();
+    let is_too_long = builder
+        .build_int_compare(IntPredicate::UGT, initial_tape_len, tape_len, "is_too_long")
+        .unwrap();
+    builder.build_conditional_branch(is_too_long, too_long, cell_loop);
+
+    builder.position_at_end(too_long);
+    let too_long_format = builder
+        .build_global_string_ptr(
+            "Initial tape of %d cells does not fit a tape of %d cells\n",
+            "too_long_format",
+        )
+        .unwrap();
+    builder.build_call(
+        dprintf_fn,
+        &[
+            stderr.into(),
+            too_long_format.as_pointer_value().into(),
+            initial_tape_len.into(),
+            tape_len.into(),
+        ],
+        "too_long_call",
+    );
+    builder.build_return(Some(&i32_type.const_int(1, false)));
+
+    builder.position_at_end(cell_loop);
+    let cell_index = builder
+        .build_load(i32_type, cell_index_ptr, "cell_index")
+        .unwrap()
+        .into_int_value();
+    let has_cell = builder
+        .build_int_compare(IntPredicate::ULT, cell_index, initial_tape_len, "has_cell")
+        .unwrap();
+    builder.build_conditional_branch(has_cell, cell_loop_body, cell_loop_end);
+
+    builder.position_at_end(cell_loop_body);
+    let char_ptr = unsafe {
+        builder
+            .build_in_bounds_gep(i8_type, initial_tape, &[cell_index], "char_ptr")
+            .unwrap()
+    };
+    let char_value = builder
+        .build_load(i8_type, char_ptr, "char")
+        .unwrap()
+        .into_int_value();
+    let char_index = builder
+        .build_int_z_extend(char_value, i32_type, "char_index")
+        .unwrap();
+    let char_symbol_ptr = unsafe {
+        builder
+            .build_in_bounds_gep(
+                char_symbols_type,
+                char_symbols_global.as_pointer_value(),
+                &[i32_type.const_zero(), char_index],
+                "char_symbol_ptr",
+            )
+            .unwrap()
+    };
+    let char_symbol = builder
+        .build_load(i8_type, char_symbol_ptr, "char_symbol")
+        .unwrap()
+        .into_int_value();
+    let is_unknown = builder
+        .build_int_compare(
+            IntPredicate::EQ,
+            char_symbol,
+            i8_type.const_all_ones(),
+            "is_unknown",
+        )
+        .unwrap();
+    builder.build_conditional_branch(is_unknown, unknown_symbol, store_symbol);
+
+    builder.position_at_end(unknown_symbol);
+    let unknown_symbol_format = builder
+        .build_global_string_ptr(
+            "Unknown symbol '%c' in the initial tape\n",
+            "unknown_symbol_format",
+        )
+        .unwrap();
+    builder.build_call(
+        dprintf_fn,
+        &[
+            stderr.into(),
+            unknown_symbol_format.as_pointer_value().into(),
+            char_index.into(),
+        ],
+        "unknown_symbol_call",
+    );
+    builder.build_return(Some(&i32_type.const_int(1, false)));
+
+    builder.position_at_end(store_symbol);
+    let cell_ptr = unsafe {
+        builder
+            .build_in_bounds_gep(i8_type, tape_ptr, &[cell_index], "cell_ptr")
+            .unwrap()
+    };
+    builder.build_store(cell_ptr, char_symbol);
+    let next_cell_index = builder
+        .build_int_add(cell_index, i32_type.const_int(1, false), "next_cell_index")
+        .unwrap();
+    builder.build_store(cell_index_ptr, next_cell_index);
+    builder.build_unconditional_branch(cell_loop);
+
+    builder.position_at_end(cell_loop_end);
+    builder.build_return(Some(&i32_type.const_zero()));
+    load_tape_fn
 }
 
 // TODO - Write this to minimise the work for printing something
@@ -22,18 +1011,24 @@ pub trait ToLlvmIr {
 // }
 
 impl ToLlvmIr for ParseTree {
-    fn to_llvm_ir(&self) -> String {
+    fn try_to_llvm_ir_with(&self, options: &LlvmCodegenOptions) -> Result<String, String> {
+        // Tracing reads the state of every step from the state variable
+        if options.dispatch == LlvmDispatch::Threaded && options.output == LlvmOutput::Trace {
+            return Err("The threaded dispatch cannot trace the steps".to_string());
+        }
+
         // Create LLVM context, module, and builder
         let context = Context::create();
         let module = context.create_module("tape_machine_fixed");
         let builder = context.create_builder();
 
-        // Set target triple for macOS on ARM64
-        let triple = TargetTriple::create("arm64-apple-macosx13.0.0");
+        // Target the host, where clang builds and runs the program
+        let triple = TargetMachine::get_default_triple();
         module.set_triple(&triple);
 
         // Define basic LLVM types
         let i32_type = context.i32_type();
+        let i64_type = context.i64_type();
         let i8_type = context.i8_type();
         let ptr_type = context.ptr_type(AddressSpace::default());
 
@@ -47,9 +1042,101 @@ impl ToLlvmIr for ParseTree {
         let scanf_type = i32_type.fn_type(&[ptr_type.into()], true);
         let scanf_fn = module.add_function("scanf", scanf_type, None);
 
-        // Define main function
-        let main_type = i32_type.fn_type(&[], false);
+        // Declare the helpers used for filling the tape and reading the trace settings
+        let memset_type =
+            ptr_type.fn_type(&[ptr_type.into(), i32_type.into(), i64_type.into()], false);
+        let memset_fn = module.add_function("memset", memset_type, None);
+
+        let getenv_type = ptr_type.fn_type(&[ptr_type.into()], false);
+        let getenv_fn = module.add_function("getenv", getenv_type, None);
+
+        let strcmp_type = i32_type.fn_type(&[ptr_type.into(), ptr_type.into()], false);
+        let strcmp_fn = module.add_function("strcmp", strcmp_type, None);
+
+        let strlen_type = i64_type.fn_type(&[ptr_type.into()], false);
+        let strlen_fn = module.add_function("strlen", strlen_type, None);
+
+        // Used to stop the machine when the head leaves the tape
+        let dprintf_type = i32_type.fn_type(&[i32_type.into(), ptr_type.into()], true);
+        let dprintf_fn = module.add_function("dprintf", dprintf_type, None);
+
+        let exit_type = context.void_type().fn_type(&[i32_type.into()], false);
+        let exit_fn = module.add_function("exit", exit_type, None);
+
+        // Used to read the command line arguments
+        let sscanf_type = i32_type.fn_type(&[ptr_type.into(), ptr_type.into()], true);
+        let sscanf_fn = module.add_function("sscanf", sscanf_type, None);
+        let parse_args_fn = build_parse_args_fn(
+            &context, &module, &builder, strcmp_fn, strlen_fn, sscanf_fn, dprintf_fn,
+        );
+        let load_tape_fn = build_load_tape_fn(
+            &context,
+            &module,
+            &builder,
+            strlen_fn,
+            dprintf_fn,
+            &self.symbols,
+        );
+
+        // Define main function, `main(argc, argv)`
+        let main_type = i32_type.fn_type(&[i32_type.into(), ptr_type.into()], false);
         let main_fn = module.add_function("main", main_type, None);
+        let argc = main_fn.get_nth_param(0).unwrap().into_int_value();
+        let argv = main_fn.get_nth_param(1).unwrap().into_pointer_value();
+
+        let debug_info = options.debug_info.as_ref().map(|path| {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let directory = path
+                .parent()
+                .map(|parent| parent.to_string_lossy())
+                .unwrap_or_default();
+            let (debug_builder, compile_unit) = module.create_debug_info_builder(
+                true,
+                DWARFSourceLanguage::C,
+                &file_name,
+                &directory,
+                "state_machine_compiler_rust",
+                options.opt_level != LlvmOptLevel::O0,
+                "",
+                0,
+                "",
+                DWARFEmissionKind::Full,
+                0,
+                false,
+                false,
+                "",
+                "",
+            );
+            let int_type = debug_builder
+                .create_basic_type("int", 32, DW_ATE_SIGNED, DIFlags::PUBLIC)
+                .unwrap();
+            let main_type = debug_builder.create_subroutine_type(
+                compile_unit.get_file(),
+                Some(int_type.as_type()),
+                &[],
+                DIFlags::PUBLIC,
+            );
+            let main_subprogram = debug_builder.create_function(
+                compile_unit.get_file().as_debug_info_scope(),
+                "main",
+                None,
+                compile_unit.get_file(),
+                1,
+                main_type,
+                false,
+                true,
+                1,
+                DIFlags::PUBLIC,
+                options.opt_level != LlvmOptLevel::O0,
+            );
+            main_fn.set_subprogram(main_subprogram);
+            module.add_basic_value_flag(
+                "Debug Info Version",
+                FlagBehavior::Warning,
+                i32_type.const_int(DEBUG_METADATA_VERSION, false),
+            );
+            (debug_builder, main_subprogram)
+        });
 
         // Actual instruction building starts from here
         let entry = context.append_basic_block(main_fn, "entry");
@@ -58,9 +1145,59 @@ impl ToLlvmIr for ParseTree {
         // Allocate and initialize variables
         let num_steps_ptr = builder.build_alloca(i32_type, "num_steps_ptr").unwrap();
         let arr_size_ptr = builder.build_alloca(i32_type, "arr_size_ptr").unwrap();
+        let initial_tape_ptr = builder.build_alloca(ptr_type, "initial_tape_ptr").unwrap();
+        builder.build_store(initial_tape_ptr, ptr_type.const_null());
         let i32_0 = i32_type.const_int(0, false);
 
+        // Read the arguments when there are some, otherwise prompt for the input
+        let read_args = context.append_basic_block(main_fn, "read_args");
+        let invalid_args = context.append_basic_block(main_fn, "invalid_args");
+        let prompt_input = context.append_basic_block(main_fn, "prompt_input");
+        let input_ready = context.append_basic_block(main_fn, "input_ready");
+        let has_args = builder
+            .build_int_compare(
+                IntPredicate::SGT,
+                argc,
+                i32_type.const_int(1, false),
+                "has_args",
+            )
+            .unwrap();
+        builder.build_conditional_branch(has_args, read_args, prompt_input);
+
+        builder.position_at_end(read_args);
+        let parse_args_result = builder
+            .build_call(
+                parse_args_fn,
+                &[
+                    argc.into(),
+                    argv.into(),
+                    num_steps_ptr.into(),
+                    arr_size_ptr.into(),
+                    initial_tape_ptr.into(),
+                ],
+                "parse_args_call",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let are_args_valid = builder
+            .build_int_compare(IntPredicate::EQ, parse_args_result, i32_0, "are_args_valid")
+            .unwrap();
+        builder.build_conditional_branch(are_args_valid, input_ready, invalid_args);
+
+        // Same exit code as a command line error of clap
+        builder.position_at_end(invalid_args);
+        builder.build_call(
+            exit_fn,
+            &[i32_type.const_int(2, false).into()],
+            "invalid_args_exit_call",
+        );
+        builder.build_unreachable();
+
         // Prompt user for input (number of steps)
+        builder.position_at_end(prompt_input);
         let num_steps_prompt = builder
             .build_global_string_ptr("Enter number of steps: ", "num_steps_prompt")
             .unwrap();
@@ -78,13 +1215,6 @@ impl ToLlvmIr for ParseTree {
             "scanf_call_1",
         );
 
-        // Load num_steps value
-        let num_steps = builder
-            .build_load(i32_type, num_steps_ptr, "num_steps")
-            .unwrap()
-            .into_int_value();
-        // let num_steps = builder.build_load(i32_type, num_steps_ptr.as_basic_value_enum().into_pointer_value(), "num_steps").into_int_value();
-
         // Prompt user for input (array size)
         let arr_size_prompt = builder
             .build_global_string_ptr("Enter array size: ", "arr_size_prompt")
@@ -99,8 +1229,18 @@ impl ToLlvmIr for ParseTree {
             &[scanf_format.as_pointer_value().into(), arr_size_ptr.into()],
             "scanf_call_2",
         );
+        builder.build_unconditional_branch(input_ready);
+        builder.position_at_end(input_ready);
+
+        // Load num_steps value
+        let num_steps = builder
+            .build_load(i32_type, num_steps_ptr, "num_steps")
+            .unwrap()
+            .into_int_value();
+        // let num_steps = builder.build_load(i32_type, num_steps_ptr.as_basic_value_enum().into_pointer_value(), "num_steps").into_int_value();
 
         // Allocate tape dynamically using malloc
+        // Every cell holds the index of a symbol in self.symbols
         let arr_size = builder
             .build_load(i32_type, arr_size_ptr, "arr_size")
             .unwrap()
@@ -114,9 +1254,89 @@ impl ToLlvmIr for ParseTree {
             .into_pointer_value();
 
         // Initialize tape with 'X'
+        let total_symbols = self.symbols.len();
+        let total_states = self.states.len();
+        let blank_symbol_index = self.symbols.iter().position(|s| s == "X").unwrap();
+        let arr_size_i64 = builder
+            .build_int_z_extend(arr_size, i64_type, "arr_size_i64")
+            .unwrap();
+        builder.build_call(
+            memset_fn,
+            &[
+                tape_ptr.into(),
+                i32_type
+                    .const_int(blank_symbol_index.try_into().unwrap(), false)
+                    .into(),
+                arr_size_i64.into(),
+            ],
+            "tape_memset_call",
+        );
+
+        // Write the initial tape given as argument at the start of the tape
+        let load_initial_tape = context.append_basic_block(main_fn, "load_initial_tape");
+        let tape_ready = context.append_basic_block(main_fn, "tape_ready");
+        let initial_tape = builder
+            .build_load(ptr_type, initial_tape_ptr, "initial_tape")
+            .unwrap()
+            .into_pointer_value();
+        let has_initial_tape = builder
+            .build_is_not_null(initial_tape, "has_initial_tape")
+            .unwrap();
+        builder.build_conditional_branch(has_initial_tape, load_initial_tape, tape_ready);
+
+        builder.position_at_end(load_initial_tape);
+        let load_tape_result = builder
+            .build_call(
+                load_tape_fn,
+                &[tape_ptr.into(), arr_size.into(), initial_tape.into()],
+                "load_tape_call",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let is_tape_loaded = builder
+            .build_int_compare(IntPredicate::EQ, load_tape_result, i32_0, "is_tape_loaded")
+            .unwrap();
+        builder.build_conditional_branch(is_tape_loaded, tape_ready, invalid_args);
+        builder.position_at_end(tape_ready);
+
+        // Trace settings: compile time defaults, overridden by the environment
+        let trace_settings = if options.output == LlvmOutput::Trace {
+            let trace_level = build_env_choice(
+                &builder,
+                getenv_fn,
+                strcmp_fn,
+                TRACE_ENV_VAR,
+                &[
+                    (TraceLevel::None.as_str(), TraceLevel::None as u64),
+                    (
+                        TraceLevel::Transitions.as_str(),
+                        TraceLevel::Transitions as u64,
+                    ),
+                    (TraceLevel::Full.as_str(), TraceLevel::Full as u64),
+                ],
+                i32_type.const_int(options.trace.level as u64, false),
+                "trace_level",
+            );
+            let trace_format = build_env_choice(
+                &builder,
+                getenv_fn,
+                strcmp_fn,
+                TRACE_FORMAT_ENV_VAR,
+                &[
+                    (TraceFormat::Human.as_str(), TraceFormat::Human as u64),
+                    (TraceFormat::Compact.as_str(), TraceFormat::Compact as u64),
+                ],
+                i32_type.const_int(options.trace.format as u64, false),
+                "trace_format",
+            );
+            Some((trace_level, trace_format))
+        } else {
+            None
+        };
 
-        let steps_loop = context.append_basic_block(main_fn, "steps_loop");
-        let steps_loop_body = context.append_basic_block(main_fn, "steps_loop_body");
         let steps_loop_end = context.append_basic_block(main_fn, "steps_loop_end");
         let main_return = context.append_basic_block(main_fn, "main_return");
 
@@ -125,138 +1345,76 @@ impl ToLlvmIr for ParseTree {
             .build_alloca(i32_type, "current_tape_index_ptr")
             .unwrap();
         let current_step_ptr = builder.build_alloca(i32_type, "current_step_ptr").unwrap();
-        let current_symbol_index_ptr = builder
-            .build_alloca(i32_type, "current_symbol_index_ptr")
-            .unwrap();
-        let current_state_index_ptr = builder
-            .build_alloca(i32_type, "current_state_index_ptr")
-            .unwrap();
         builder.build_store(current_tape_index_ptr, i32_0);
         builder.build_store(current_step_ptr, i32_0);
 
-        // TODO - Update it based on what the initial symbol is
-        builder.build_store(current_symbol_index_ptr, i32_0);
-        builder.build_store(current_state_index_ptr, i32_0);
-
-        let print_steps_format = builder
-            .build_global_string_ptr("All Symbols: %s\n", "print_current_step_format")
-            .unwrap();
-
-        let symbol_index_value_mapping = self
-            .symbols
+        let initial_state_index = self
+            .states
             .iter()
-            .enumerate()
-            .map(|(i, s)| format!("{}:{}", i, s))
-            .collect::<Vec<String>>()
-            .join(", ");
-
-        let symbol_index_value_mapping_ptr = builder
-            .build_global_string_ptr(&symbol_index_value_mapping, "symbol_index_value_mapping")
+            .position(|s| s == &self.initial_state)
             .unwrap();
-        builder.build_call(
-            printf_fn,
-            &[
-                print_steps_format.as_pointer_value().into(),
-                symbol_index_value_mapping_ptr.as_pointer_value().into(),
-            ],
-            "print_all_symbols",
-        );
+        // Only the switch dispatch keeps the state in a variable
+        let current_state_index_ptr = (options.dispatch == LlvmDispatch::Switch).then(|| {
+            let current_state_index_ptr = builder
+                .build_alloca(i32_type, "current_state_index_ptr")
+                .unwrap();
+            builder.build_store(
+                current_state_index_ptr,
+                i32_type.const_int(initial_state_index.try_into().unwrap(), false),
+            );
+            current_state_index_ptr
+        });
 
-        let print_steps_format = builder
-            .build_global_string_ptr("All States: %s\n", "print_current_step_format")
-            .unwrap();
+        // Index of every symbol and state, to read the trace
+        if trace_settings.is_some() {
+            let print_steps_format = builder
+                .build_global_string_ptr("All Symbols: %s\n", "print_current_step_format")
+                .unwrap();
 
-        let state_index_value_mapping = self
-            .states
-            .iter()
-            .enumerate()
-            .map(|(i, s)| format!("{}:{}", i, s))
-            .collect::<Vec<String>>()
-            .join(", ");
+            let symbol_index_value_mapping = self
+                .symbols
+                .iter()
+                .enumerate()
+                .map(|(i, s)| format!("{}:{}", i, s))
+                .collect::<Vec<String>>()
+                .join(", ");
 
-        let state_index_value_mapping_ptr = builder
-            .build_global_string_ptr(&state_index_value_mapping, "state_index_value_mapping")
-            .unwrap();
-        builder.build_call(
-            printf_fn,
-            &[
-                print_steps_format.as_pointer_value().into(),
-                state_index_value_mapping_ptr.as_pointer_value().into(),
-            ],
-            "print_all_states",
-        );
-        builder.build_unconditional_branch(steps_loop);
+            let symbol_index_value_mapping_ptr = builder
+                .build_global_string_ptr(&symbol_index_value_mapping, "symbol_index_value_mapping")
+                .unwrap();
+            builder.build_call(
+                printf_fn,
+                &[
+                    print_steps_format.as_pointer_value().into(),
+                    symbol_index_value_mapping_ptr.as_pointer_value().into(),
+                ],
+                "print_all_symbols",
+            );
 
-        // Loop condition
-        builder.position_at_end(steps_loop);
-        let current_step_val = builder
-            .build_load(i32_type, current_step_ptr, "current_step_val")
-            .unwrap()
-            .into_int_value();
-        let current_symbol_index = builder
-            .build_load(
-                i32_type,
-                current_symbol_index_ptr,
-                "current_symbol_index_val",
-            )
-            .unwrap()
-            .into_int_value();
-        let current_state_index = builder
-            .build_load(i32_type, current_state_index_ptr, "current_state_index_val")
-            .unwrap()
-            .into_int_value();
-        // current_symbol_index * total_states + current_state_index
-        let total_symbols = self.symbols.len();
-        let total_states = self.states.len();
-        let lhs = builder
-            .build_int_mul(
-                current_symbol_index,
-                i32_type.const_int(total_states.try_into().unwrap(), false),
-                "current_symbol_index__x__total_states",
-            )
-            .unwrap();
-        let current_switch_case_number = builder
-            .build_int_add(lhs, current_state_index, "current_switch_case_number")
-            .unwrap();
-        let step_limit_cond = builder
-            .build_int_compare(
-                IntPredicate::ULT,
-                current_step_val,
-                num_steps,
-                "step_limit_cond",
-            )
-            .unwrap();
-        builder.build_conditional_branch(step_limit_cond, steps_loop_body, steps_loop_end);
+            let print_steps_format = builder
+                .build_global_string_ptr("All States: %s\n", "print_current_step_format")
+                .unwrap();
 
-        // // Loop body: initialize tape with 'X'
-        builder.position_at_end(steps_loop_body);
-        let print_steps_format = builder
-            .build_global_string_ptr("Current step: %d\n", "print_current_step_format")
-            .unwrap();
-        builder.build_call(
-            printf_fn,
-            &[
-                print_steps_format.as_pointer_value().into(),
-                current_step_val.into(),
-            ],
-            "current_step_print_call",
-        );
+            let state_index_value_mapping = self
+                .states
+                .iter()
+                .enumerate()
+                .map(|(i, s)| format!("{}:{}", i, s))
+                .collect::<Vec<String>>()
+                .join(", ");
 
-        // Build a switch statement based on current step value
-        // let current_step_nature = builder
-        //     .build_int_signed_rem(
-        //         current_step_val,
-        //         i32_type.const_int(4, false),
-        //         "is_even_check",
-        //     )
-        //     .unwrap();
-        // Later we will use the current state and current symbol value
-        let switch_default = context.append_basic_block(main_fn, "switch_default");
-        let after_switch = context.append_basic_block(main_fn, "after_switch");
-        let mut case_switch_mapping = vec![];
-        let print_steps_format = builder
-            .build_global_string_ptr("Symbol: %s State: %s\n", "print_current_step_format")
-            .unwrap();
+            let state_index_value_mapping_ptr = builder
+                .build_global_string_ptr(&state_index_value_mapping, "state_index_value_mapping")
+                .unwrap();
+            builder.build_call(
+                printf_fn,
+                &[
+                    print_steps_format.as_pointer_value().into(),
+                    state_index_value_mapping_ptr.as_pointer_value().into(),
+                ],
+                "print_all_states",
+            );
+        }
 
         let mut symbol_global_value_map: Vec<inkwell::values::GlobalValue> =
             Vec::with_capacity(total_symbols);
@@ -286,6 +1444,260 @@ impl ToLlvmIr for ParseTree {
             state_global_value_map.push(state);
         }
 
+        // Lookup tables from index to name, used while tracing
+        let symbol_names =
+            build_names_array(&context, &module, &symbol_global_value_map, "symbol_names");
+        let state_names =
+            build_names_array(&context, &module, &state_global_value_map, "state_names");
+
+        // The head starts on the first cell, there must be one
+        let tape_overflow = context.append_basic_block(main_fn, "tape_overflow");
+        let is_tape_empty = builder
+            .build_int_compare(IntPredicate::EQ, arr_size, i32_0, "is_tape_empty")
+            .unwrap();
+        // The threaded dispatch starts in the block of the initial state
+        let state_blocks: Vec<BasicBlock> = match options.dispatch {
+            LlvmDispatch::Switch => vec![],
+            LlvmDispatch::Threaded => self
+                .states
+                .iter()
+                .map(|state| context.append_basic_block(main_fn, &format!("state_{}", state)))
+                .collect(),
+        };
+        let steps_loop = (options.dispatch == LlvmDispatch::Switch)
+            .then(|| context.append_basic_block(main_fn, "steps_loop"));
+        let steps_start = steps_loop.unwrap_or_else(|| state_blocks[initial_state_index]);
+        builder.build_conditional_branch(is_tape_empty, tape_overflow, steps_start);
+
+        let print_tape_fn = build_print_tape_fn(
+            &context,
+            &module,
+            &builder,
+            printf_fn,
+            symbol_names,
+            total_symbols as u32,
+            None,
+        );
+
+        // The switch dispatch reads the state and the symbol at the start of
+        // every step of one loop
+        let switch_dispatch = if let (Some(steps_loop), Some(current_state_index_ptr)) =
+            (steps_loop, current_state_index_ptr)
+        {
+            // Loop condition
+            let steps_loop_body = context.append_basic_block(main_fn, "steps_loop_body");
+            builder.position_at_end(steps_loop);
+            let current_step_val = builder
+                .build_load(i32_type, current_step_ptr, "current_step_val")
+                .unwrap()
+                .into_int_value();
+            let current_tape_index = builder
+                .build_load(i32_type, current_tape_index_ptr, "current_tape_index_val")
+                .unwrap()
+                .into_int_value();
+            // The current symbol is whatever is written on the tape under the head
+            let current_symbol_index =
+                load_tape_symbol_index(&context, &builder, tape_ptr, current_tape_index);
+            let current_state_index = builder
+                .build_load(i32_type, current_state_index_ptr, "current_state_index_val")
+                .unwrap()
+                .into_int_value();
+            // current_symbol_index * total_states + current_state_index
+            let lhs = builder
+                .build_int_mul(
+                    current_symbol_index,
+                    i32_type.const_int(total_states.try_into().unwrap(), false),
+                    "current_symbol_index__x__total_states",
+                )
+                .unwrap();
+            let current_switch_case_number = builder
+                .build_int_add(lhs, current_state_index, "current_switch_case_number")
+                .unwrap();
+            let step_limit_cond = builder
+                .build_int_compare(
+                    IntPredicate::ULT,
+                    current_step_val,
+                    num_steps,
+                    "step_limit_cond",
+                )
+                .unwrap();
+            builder.build_conditional_branch(step_limit_cond, steps_loop_body, steps_loop_end);
+
+            // Loop body: trace the current step, then dispatch on (symbol, state)
+            let step_dispatch = context.append_basic_block(main_fn, "step_dispatch");
+            builder.position_at_end(steps_loop_body);
+            if let Some((trace_level, trace_format)) = trace_settings {
+                let trace_step = context.append_basic_block(main_fn, "trace_step");
+                let trace_human = context.append_basic_block(main_fn, "trace_human");
+                let trace_human_tape = context.append_basic_block(main_fn, "trace_human_tape");
+                let trace_compact = context.append_basic_block(main_fn, "trace_compact");
+                let trace_compact_tape = context.append_basic_block(main_fn, "trace_compact_tape");
+                let trace_compact_end = context.append_basic_block(main_fn, "trace_compact_end");
+
+                let is_tracing = builder
+                    .build_int_compare(
+                        IntPredicate::UGE,
+                        trace_level,
+                        i32_type.const_int(TraceLevel::Transitions as u64, false),
+                        "is_tracing",
+                    )
+                    .unwrap();
+                let is_tracing_tape = builder
+                    .build_int_compare(
+                        IntPredicate::UGE,
+                        trace_level,
+                        i32_type.const_int(TraceLevel::Full as u64, false),
+                        "is_tracing_tape",
+                    )
+                    .unwrap();
+                builder.build_conditional_branch(is_tracing, trace_step, step_dispatch);
+
+                builder.position_at_end(trace_step);
+                let current_state_name = load_name(
+                    &context,
+                    &builder,
+                    state_names,
+                    total_states as u32,
+                    current_state_index,
+                );
+                let current_symbol_name = load_name(
+                    &context,
+                    &builder,
+                    symbol_names,
+                    total_symbols as u32,
+                    current_symbol_index,
+                );
+                let is_compact = builder
+                    .build_int_compare(
+                        IntPredicate::EQ,
+                        trace_format,
+                        i32_type.const_int(TraceFormat::Compact as u64, false),
+                        "is_compact",
+                    )
+                    .unwrap();
+                builder.build_conditional_branch(is_compact, trace_compact, trace_human);
+
+                // Step: <step> State: <state> Symbol: <symbol>
+                // <tape>
+                //    ^
+                builder.position_at_end(trace_human);
+                let print_steps_format = builder
+                    .build_global_string_ptr(
+                        "Step: %d State: %s Symbol: %s\n",
+                        "trace_human_format",
+                    )
+                    .unwrap();
+                builder.build_call(
+                    printf_fn,
+                    &[
+                        print_steps_format.as_pointer_value().into(),
+                        current_step_val.into(),
+                        current_state_name.into(),
+                        current_symbol_name.into(),
+                    ],
+                    "trace_human_call",
+                );
+                builder.build_conditional_branch(is_tracing_tape, trace_human_tape, step_dispatch);
+
+                let print_head_marker_fn = build_print_head_marker_fn(
+                    &context,
+                    &module,
+                    &builder,
+                    printf_fn,
+                    strlen_fn,
+                    symbol_names,
+                    total_symbols as u32,
+                );
+
+                builder.position_at_end(trace_human_tape);
+                let newline_format = builder
+                    .build_global_string_ptr("\n", "newline_format")
+                    .unwrap();
+                builder.build_call(
+                    print_tape_fn,
+                    &[tape_ptr.into(), arr_size.into()],
+                    "print_tape_call",
+                );
+                builder.build_call(
+                    printf_fn,
+                    &[newline_format.as_pointer_value().into()],
+                    "print_newline_call",
+                );
+                builder.build_call(
+                    print_head_marker_fn,
+                    &[tape_ptr.into(), current_tape_index.into()],
+                    "print_head_marker_call",
+                );
+                builder.build_unconditional_branch(step_dispatch);
+
+                // <step>\t<state>\t<head>\t<symbol>[\t<tape>]
+                builder.position_at_end(trace_compact);
+                let print_steps_format = builder
+                    .build_global_string_ptr("%d\t%s\t%d\t%s", "trace_compact_format")
+                    .unwrap();
+                builder.build_call(
+                    printf_fn,
+                    &[
+                        print_steps_format.as_pointer_value().into(),
+                        current_step_val.into(),
+                        current_state_name.into(),
+                        current_tape_index.into(),
+                        current_symbol_name.into(),
+                    ],
+                    "trace_compact_call",
+                );
+                builder.build_conditional_branch(
+                    is_tracing_tape,
+                    trace_compact_tape,
+                    trace_compact_end,
+                );
+
+                builder.position_at_end(trace_compact_tape);
+                let tab_format = builder.build_global_string_ptr("\t", "tab_format").unwrap();
+                builder.build_call(
+                    printf_fn,
+                    &[tab_format.as_pointer_value().into()],
+                    "print_tab_call",
+                );
+                builder.build_call(
+                    print_tape_fn,
+                    &[tape_ptr.into(), arr_size.into()],
+                    "print_tape_call",
+                );
+                builder.build_unconditional_branch(trace_compact_end);
+
+                builder.position_at_end(trace_compact_end);
+                builder.build_call(
+                    printf_fn,
+                    &[newline_format.as_pointer_value().into()],
+                    "print_newline_call",
+                );
+                builder.build_unconditional_branch(step_dispatch);
+            } else {
+                builder.build_unconditional_branch(step_dispatch);
+            }
+            Some((
+                current_state_index_ptr,
+                current_step_val,
+                current_symbol_index,
+                current_state_index,
+                current_switch_case_number,
+                step_dispatch,
+            ))
+        } else {
+            None
+        };
+
+        // Blocks between the transitions of the switch dispatch
+        let switch_blocks = switch_dispatch.map(|_| {
+            (
+                context.append_basic_block(main_fn, "switch_default"),
+                context.append_basic_block(main_fn, "after_switch"),
+            )
+        });
+        let no_transition = context.append_basic_block(main_fn, "no_transition");
+        let mut case_switch_mapping = vec![];
+
         // We will cover all cases for all combination of symbols and states
         // Total symbols = 5
         // Total states = 6
@@ -298,8 +1710,6 @@ impl ToLlvmIr for ParseTree {
             // let switch_case_number = sym_index * total_states + state_index;
             let sym_index = switch_case_number / total_states;
             let state_index = switch_case_number % total_states;
-            let symbol = symbol_global_value_map[sym_index];
-            let state = state_global_value_map[state_index];
             let switch_case = context.append_basic_block(
                 main_fn,
                 &format!(
@@ -307,17 +1717,6 @@ impl ToLlvmIr for ParseTree {
                     self.states[state_index], self.symbols[sym_index]
                 ),
             );
-            builder.position_at_end(switch_case);
-            builder.build_call(
-                printf_fn,
-                &[
-                    print_steps_format.as_pointer_value().into(),
-                    symbol.as_pointer_value().into(),
-                    state.as_pointer_value().into(),
-                ],
-                "current_step_print_call",
-            );
-            builder.build_unconditional_branch(after_switch);
             case_switch_mapping.push((
                 i32_type.const_int(switch_case_number.try_into().unwrap(), false),
                 switch_case,
@@ -343,8 +1742,9 @@ impl ToLlvmIr for ParseTree {
         // Then we will process this to generate
         // [{switch_case_number,steps, final_state}] vector map
 
-        let mut state_transition_map: HashMap<String, Vec<Transition>> = HashMap::new();
-        for transition in self.transitions.iter() {
+        // The OR conditions of a state come before its star one
+        let mut state_transition_map: HashMap<String, Vec<&Transition>> = HashMap::new();
+        for transition in self.transitions_star_last() {
             let initial_state = &transition.initial_state;
             let condition = &transition.condition;
             let steps = &transition.steps;
@@ -352,24 +1752,15 @@ impl ToLlvmIr for ParseTree {
             state_transition_map
                 .entry(initial_state.clone())
                 .or_default()
-                .push(transition.clone());
+                .push(transition);
         }
 
         // initial_state -> [{symbols, steps, final_state}]
-        let mut processed_state_transition_map: HashMap<
-            String,
-            Vec<(Vec<String>, Vec<TransitionStep>, String)>,
-        > = HashMap::new();
+        let mut processed_state_transition_map: HashMap<String, Vec<ProcessedTransition>> =
+            HashMap::new();
 
-        for (initial_state, transitions) in state_transition_map.iter_mut() {
+        for (initial_state, transitions) in state_transition_map.iter() {
             let mut inserted_symbols = HashSet::new();
-            // Sort the transitions based on the condition so that we always process
-            // OR condition first
-            transitions.sort_by(|a, b| match (&a.condition, &b.condition) {
-                (Condition::Star, Condition::OR(_)) => std::cmp::Ordering::Greater,
-                (Condition::OR(_), Condition::Star) => std::cmp::Ordering::Less,
-                _ => std::cmp::Ordering::Equal,
-            });
             for transition in transitions.iter() {
                 let final_state = transition.final_state.clone();
                 let steps = transition.steps.clone();
@@ -395,33 +1786,52 @@ impl ToLlvmIr for ParseTree {
                 processed_state_transition_map
                     .entry(initial_state.clone())
                     .or_default()
-                    .push((symbols, steps, final_state.clone()));
+                    .push((symbols, steps, final_state.clone(), transition.line));
             }
         }
 
+        // Cases already given a transition, the first matching transition wins
+        let mut filled_switch_cases: HashSet<usize> = HashSet::new();
         for (initial_state, transitions) in processed_state_transition_map.iter() {
-            for (matching_symbols, steps, final_state) in transitions {
+            // Undeclared states were already reported by the parser
+            let Some(&state_index) = state_to_index_map.get(initial_state) else {
+                continue;
+            };
+            for (matching_symbols, steps, final_state, line) in transitions {
+                let Some(&final_state_index) = state_to_index_map.get(final_state) else {
+                    continue;
+                };
                 // Switch case numbers for this transition
                 let switch_case_numbers = matching_symbols
                     .iter()
-                    .map(|s| {
-                        let symbol_index = symbol_to_index_map[s];
-                        let state_index = state_to_index_map[initial_state];
-                        symbol_index * total_states + state_index
-                    })
+                    .filter_map(|s| symbol_to_index_map.get(s))
+                    .map(|symbol_index| symbol_index * total_states + state_index)
                     .collect::<Vec<usize>>();
 
                 for switch_case_number in switch_case_numbers {
+                    if !filled_switch_cases.insert(switch_case_number) {
+                        continue;
+                    }
                     let (_, switch_case) = case_switch_mapping[switch_case_number];
-                    // builder.position_at_end(switch_case);
 
-                    // Position at the instruction just before the last instruction
-                    let unconditional_jump = switch_case.get_terminator().unwrap();
-                    builder.position_before(&unconditional_jump);
+                    // Every move of the head checks it is still on the tape
+                    // before going on
+                    builder.position_at_end(switch_case);
+                    if let Some((debug_builder, main_subprogram)) = &debug_info {
+                        let location = debug_builder.create_debug_location(
+                            &context,
+                            *line as u32,
+                            0,
+                            main_subprogram.as_debug_info_scope(),
+                            None,
+                        );
+                        builder.set_current_debug_location(location);
+                    }
+                    let mut current_block = switch_case;
                     for step in steps {
                         match step {
-                            TransitionStep::L => {
-                                let mut current_tape_index_value = builder
+                            TransitionStep::L | TransitionStep::R => {
+                                let current_tape_index_value = builder
                                     .build_load(
                                         i32_type,
                                         current_tape_index_ptr,
@@ -429,20 +1839,49 @@ impl ToLlvmIr for ParseTree {
                                     )
                                     .unwrap()
                                     .into_int_value();
-                                current_tape_index_value = builder
-                                    .build_int_sub(
+                                // Moving left of the first cell wraps around to
+                                // an index past the end of the tape
+                                let moved_tape_index_value = if *step == TransitionStep::L {
+                                    builder.build_int_sub(
                                         current_tape_index_value,
                                         i32_type.const_int(1, false),
                                         "move_left",
                                     )
+                                } else {
+                                    builder.build_int_add(
+                                        current_tape_index_value,
+                                        i32_type.const_int(1, false),
+                                        "move_right",
+                                    )
+                                }
+                                .unwrap();
+                                builder.build_store(current_tape_index_ptr, moved_tape_index_value);
+                                let is_on_tape = builder
+                                    .build_int_compare(
+                                        IntPredicate::ULT,
+                                        moved_tape_index_value,
+                                        arr_size,
+                                        "is_on_tape",
+                                    )
                                     .unwrap();
-                                builder
-                                    .build_store(current_tape_index_ptr, current_tape_index_value);
-                                // Move left
+                                let on_tape = context.insert_basic_block_after(
+                                    current_block,
+                                    &format!(
+                                        "{}_on_tape",
+                                        switch_case.get_name().to_str().unwrap()
+                                    ),
+                                );
+                                builder.build_conditional_branch(
+                                    is_on_tape,
+                                    on_tape,
+                                    tape_overflow,
+                                );
+                                builder.position_at_end(on_tape);
+                                current_block = on_tape;
                             }
-                            TransitionStep::R => {
-                                // Move right
-                                let mut current_tape_index_value = builder
+                            TransitionStep::P(symbol) => {
+                                // Write the symbol index in the current cell
+                                let current_tape_index_value = builder
                                     .build_load(
                                         i32_type,
                                         current_tape_index_ptr,
@@ -450,131 +1889,306 @@ impl ToLlvmIr for ParseTree {
                                     )
                                     .unwrap()
                                     .into_int_value();
-                                current_tape_index_value = builder
-                                    .build_int_add(
-                                        current_tape_index_value,
-                                        i32_type.const_int(1, false),
-                                        "move_right",
-                                    )
-                                    .unwrap();
-                                builder
-                                    .build_store(current_tape_index_ptr, current_tape_index_value);
-                            }
-                            TransitionStep::P(symbol) => {
-                                // TODO - Print symbol index in the current index
+                                let cell_ptr = unsafe {
+                                    builder
+                                        .build_in_bounds_gep(
+                                            i8_type,
+                                            tape_ptr,
+                                            &[current_tape_index_value],
+                                            "cell_ptr",
+                                        )
+                                        .unwrap()
+                                };
+                                builder.build_store(
+                                    cell_ptr,
+                                    i8_type.const_int(
+                                        symbol_to_index_map[symbol].try_into().unwrap(),
+                                        false,
+                                    ),
+                                );
                             }
                             TransitionStep::X => {
                                 // Do nothing
                             }
                         }
                     }
+                    match (switch_dispatch, switch_blocks) {
+                        (Some((current_state_index_ptr, ..)), Some((_, after_switch))) => {
+                            builder.build_store(
+                                current_state_index_ptr,
+                                i32_type.const_int(final_state_index.try_into().unwrap(), false),
+                            );
+                            builder.build_unconditional_branch(after_switch);
+                        }
+                        _ => {
+                            // Count the step and go straight to the next state
+                            let current_step_val = builder
+                                .build_load(i32_type, current_step_ptr, "current_step_val")
+                                .unwrap()
+                                .into_int_value();
+                            let updated_current_step_val = builder
+                                .build_int_add(
+                                    current_step_val,
+                                    i32_type.const_int(1, false),
+                                    "current_step_increment",
+                                )
+                                .unwrap();
+                            builder.build_store(current_step_ptr, updated_current_step_val);
+                            builder.build_unconditional_branch(state_blocks[final_state_index]);
+                        }
+                    }
+                    builder.unset_current_debug_location();
                 }
             }
         }
 
-        builder.position_at_end(switch_default);
-        let print_steps_format = builder
-            .build_global_string_ptr("Default Remainder: %d\n", "print_current_step_format")
+        // Machine halts when there is no transition for the current state and symbol
+        for (switch_case_number, (_, switch_case)) in case_switch_mapping.iter().enumerate() {
+            if !filled_switch_cases.contains(&switch_case_number) {
+                builder.position_at_end(*switch_case);
+                builder.build_unconditional_branch(no_transition);
+            }
+        }
+
+        // Threaded: each state reads the symbol under the head and branches to
+        // its case, the last symbol is the default
+        for (state_index, state_block) in state_blocks.iter().enumerate() {
+            let state_dispatch = context.insert_basic_block_after(
+                *state_block,
+                &format!("state_{}_dispatch", self.states[state_index]),
+            );
+            builder.position_at_end(*state_block);
+            let current_step_val = builder
+                .build_load(i32_type, current_step_ptr, "current_step_val")
+                .unwrap()
+                .into_int_value();
+            let step_limit_cond = builder
+                .build_int_compare(
+                    IntPredicate::ULT,
+                    current_step_val,
+                    num_steps,
+                    "step_limit_cond",
+                )
+                .unwrap();
+            builder.build_conditional_branch(step_limit_cond, state_dispatch, steps_loop_end);
+
+            builder.position_at_end(state_dispatch);
+            let current_tape_index = builder
+                .build_load(i32_type, current_tape_index_ptr, "current_tape_index_val")
+                .unwrap()
+                .into_int_value();
+            let current_symbol_index =
+                load_tape_symbol_index(&context, &builder, tape_ptr, current_tape_index);
+            let mut symbol_cases = (0..total_symbols)
+                .map(|symbol_index| {
+                    (
+                        i32_type.const_int(symbol_index as u64, false),
+                        case_switch_mapping[symbol_index * total_states + state_index].1,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let (_, last_symbol_case) = symbol_cases.pop().unwrap();
+            builder.build_switch(current_symbol_index, last_symbol_case, &symbol_cases);
+        }
+
+        builder.position_at_end(no_transition);
+        if options.output != LlvmOutput::Silent {
+            // The threaded dispatch knows them from the case it comes from
+            let (current_state_index, current_symbol_index) = match switch_dispatch {
+                Some((_, _, current_symbol_index, current_state_index, ..)) => {
+                    (current_state_index, current_symbol_index)
+                }
+                None => {
+                    let halted_state_index =
+                        builder.build_phi(i32_type, "halted_state_index").unwrap();
+                    let halted_symbol_index =
+                        builder.build_phi(i32_type, "halted_symbol_index").unwrap();
+                    for (switch_case_number, (_, switch_case)) in
+                        case_switch_mapping.iter().enumerate()
+                    {
+                        if !filled_switch_cases.contains(&switch_case_number) {
+                            halted_state_index.add_incoming(&[(
+                                &i32_type
+                                    .const_int((switch_case_number % total_states) as u64, false),
+                                *switch_case,
+                            )]);
+                            halted_symbol_index.add_incoming(&[(
+                                &i32_type
+                                    .const_int((switch_case_number / total_states) as u64, false),
+                                *switch_case,
+                            )]);
+                        }
+                    }
+                    (
+                        halted_state_index.as_basic_value().into_int_value(),
+                        halted_symbol_index.as_basic_value().into_int_value(),
+                    )
+                }
+            };
+            let no_transition_format = builder
+                .build_global_string_ptr(
+                    "No transition for State: %s Symbol: %s\n",
+                    "no_transition_format",
+                )
+                .unwrap();
+            let halted_state_name = load_name(
+                &context,
+                &builder,
+                state_names,
+                total_states as u32,
+                current_state_index,
+            );
+            let halted_symbol_name = load_name(
+                &context,
+                &builder,
+                symbol_names,
+                total_symbols as u32,
+                current_symbol_index,
+            );
+            builder.build_call(
+                printf_fn,
+                &[
+                    no_transition_format.as_pointer_value().into(),
+                    halted_state_name.into(),
+                    halted_symbol_name.into(),
+                ],
+                "no_transition_print_call",
+            );
+        }
+        builder.build_unconditional_branch(steps_loop_end);
+
+        // tape overflow at step <step>, on stderr with the same exit code as
+        // a panic of the generated Rust program
+        builder.position_at_end(tape_overflow);
+        let overflow_step = builder
+            .build_load(i32_type, current_step_ptr, "overflow_step")
+            .unwrap();
+        let tape_overflow_format = builder
+            .build_global_string_ptr("tape overflow at step %d\n", "tape_overflow_format")
             .unwrap();
         builder.build_call(
-            printf_fn,
+            dprintf_fn,
             &[
-                print_steps_format.as_pointer_value().into(),
-                current_step_val.into(),
+                i32_type.const_int(2, false).into(),
+                tape_overflow_format.as_pointer_value().into(),
+                overflow_step.into(),
             ],
-            "current_step_print_call",
+            "tape_overflow_print_call",
         );
-        builder.build_unconditional_branch(after_switch);
-
-        // builder.position_at_end(switch_even_case);
-        // let print_steps_format = builder
-        //     .build_global_string_ptr("Remainder 0: %d\n", "print_current_step_format")
-        //     .unwrap();
-        // builder.build_call(
-        //     printf_fn,
-        //     &[
-        //         print_steps_format.as_pointer_value().into(),
-        //         current_step_val.into(),
-        //     ],
-        //     "current_step_print_call",
-        // );
-        // builder.build_unconditional_branch(after_switch);
-
-        // builder.position_at_end(switch_odd_case);
-        // let print_steps_format = builder
-        //     .build_global_string_ptr("Remainder 1: %d\n", "print_current_step_format")
-        //     .unwrap();
-        // builder.build_call(
-        //     printf_fn,
-        //     &[
-        //         print_steps_format.as_pointer_value().into(),
-        //         current_step_val.into(),
-        //     ],
-        //     "current_step_print_call",
-        // );
-        // builder.build_unconditional_branch(after_switch);
-
-        // Insert swicht statement in steps loop body
-        builder.position_at_end(steps_loop_body);
-        // print_call(builder, "Switch Default Case", "switch_default_print", printf_fn, , print_call_name);
-        builder.build_switch(
-            current_switch_case_number,
-            switch_default,
-            &case_switch_mapping,
+        builder.build_call(
+            exit_fn,
+            &[i32_type.const_int(101, false).into()],
+            "tape_overflow_exit_call",
         );
+        builder.build_unreachable();
 
-        builder.position_at_end(after_switch);
-        let updated_current_step_val = builder
-            .build_int_add(
-                current_step_val,
-                i32_type.const_int(1, false),
-                "current_step_increment",
-            )
-            .unwrap();
-        builder.build_store(current_step_ptr, updated_current_step_val);
-
-        // TODO - Move this to every switch statement block
-        // This will be updated based on the associated conditions
-        let mut updated_current_symbol_index = builder
-            .build_int_add(
-                current_symbol_index,
-                i32_type.const_int(2, false),
-                "current_symbol_index_increment_1",
-            )
-            .unwrap();
-
-        // TODO - This will be replaced by forcing the flow to go to
-        // Invalid block if the current symbol doesn't exist in the
-        // available options
-        updated_current_symbol_index = builder
-            .build_int_signed_rem(
-                updated_current_symbol_index,
-                i32_type.const_int(total_symbols.try_into().unwrap(), false),
-                "clip_symbol_index",
-            )
-            .unwrap();
+        if let (
+            Some((_, current_step_val, _, _, current_switch_case_number, step_dispatch)),
+            Some((switch_default, after_switch)),
+            Some(steps_loop),
+        ) = (switch_dispatch, switch_blocks, steps_loop)
+        {
+            builder.position_at_end(switch_default);
+            let print_steps_format = builder
+                .build_global_string_ptr("Default Remainder: %d\n", "print_current_step_format")
+                .unwrap();
+            builder.build_call(
+                printf_fn,
+                &[
+                    print_steps_format.as_pointer_value().into(),
+                    current_step_val.into(),
+                ],
+                "current_step_print_call",
+            );
+            builder.build_unconditional_branch(after_switch);
 
-        builder.build_store(current_symbol_index_ptr, updated_current_symbol_index);
+            // Insert swicht statement in the dispatch block
+            builder.position_at_end(step_dispatch);
+            builder.build_switch(
+                current_switch_case_number,
+                switch_default,
+                &case_switch_mapping,
+            );
 
-        // We will similarly update the state index later
-        builder.build_unconditional_branch(steps_loop);
+            builder.position_at_end(after_switch);
+            let updated_current_step_val = builder
+                .build_int_add(
+                    current_step_val,
+                    i32_type.const_int(1, false),
+                    "current_step_increment",
+                )
+                .unwrap();
+            builder.build_store(current_step_ptr, updated_current_step_val);
+            builder.build_unconditional_branch(steps_loop);
+        }
 
         // Loop end
+        // <tape>
+        // =========
+        //
+        // <tape without the blank symbol>
         builder.position_at_end(steps_loop_end);
-        let print_loop_end_format = builder
-            .build_global_string_ptr("Reached end of steps loop.", "step_loop_end_format")
-            .unwrap();
-        builder.build_call(
-            printf_fn,
-            &[print_loop_end_format.as_pointer_value().into()],
-            "step_loop_end_print",
-        );
+        if options.output != LlvmOutput::Silent {
+            let print_clean_tape_fn = build_print_tape_fn(
+                &context,
+                &module,
+                &builder,
+                printf_fn,
+                symbol_names,
+                total_symbols as u32,
+                Some(blank_symbol_index as u32),
+            );
+            builder.position_at_end(steps_loop_end);
+            let final_tape_separator = builder
+                .build_global_string_ptr("\n=========\n\n", "final_tape_separator")
+                .unwrap();
+            let final_newline = builder
+                .build_global_string_ptr("\n", "final_newline")
+                .unwrap();
+            builder.build_call(
+                print_tape_fn,
+                &[tape_ptr.into(), arr_size.into()],
+                "print_final_tape_call",
+            );
+            builder.build_call(
+                printf_fn,
+                &[final_tape_separator.as_pointer_value().into()],
+                "print_final_tape_separator_call",
+            );
+            builder.build_call(
+                print_clean_tape_fn,
+                &[tape_ptr.into(), arr_size.into()],
+                "print_clean_tape_call",
+            );
+            builder.build_call(
+                printf_fn,
+                &[final_newline.as_pointer_value().into()],
+                "print_final_newline_call",
+            );
+        }
         builder.build_unconditional_branch(main_return);
 
         builder.position_at_end(main_return);
         builder.build_return(Some(&i32_type.const_int(0, false)));
 
+        if let Some((debug_builder, _)) = &debug_info {
+            debug_builder.finalize();
+        }
+
+        // Report malformed IR here rather than when clang compiles it
+        module.verify().map_err(|e| e.to_string())?;
+        if options.opt_level != LlvmOptLevel::O0 {
+            let target_machine = target_machine(&triple, options.opt_level)?;
+            module
+                .run_passes(
+                    options.opt_level.passes(),
+                    &target_machine,
+                    PassBuilderOptions::create(),
+                )
+                .map_err(|e| e.to_string())?;
+        }
+
         // Generate LLVM IR as a string
-        module.print_to_string().to_string()
+        Ok(module.print_to_string().to_string())
     }
 }
@@ -0,0 +1,540 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::interpreter::{Action, Configuration, Halt, Machine, StepResult};
+use crate::parser::ParseTree;
+
+// `count` copies of the same block of cells
+#[derive(Debug, PartialEq, Clone)]
+struct Run {
+    block: Vec<usize>,
+    count: usize,
+}
+
+// Why a macro step stopped
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum MacroEnd {
+    // The head left the block
+    Exited,
+    // No transition for the state and symbol under the head
+    Halted,
+    // The next step prints outside of the block
+    Boundary,
+    // The machine never leaves the block
+    Loop,
+}
+
+// The steps applied from a state and a head position until the head leaves
+// the block. Head positions are relative to the start of the block.
+#[derive(Debug, PartialEq, Clone)]
+struct MacroStep {
+    state: usize,
+    block: Vec<usize>,
+    head: isize,
+    steps: usize,
+    // Leftmost and rightmost cells visited
+    min_head: isize,
+    max_head: isize,
+    end: MacroEnd,
+}
+
+// Interpreter for long runs, following the semantics of `Machine`. The tape
+// is cut into blocks of `block_size` cells and stored as runs of identical
+// blocks. The steps applied within a block are computed once per state,
+// block and head position, and a sweep over a run of identical blocks which
+// leaves each of them in the same state is applied in one jump.
+#[derive(Debug, Clone)]
+pub struct MacroMachine<'a> {
+    machine: Machine<'a>,
+    block_size: usize,
+    // Runs left of the head block, the nearest last
+    left: Vec<Run>,
+    // Number of blocks in `left`
+    left_blocks: usize,
+    // Block under the head
+    block: Vec<usize>,
+    // Runs right of the head block, the nearest last. Blank blocks follow.
+    right: Vec<Run>,
+    // Head position in the block
+    offset: usize,
+    // Same as the length of `Machine::tape`
+    tape_len: usize,
+    pub state: usize,
+    pub steps: usize,
+    // Block -> state * block_size + head position -> macro step
+    cache: HashMap<Vec<usize>, Vec<Option<Rc<MacroStep>>>>,
+}
+
+impl<'a> MacroMachine<'a> {
+    pub fn new(tree: &'a ParseTree, block_size: usize) -> Self {
+        MacroMachine::with_tape(tree, Vec::new(), block_size)
+    }
+
+    pub fn with_tape(tree: &'a ParseTree, tape: Vec<usize>, block_size: usize) -> Self {
+        let machine = Machine::with_tape(tree, tape);
+        let configuration = machine.configuration();
+        MacroMachine::from_machine(machine, configuration, block_size)
+    }
+
+    pub fn from_configuration(
+        tree: &'a ParseTree,
+        configuration: Configuration,
+        block_size: usize,
+    ) -> Self {
+        let machine = Machine::from_configuration(tree, configuration);
+        let configuration = machine.configuration();
+        MacroMachine::from_machine(machine, configuration, block_size)
+    }
+
+    fn from_machine(machine: Machine<'a>, configuration: Configuration, block_size: usize) -> Self {
+        assert!(block_size > 0, "Blocks must hold at least one cell");
+        let blank = machine.blank();
+        let mut blocks = configuration
+            .tape
+            .chunks(block_size)
+            .map(|cells| {
+                let mut block = cells.to_vec();
+                block.resize(block_size, blank);
+                block
+            })
+            .collect::<Vec<Vec<usize>>>();
+        let head_block = configuration.head / block_size;
+        let right_blocks = blocks.split_off(head_block + 1);
+        let block = blocks.pop().unwrap();
+
+        let mut macro_machine = MacroMachine {
+            machine,
+            block_size,
+            left: Vec::new(),
+            left_blocks: 0,
+            block,
+            right: Vec::new(),
+            offset: configuration.head % block_size,
+            tape_len: configuration.tape.len(),
+            state: configuration.state,
+            steps: configuration.steps,
+            cache: HashMap::new(),
+        };
+        for block in blocks {
+            macro_machine.push_left(block, 1);
+            macro_machine.left_blocks += 1;
+        }
+        for block in right_blocks.into_iter().rev() {
+            macro_machine.push_right(block, 1);
+        }
+        macro_machine
+    }
+
+    pub fn tree(&self) -> &'a ParseTree {
+        self.machine.tree
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn head(&self) -> usize {
+        self.left_blocks * self.block_size + self.offset
+    }
+
+    // Symbol under the head
+    pub fn symbol(&self) -> usize {
+        self.block[self.offset]
+    }
+
+    pub fn state_name(&self) -> &str {
+        &self.tree().states[self.state]
+    }
+
+    pub fn symbol_name(&self) -> &str {
+        &self.tree().symbols[self.symbol()]
+    }
+
+    // The whole tape, as `Machine::configuration` would give it
+    pub fn configuration(&self) -> Configuration {
+        let mut tape = Vec::with_capacity(self.tape_len);
+        let head_run = Run {
+            block: self.block.clone(),
+            count: 1,
+        };
+        let runs = self
+            .left
+            .iter()
+            .chain(std::iter::once(&head_run))
+            .chain(self.right.iter().rev());
+        for run in runs {
+            for _ in 0..run.count {
+                if tape.len() >= self.tape_len {
+                    break;
+                }
+                tape.extend_from_slice(&run.block);
+            }
+        }
+        tape.resize(self.tape_len, self.machine.blank());
+        Configuration {
+            state: self.state,
+            head: self.head(),
+            tape,
+            steps: self.steps,
+        }
+    }
+
+    // The tape with the runs of identical blocks written `(block)^count`,
+    // e.g. `ee0X (1X)^250 0X` with blocks of two cells
+    pub fn tape_rle_string(&self) -> String {
+        let symbols = &self.tree().symbols;
+        let mut runs: Vec<Run> = Vec::new();
+        let blanks = Run {
+            block: vec![self.machine.blank(); self.block_size],
+            count: usize::MAX,
+        };
+        let head_run = Run {
+            block: self.block.clone(),
+            count: 1,
+        };
+        for run in self
+            .left
+            .iter()
+            .chain(std::iter::once(&head_run))
+            .chain(self.right.iter().rev())
+            .chain(std::iter::once(&blanks))
+        {
+            match runs.last_mut() {
+                Some(last) if last.block == run.block => {
+                    last.count = last.count.saturating_add(run.count)
+                }
+                _ => runs.push(run.clone()),
+            }
+        }
+
+        // Cells outside of runs are written next to each other
+        let mut parts: Vec<String> = Vec::new();
+        let push_cells = |parts: &mut Vec<String>, cells: &[usize]| {
+            let cells = cells.iter().map(|&s| symbols[s].as_str());
+            match parts.last_mut() {
+                Some(last) if !last.starts_with('(') => last.extend(cells),
+                _ => parts.push(cells.collect()),
+            }
+        };
+        let mut cells = 0;
+        for run in runs {
+            if cells >= self.tape_len {
+                break;
+            }
+            // The last blocks may end after the tape
+            let count = run.count.min((self.tape_len - cells) / self.block_size);
+            match count {
+                0 => {}
+                1 => push_cells(&mut parts, &run.block),
+                _ => parts.push(format!(
+                    "({})^{}",
+                    run.block
+                        .iter()
+                        .map(|&s| symbols[s].as_str())
+                        .collect::<String>(),
+                    count
+                )),
+            }
+            cells += count * self.block_size;
+            if count < run.count {
+                let rest = self.tape_len - cells;
+                push_cells(&mut parts, &run.block[..rest]);
+                cells += rest;
+            }
+        }
+        parts.join(" ")
+    }
+
+    // Apply one transition, as `Machine::step` does
+    pub fn step(&mut self) -> StepResult {
+        let Some(index) = self.machine.transition_at(self.state, self.symbol()) else {
+            return StepResult::Halted(Halt::NoTransition);
+        };
+        let actions = self.machine.actions(index).to_vec();
+
+        // Check the head never leaves the tape before changing anything
+        let mut head = self.head();
+        for action in &actions {
+            match action {
+                Action::L if head == 0 => return StepResult::Halted(Halt::LeftEdge),
+                Action::L => head -= 1,
+                Action::R => head += 1,
+                Action::P(_) => {}
+            }
+        }
+
+        for action in &actions {
+            match action {
+                Action::R => {
+                    self.offset += 1;
+                    if self.offset == self.block_size {
+                        self.shift_right();
+                    }
+                    self.tape_len = self.tape_len.max(self.head() + 1);
+                }
+                Action::L => {
+                    if self.offset == 0 {
+                        self.shift_left();
+                    } else {
+                        self.offset -= 1;
+                    }
+                }
+                Action::P(symbol) => self.block[self.offset] = *symbol,
+            }
+        }
+        self.state = self.machine.final_state(index);
+        self.steps += 1;
+        StepResult::Fired(index)
+    }
+
+    // Run until the machine halts or `max_steps` steps have been applied
+    pub fn run(&mut self, max_steps: usize) -> Option<Halt> {
+        let target = self.steps.saturating_add(max_steps);
+        while self.steps < target {
+            let remaining = target - self.steps;
+            let macro_step = self.macro_step();
+            match macro_step.end {
+                MacroEnd::Halted if macro_step.steps == 0 => return Some(Halt::NoTransition),
+                MacroEnd::Exited | MacroEnd::Halted | MacroEnd::Boundary
+                    if macro_step.steps > 0
+                        && macro_step.steps <= remaining
+                        && self.block_start() + macro_step.min_head >= 0 =>
+                {
+                    self.apply(&macro_step, remaining);
+                }
+                // Near the left edge, at the end of the run, or when a step
+                // reaches out of the block
+                _ => {
+                    if let StepResult::Halted(halt) = self.step() {
+                        return Some(halt);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn block_start(&self) -> isize {
+        (self.left_blocks * self.block_size) as isize
+    }
+
+    fn macro_step(&mut self) -> Rc<MacroStep> {
+        let index = self.state * self.block_size + self.offset;
+        if let Some(macro_step) = self
+            .cache
+            .get(&self.block)
+            .and_then(|steps| steps[index].clone())
+        {
+            return macro_step;
+        }
+        let macro_step =
+            Rc::new(self.compute_macro_step(self.state, self.offset, self.block.clone()));
+        let states = self.tree().states.len();
+        self.cache
+            .entry(self.block.clone())
+            .or_insert_with(|| vec![None; states * self.block_size])[index] =
+            Some(macro_step.clone());
+        macro_step
+    }
+
+    fn compute_macro_step(&self, state: usize, head: usize, block: Vec<usize>) -> MacroStep {
+        let size = self.block_size as isize;
+        let mut macro_step = MacroStep {
+            state,
+            block,
+            head: head as isize,
+            steps: 0,
+            min_head: head as isize,
+            max_head: head as isize,
+            end: MacroEnd::Exited,
+        };
+        let mut seen = HashSet::new();
+        loop {
+            if macro_step.head < 0 || macro_step.head >= size {
+                macro_step.end = MacroEnd::Exited;
+                return macro_step;
+            }
+            if !seen.insert((macro_step.state, macro_step.head, macro_step.block.clone())) {
+                macro_step.end = MacroEnd::Loop;
+                return macro_step;
+            }
+            let symbol = macro_step.block[macro_step.head as usize];
+            let Some(index) = self.machine.transition_at(macro_step.state, symbol) else {
+                macro_step.end = MacroEnd::Halted;
+                return macro_step;
+            };
+            let actions = self.machine.actions(index);
+
+            let mut head = macro_step.head;
+            for action in actions {
+                match action {
+                    Action::R => head += 1,
+                    Action::L => head -= 1,
+                    Action::P(_) if head < 0 || head >= size => {
+                        macro_step.end = MacroEnd::Boundary;
+                        return macro_step;
+                    }
+                    Action::P(_) => {}
+                }
+            }
+
+            for action in actions {
+                match action {
+                    Action::R => {
+                        macro_step.head += 1;
+                        macro_step.max_head = macro_step.max_head.max(macro_step.head);
+                    }
+                    Action::L => {
+                        macro_step.head -= 1;
+                        macro_step.min_head = macro_step.min_head.min(macro_step.head);
+                    }
+                    Action::P(symbol) => macro_step.block[macro_step.head as usize] = *symbol,
+                }
+            }
+            macro_step.state = self.machine.final_state(index);
+            macro_step.steps += 1;
+        }
+    }
+
+    // Apply `macro_step` to the head block, and to the identical blocks
+    // following it in the direction the head left if the machine enters each
+    // of them in the same state at the same position
+    fn apply(&mut self, macro_step: &MacroStep, remaining: usize) {
+        let size = self.block_size as isize;
+        let offset = self.offset as isize;
+        let repeats = remaining / macro_step.steps;
+        let sweep = macro_step.end == MacroEnd::Exited && macro_step.state == self.state;
+
+        if sweep && macro_step.head == offset + size {
+            let following = match self.right.last() {
+                Some(run) if run.block == self.block => run.count,
+                // The blank blocks never end
+                None if self.block.iter().all(|&s| s == self.machine.blank()) => usize::MAX,
+                _ => 0,
+            };
+            let blocks = following.saturating_add(1).min(repeats);
+            let last_start = self.block_start() + (blocks as isize - 1) * size;
+            self.tape_len = self
+                .tape_len
+                .max((last_start + macro_step.max_head + 1) as usize);
+            self.drop_right(blocks - 1);
+            self.push_left(macro_step.block.clone(), blocks);
+            self.left_blocks += blocks;
+            self.block = self.pop_right();
+            self.steps += blocks * macro_step.steps;
+            return;
+        }
+
+        if sweep && macro_step.head == offset - size {
+            let following = match self.left.last() {
+                Some(run) if run.block == self.block => run.count,
+                _ => 0,
+            };
+            // The leftmost block must not take the head left of the first cell
+            let reachable = ((self.block_start() + macro_step.min_head) / size) as usize;
+            let blocks = following.min(reachable).saturating_add(1).min(repeats);
+            self.tape_len = self
+                .tape_len
+                .max((self.block_start() + macro_step.max_head + 1) as usize);
+            self.drop_left(blocks - 1);
+            self.left_blocks -= blocks;
+            self.push_right(macro_step.block.clone(), blocks);
+            self.block = self.pop_left();
+            self.steps += blocks * macro_step.steps;
+            return;
+        }
+
+        self.tape_len = self
+            .tape_len
+            .max((self.block_start() + macro_step.max_head + 1) as usize);
+        self.block.clone_from(&macro_step.block);
+        self.state = macro_step.state;
+        self.steps += macro_step.steps;
+        let mut head = macro_step.head;
+        while head >= size {
+            self.shift_right();
+            head -= size;
+        }
+        while head < 0 {
+            self.shift_left();
+            head += size;
+        }
+        self.offset = head as usize;
+    }
+
+    fn shift_right(&mut self) {
+        let block = std::mem::take(&mut self.block);
+        self.push_left(block, 1);
+        self.left_blocks += 1;
+        self.block = self.pop_right();
+        self.offset = 0;
+    }
+
+    fn shift_left(&mut self) {
+        let block = std::mem::take(&mut self.block);
+        self.push_right(block, 1);
+        self.left_blocks -= 1;
+        self.block = self.pop_left();
+        self.offset = self.block_size - 1;
+    }
+
+    fn push_left(&mut self, block: Vec<usize>, count: usize) {
+        match self.left.last_mut() {
+            _ if count == 0 => {}
+            Some(run) if run.block == block => run.count += count,
+            _ => self.left.push(Run { block, count }),
+        }
+    }
+
+    fn push_right(&mut self, block: Vec<usize>, count: usize) {
+        let blank = self.machine.blank();
+        match self.right.last_mut() {
+            _ if count == 0 => {}
+            // Blank blocks at the end are implicit
+            None if block.iter().all(|&s| s == blank) => {}
+            Some(run) if run.block == block => run.count += count,
+            _ => self.right.push(Run { block, count }),
+        }
+    }
+
+    fn pop_left(&mut self) -> Vec<usize> {
+        let run = self.left.last_mut().unwrap();
+        run.count -= 1;
+        if run.count == 0 {
+            self.left.pop().unwrap().block
+        } else {
+            run.block.clone()
+        }
+    }
+
+    fn pop_right(&mut self) -> Vec<usize> {
+        let Some(run) = self.right.last_mut() else {
+            return vec![self.machine.blank(); self.block_size];
+        };
+        run.count -= 1;
+        if run.count == 0 {
+            self.right.pop().unwrap().block
+        } else {
+            run.block.clone()
+        }
+    }
+
+    fn drop_left(&mut self, count: usize) {
+        if count > 0 {
+            let run = self.left.last_mut().unwrap();
+            run.count -= count;
+            if run.count == 0 {
+                self.left.pop();
+            }
+        }
+    }
+
+    fn drop_right(&mut self, count: usize) {
+        if let Some(run) = self.right.last_mut().filter(|_| count > 0) {
+            run.count -= count;
+            if run.count == 0 {
+                self.right.pop();
+            }
+        }
+    }
+}
@@ -1,13 +1,32 @@
 use env_logger::Env;
 use state_machine_compiler_rust::{
-    lexer::Lexer, llvmconverter::ToLlvmIr, parser::{Parser, ToDot}
+    analysis::Analysis,
+    animation::dot_frames,
+    bench::{self, Backend, BenchOptions},
+    cconverter::{CCodegenOptions, ToC},
+    coverage::Coverage,
+    cycles::{CycleDetector, Verdict},
+    debugger::Debugger,
+    diagnostics::{Emitter, MessageFormat},
+    graphs::{GraphFormat, ToGraphMl, ToMermaid, ToPlantUml},
+    interpreter::{parse_tape, Configuration, Halt, Machine},
+    jsconverter::ToJavaScript,
+    lexer::Lexer,
+    llvmconverter::{LlvmCodegenOptions, LlvmDispatch, LlvmOptLevel, LlvmOutput, ToLlvmIr},
+    macromachine::MacroMachine,
+    parser::{DotOptions, ParseTree, Parser, RustCodegenOptions, RustDispatch, ToDot},
+    pythonconverter::ToPython,
+    snapshot,
+    spacetime::{self, DiagramFormat},
+    trace::{TraceFormat, TraceLevel, TraceOptions},
+    wasmconverter::ToWasm,
 };
 use std::{
     fs::File,
-    io::{Read, Write},
+    io::{self, Read, Write},
 };
 
-use clap::Parser as ClapParser;
+use clap::{Parser as ClapParser, Subcommand};
 use log::{debug, error, info};
 // use env_logger::
 
@@ -15,6 +34,232 @@ use log::{debug, error, info};
 struct Args {
     #[arg(short, long)]
     input_file_path: std::path::PathBuf,
+
+    /// How to render errors and warnings: human or json
+    #[arg(long, default_value = "human")]
+    message_format: MessageFormat,
+
+    /// Default trace level of the generated programs: none, transitions or full
+    #[arg(long, default_value = "transitions")]
+    trace: TraceLevel,
+
+    /// Default trace format of the generated programs: human or compact
+    #[arg(long, default_value = "human")]
+    trace_format: TraceFormat,
+
+    /// Generate a library module exposing a TapeMachine type instead of a program
+    #[arg(long)]
+    rust_library: bool,
+
+    /// How the generated program finds the transition to apply: match or table
+    #[arg(long, default_value = "match")]
+    rust_dispatch: RustDispatch,
+
+    /// Optimization level of the LLVM IR: 0, 1, 2 or 3
+    #[arg(short = 'O', long, default_value = "0")]
+    opt_level: LlvmOptLevel,
+
+    /// What the LLVM program prints: silent, final-tape or trace
+    #[arg(long, default_value = "final-tape")]
+    llvm_output: LlvmOutput,
+
+    /// How the LLVM program goes from one transition to the next: switch or threaded
+    #[arg(long, default_value = "switch")]
+    llvm_dispatch: LlvmDispatch,
+
+    /// Emit DWARF debug info in the LLVM IR pointing at the transitions of the input file
+    #[arg(short = 'g', long)]
+    debug_info: bool,
+
+    /// Also generate a C99 program in state_machine.c
+    #[arg(long)]
+    c: bool,
+
+    /// Generate a header-only C library in tape_machine.h instead of the C program
+    #[arg(long, requires = "c")]
+    c_header_only: bool,
+
+    /// Also generate a WebAssembly module in state_machine.wasm, with its text in state_machine.wat
+    #[arg(long)]
+    wasm: bool,
+
+    /// Also generate a Python simulator class in tape_machine.py
+    #[arg(long)]
+    python: bool,
+
+    /// Also generate a JavaScript simulator class in tape_machine.mjs
+    #[arg(long)]
+    javascript: bool,
+
+    /// Format of the state diagram: dot, mermaid, plantuml or graphml
+    #[arg(long, default_value = "dot")]
+    graph_format: GraphFormat,
+
+    /// Merge the transitions between the same two states into one edge of the dot file
+    #[arg(long)]
+    dot_merge_edges: bool,
+
+    /// Draw the states without any transition out of them differently in the dot file
+    #[arg(long)]
+    dot_halting: bool,
+
+    /// Group states whose names only differ by trailing digits into clusters of the dot file
+    #[arg(long)]
+    dot_clusters: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Step through the machine interactively instead of generating code
+    Debug {
+        /// Initial tape, e.g. 0110 or 0,1,1,0
+        #[arg(short, long, default_value = "")]
+        tape: String,
+
+        /// Number of tape cells shown on each side of the head
+        #[arg(short, long, default_value_t = 10)]
+        window: usize,
+
+        /// Steps after which `continue` stops if no breakpoint is hit
+        #[arg(long, default_value_t = 1_000_000)]
+        max_steps: usize,
+    },
+    /// Report unreachable states, halting states, missing transitions and
+    /// strongly connected components instead of generating code
+    Lint,
+    /// Run the machine on sample tapes and report which transitions were applied
+    Coverage {
+        /// Initial tape of one run, can be given several times
+        #[arg(short, long)]
+        tape: Vec<String>,
+
+        /// Steps after which a run is stopped if the machine has not halted
+        #[arg(long, default_value_t = 10_000)]
+        max_steps: usize,
+
+        /// Where to write the dot file with the coverage on the edges
+        #[arg(long, default_value = "state_machine_coverage.dot")]
+        dot: std::path::PathBuf,
+    },
+    /// Render a run as a space-time diagram, one row per step
+    Spacetime {
+        /// Initial tape, e.g. 0110 or 0,1,1,0
+        #[arg(short, long, default_value = "")]
+        tape: String,
+
+        /// Number of steps to render if the machine does not halt before
+        #[arg(long, default_value_t = 100)]
+        max_steps: usize,
+
+        /// Diagram format: text or svg
+        #[arg(short, long, default_value = "text")]
+        format: DiagramFormat,
+
+        /// Where to write the diagram, printed if not given
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Write one dot file per step of a run with the active state and edge highlighted
+    Frames {
+        /// Initial tape, e.g. 0110 or 0,1,1,0
+        #[arg(short, long, default_value = "")]
+        tape: String,
+
+        /// Number of steps to render if the machine does not halt before
+        #[arg(long, default_value_t = 100)]
+        max_steps: usize,
+
+        /// Directory the frames are written to, as frame_0000.dot, frame_0001.dot, ...
+        #[arg(short, long, default_value = "frames")]
+        output_dir: std::path::PathBuf,
+    },
+    /// Run the machine with the tape stored as runs of identical blocks, skipping over repeated sweeps
+    Run {
+        /// Initial tape, e.g. 0110 or 0,1,1,0
+        #[arg(short, long, default_value = "")]
+        tape: String,
+
+        /// Steps after which the run is stopped if the machine has not halted
+        #[arg(long, default_value_t = 1_000_000)]
+        max_steps: usize,
+
+        /// Number of cells in a block of the tape
+        #[arg(short, long, default_value_t = 1, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        block_size: usize,
+
+        /// Run step by step and stop when a configuration comes back, or the machine repeats itself further right
+        #[arg(long, conflicts_with = "block_size")]
+        detect_cycles: bool,
+
+        /// Start from the state, head, steps and tape saved in a snapshot file instead of the tape
+        #[arg(long, conflicts_with = "tape")]
+        resume: Option<std::path::PathBuf>,
+
+        /// Save the state, head, steps and tape to this file when the run stops
+        #[arg(long)]
+        snapshot: Option<std::path::PathBuf>,
+
+        /// Also save the snapshot every this many steps
+        #[arg(long, requires = "snapshot", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        snapshot_every: Option<usize>,
+    },
+    /// Build the machine with every backend and compare their compile time and steps per second
+    Bench {
+        /// Initial tape, e.g. 0110 or 0,1,1,0
+        #[arg(short, long, default_value = "")]
+        tape: String,
+
+        /// Steps after which a run is stopped if the machine has not halted
+        #[arg(long, default_value_t = 1_000_000)]
+        steps: usize,
+
+        /// Backend to compare: interpreter, rust, c, llvm or llvm-threaded, can be given several times, all by default
+        #[arg(short, long)]
+        backend: Vec<Backend>,
+    },
+}
+
+fn dot_options(args: &Args) -> DotOptions {
+    DotOptions {
+        coverage: None,
+        merge_edges: args.dot_merge_edges,
+        style_halting: args.dot_halting,
+        clusters: args.dot_clusters,
+        highlight: None,
+    }
+}
+
+// The symbols of a tape argument, exits on an invalid tape
+fn tape_or_exit(tree: &ParseTree, tape: &str) -> Vec<usize> {
+    match parse_tape(tree, tape) {
+        Ok(tape) => tape,
+        Err(e) => {
+            error!("Invalid tape: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Written next to `path` then renamed over it, so a run killed while saving
+// keeps the previous snapshot
+fn write_snapshot(tree: &ParseTree, configuration: &Configuration, path: &std::path::Path) {
+    let text = snapshot::to_text(tree, configuration);
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let written = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(text.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if let Err(e) = written {
+        error!("Failed to write the snapshot {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+    info!("Saved step {} to {}", configuration.steps, path.display());
 }
 
 fn main() {
@@ -52,36 +297,364 @@ fn main() {
     }
 
     info!("Lexing the input file");
-    let lexer = Lexer::new(&source);
+    let emitter = Emitter::new(&args.input_file_path.to_string_lossy(), args.message_format);
+    let lexer = Lexer::new(&source).with_emitter(emitter);
 
     info!("Parsing the input file");
     let mut parser = Parser::new(lexer);
-    parser.program();
+    // The diagnostic has already been emitted
+    if parser.try_program().is_err() {
+        std::process::exit(1);
+    }
 
     debug!("Parsed tree: {:?}", parser.tree);
 
-    info!("Generating the dot file");
-    let dot = parser.tree.to_dot();
-    let dot_file_path = "state_machine.dot";
-    if let Err(e) = File::create(dot_file_path).and_then(|mut file| file.write_all(dot.as_bytes()))
+    // Subcommands stop after their own output
+    if let Some(command) = &args.command {
+        match command {
+            Command::Debug {
+                tape,
+                window,
+                max_steps,
+            } => {
+                let tape = tape_or_exit(&parser.tree, tape);
+                let machine = Machine::with_tape(&parser.tree, tape);
+                let mut debugger = Debugger::new(machine, *window, *max_steps);
+                if let Err(e) = debugger.run(io::stdin().lock(), io::stdout()) {
+                    error!("Debugger failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Command::Lint => {
+                print!("{}", Analysis::new(&parser.tree));
+            }
+            Command::Coverage {
+                tape,
+                max_steps,
+                dot,
+            } => {
+                // Without any sample tape run once on the blank tape
+                let tapes = if tape.is_empty() {
+                    vec![String::new()]
+                } else {
+                    tape.clone()
+                };
+                let mut coverage = Coverage::new(&parser.tree);
+                for input in &tapes {
+                    let tape = tape_or_exit(&parser.tree, input);
+                    let mut machine = Machine::with_tape(&parser.tree, tape);
+                    if coverage.run(&mut machine, *max_steps).is_none() {
+                        info!("Run on tape {:?} stopped after {} steps", input, max_steps);
+                    }
+                }
+                print!("{}", coverage.report(&parser.tree));
+
+                let options = DotOptions {
+                    coverage: Some(coverage),
+                    ..dot_options(&args)
+                };
+                let dot_file = parser.tree.to_dot_with(&options);
+                if let Err(e) =
+                    File::create(dot).and_then(|mut file| file.write_all(dot_file.as_bytes()))
+                {
+                    error!("Failed to write the dot file: {}", e);
+                } else {
+                    info!("Written the coverage dot file to {}", dot.display());
+                }
+            }
+            Command::Spacetime {
+                tape,
+                max_steps,
+                format,
+                output,
+            } => {
+                let tape = tape_or_exit(&parser.tree, tape);
+                let mut machine = Machine::with_tape(&parser.tree, tape);
+                let rows = spacetime::record_run(&mut machine, *max_steps);
+                let diagram = match format {
+                    DiagramFormat::Text => spacetime::to_text(&parser.tree, &rows),
+                    DiagramFormat::Svg => spacetime::to_svg(&parser.tree, &rows),
+                };
+                match output {
+                    Some(path) => {
+                        if let Err(e) = File::create(path)
+                            .and_then(|mut file| file.write_all(diagram.as_bytes()))
+                        {
+                            error!("Failed to write the diagram: {}", e);
+                            std::process::exit(1);
+                        }
+                        info!("Written the diagram to {}", path.display());
+                    }
+                    None => print!("{}", diagram),
+                }
+            }
+            Command::Frames {
+                tape,
+                max_steps,
+                output_dir,
+            } => {
+                let tape = tape_or_exit(&parser.tree, tape);
+                let mut machine = Machine::with_tape(&parser.tree, tape);
+                let frames = dot_frames(&mut machine, *max_steps, &dot_options(&args));
+                if let Err(e) = std::fs::create_dir_all(output_dir) {
+                    error!("Failed to create {}: {}", output_dir.display(), e);
+                    std::process::exit(1);
+                }
+                for (i, frame) in frames.iter().enumerate() {
+                    let path = output_dir.join(format!("frame_{:04}.dot", i));
+                    if let Err(e) =
+                        File::create(&path).and_then(|mut file| file.write_all(frame.as_bytes()))
+                    {
+                        error!("Failed to write {}: {}", path.display(), e);
+                        std::process::exit(1);
+                    }
+                }
+                info!(
+                    "Written {} frames to {}",
+                    frames.len(),
+                    output_dir.display()
+                );
+            }
+            Command::Run {
+                tape,
+                max_steps,
+                block_size,
+                detect_cycles,
+                resume,
+                snapshot,
+                snapshot_every,
+            } => {
+                let configuration = match resume {
+                    Some(path) => {
+                        let text = std::fs::read_to_string(path).map_err(|e| e.to_string());
+                        match text.and_then(|text| snapshot::from_text(&parser.tree, &text)) {
+                            Ok(configuration) => configuration,
+                            Err(e) => {
+                                error!("Invalid snapshot {}: {}", path.display(), e);
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                    None => Machine::with_tape(&parser.tree, tape_or_exit(&parser.tree, tape))
+                        .configuration(),
+                };
+                // Without a snapshot file the run is not cut into parts
+                let every = snapshot_every
+                    .filter(|_| snapshot.is_some())
+                    .unwrap_or(*max_steps);
+                let target = configuration.steps.saturating_add(*max_steps);
+
+                if *detect_cycles {
+                    let mut machine = Machine::from_configuration(&parser.tree, configuration);
+                    let mut detector = CycleDetector::new(&machine);
+                    let verdict = loop {
+                        let steps = every.min(target - machine.steps);
+                        let verdict = detector.run(&mut machine, steps);
+                        if let Some(path) = snapshot {
+                            write_snapshot(&parser.tree, &machine.configuration(), path);
+                        }
+                        if verdict != Verdict::Unknown || machine.steps >= target {
+                            break verdict;
+                        }
+                    };
+                    match verdict {
+                        Verdict::Halted(Halt::NoTransition) => println!(
+                            "Halted after {} steps: no transition for state {} and symbol {}",
+                            machine.steps,
+                            machine.state_name(),
+                            machine.symbol_name()
+                        ),
+                        Verdict::Halted(Halt::LeftEdge) => println!(
+                            "Halted after {} steps: the head would move left of the first cell",
+                            machine.steps
+                        ),
+                        Verdict::NonHalting(non_halting) => println!(
+                            "Provably non-halting after {} steps: {}",
+                            machine.steps, non_halting
+                        ),
+                        Verdict::Unknown => println!("Stopped after {} steps", machine.steps),
+                    }
+                    println!("State: {} Head: {}", machine.state_name(), machine.head);
+                    println!("Tape: {}", machine.tape_string());
+                    return;
+                }
+                let mut machine =
+                    MacroMachine::from_configuration(&parser.tree, configuration, *block_size);
+                let halt = loop {
+                    let halt = machine.run(every.min(target - machine.steps));
+                    if let Some(path) = snapshot {
+                        write_snapshot(&parser.tree, &machine.configuration(), path);
+                    }
+                    if halt.is_some() || machine.steps >= target {
+                        break halt;
+                    }
+                };
+                match halt {
+                    Some(Halt::NoTransition) => println!(
+                        "Halted after {} steps: no transition for state {} and symbol {}",
+                        machine.steps,
+                        machine.state_name(),
+                        machine.symbol_name()
+                    ),
+                    Some(Halt::LeftEdge) => println!(
+                        "Halted after {} steps: the head would move left of the first cell",
+                        machine.steps
+                    ),
+                    None => println!("Stopped after {} steps", machine.steps),
+                }
+                println!("State: {} Head: {}", machine.state_name(), machine.head());
+                println!("Tape: {}", machine.tape_rle_string());
+            }
+            Command::Bench {
+                tape,
+                steps,
+                backend,
+            } => {
+                let tape = tape_or_exit(&parser.tree, tape);
+                let backends = if backend.is_empty() {
+                    Backend::ALL.to_vec()
+                } else {
+                    backend.clone()
+                };
+                // The LLVM program is built like the generated one
+                let options = BenchOptions {
+                    steps: *steps,
+                    opt_level: args.opt_level,
+                    ..Default::default()
+                };
+                info!(
+                    "Building and running the machine in {}",
+                    options.work_dir.display()
+                );
+                let results = bench::bench(&parser.tree, &tape, &backends, &options);
+                print!("{}", bench::report(&results));
+            }
+        }
+        return;
+    }
+
+    info!("Generating the {} file", args.graph_format.extension());
+    let graph = match args.graph_format {
+        GraphFormat::Dot => parser.tree.to_dot_with(&dot_options(&args)),
+        GraphFormat::Mermaid => parser.tree.to_mermaid(),
+        GraphFormat::PlantUml => parser.tree.to_plantuml(),
+        GraphFormat::GraphMl => parser.tree.to_graphml(),
+    };
+    let graph_file_path = format!("state_machine.{}", args.graph_format.extension());
+    if let Err(e) =
+        File::create(&graph_file_path).and_then(|mut file| file.write_all(graph.as_bytes()))
     {
-        error!("Failed to write the dot file: {}", e);
+        error!(
+            "Failed to write the {} file: {}",
+            args.graph_format.extension(),
+            e
+        );
     } else {
-        info!("Written the dot file to {}", dot_file_path);
+        info!(
+            "Written the {} file to {}",
+            args.graph_format.extension(),
+            graph_file_path
+        );
     }
 
     info!("Generating the Rust code");
-    let code = parser.tree.to_rust_code();
-    let file_path = "src/bin/state_machine.rs";
+    let trace = TraceOptions {
+        level: args.trace,
+        format: args.trace_format,
+    };
+    let code = parser.tree.to_rust_code_with(&RustCodegenOptions {
+        trace,
+        library: args.rust_library,
+        dispatch: args.rust_dispatch,
+    });
+    // The library is not a binary target, keep it out of src/bin
+    let file_path = if args.rust_library {
+        "tape_machine.rs"
+    } else {
+        "src/bin/state_machine.rs"
+    };
     if let Err(e) = File::create(file_path).and_then(|mut file| file.write_all(code.as_bytes())) {
         error!("Failed to write the Rust code: {}", e);
     } else {
         info!("Written the Rust code to {}", file_path);
     }
 
+    if args.c {
+        info!("Generating the C code");
+        let code = parser.tree.to_c_with(&CCodegenOptions {
+            trace,
+            header_only: args.c_header_only,
+        });
+        let file_path = if args.c_header_only {
+            "tape_machine.h"
+        } else {
+            "state_machine.c"
+        };
+        if let Err(e) = File::create(file_path).and_then(|mut file| file.write_all(code.as_bytes()))
+        {
+            error!("Failed to write the C code: {}", e);
+        } else {
+            info!("Written the C code to {}", file_path);
+        }
+    }
+
+    if args.wasm {
+        info!("Generating the WebAssembly module");
+        for (file_path, bytes) in [
+            ("state_machine.wasm", parser.tree.to_wasm()),
+            ("state_machine.wat", parser.tree.to_wat().into_bytes()),
+        ] {
+            if let Err(e) = File::create(file_path).and_then(|mut file| file.write_all(&bytes)) {
+                error!("Failed to write the WebAssembly module: {}", e);
+            } else {
+                info!("Written the WebAssembly module to {}", file_path);
+            }
+        }
+    }
+
+    if args.python {
+        info!("Generating the Python code");
+        let code = parser.tree.to_python();
+        if let Err(e) =
+            File::create("tape_machine.py").and_then(|mut file| file.write_all(code.as_bytes()))
+        {
+            error!("Failed to write the Python code: {}", e);
+        } else {
+            info!("Written the Python code to tape_machine.py");
+        }
+    }
+
+    if args.javascript {
+        info!("Generating the JavaScript code");
+        let code = parser.tree.to_javascript();
+        if let Err(e) =
+            File::create("tape_machine.mjs").and_then(|mut file| file.write_all(code.as_bytes()))
+        {
+            error!("Failed to write the JavaScript code: {}", e);
+        } else {
+            info!("Written the JavaScript code to tape_machine.mjs");
+        }
+    }
+
     info!("Generating llvm ir ");
     // unsafe {
-        let llvm_ir = parser.tree.to_llvm_ir();
+        let llvm_ir = match parser.tree.try_to_llvm_ir_with(&LlvmCodegenOptions {
+            trace,
+            opt_level: args.opt_level,
+            output: args.llvm_output,
+            dispatch: args.llvm_dispatch,
+            // Absolute so the debugger finds the machine file from anywhere
+            debug_info: args.debug_info.then(|| {
+                std::fs::canonicalize(&args.input_file_path)
+                    .unwrap_or_else(|_| args.input_file_path.clone())
+            }),
+        }) {
+            Ok(llvm_ir) => llvm_ir,
+            Err(e) => {
+                error!("Invalid LLVM IR: {}", e);
+                std::process::exit(1);
+            }
+        };
         let file_path = "state_machine.ll";
         if let Err(e) = File::create(file_path).and_then(|mut file| file.write_all(llvm_ir.as_bytes())) {
             error!("Failed to write the LLVM IR: {}", e);
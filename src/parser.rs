@@ -1,5 +1,11 @@
+use crate::coverage::Coverage;
+use crate::diagnostics::{closest_match, Diagnostic, Span};
+use crate::escape::dot_escape;
 use crate::lexer::{Lexer, Token, TokenType};
-use log::{debug, error, info};
+use crate::trace::{TraceFormat, TraceLevel, TraceOptions, TRACE_ENV_VAR, TRACE_FORMAT_ENV_VAR};
+use log::{debug, info};
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Condition {
@@ -37,17 +43,91 @@ pub struct Transition {
     pub condition: Condition,
     pub steps: Vec<TransitionStep>,
     pub final_state: String,
+    // Line of the declaration in the machine file, 0 when not parsed from one
+    pub line: usize,
 }
 
-impl Transition {
-    pub fn new() -> Self {
+impl Default for Transition {
+    fn default() -> Self {
         Transition {
             initial_state: String::new(),
             condition: Condition::OR(Vec::new()),
             steps: Vec::new(),
             final_state: String::new(),
+            line: 0,
+        }
+    }
+}
+
+impl Transition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn matches(&self, symbol: &str) -> bool {
+        match &self.condition {
+            Condition::OR(symbols) => symbols.iter().any(|s| s == symbol),
+            Condition::Star => true,
         }
     }
+
+    // Short description used on the edges of the graph exports: `[0,1] / R-R`
+    pub fn label(&self) -> String {
+        let condition = match &self.condition {
+            Condition::OR(symbols) => format!("[{}]", symbols.join(",")),
+            Condition::Star => "*".to_string(),
+        };
+        format!("{} / {}", condition, self.steps_string())
+    }
+
+    // Number of cells the head moves left of its position during the
+    // transition, it cannot be applied closer than that to the first cell
+    pub fn left_reach(&self) -> usize {
+        let mut offset: i64 = 0;
+        let mut left_most: i64 = 0;
+        for step in &self.steps {
+            match step {
+                TransitionStep::R => offset += 1,
+                TransitionStep::L => offset -= 1,
+                _ => {}
+            }
+            left_most = left_most.min(offset);
+        }
+        (-left_most) as usize
+    }
+
+    // Steps as written in the source file: `P(1)-R`
+    pub fn steps_string(&self) -> String {
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|step| match step {
+                TransitionStep::R => "R".to_string(),
+                TransitionStep::L => "L".to_string(),
+                TransitionStep::X => "X".to_string(),
+                TransitionStep::P(symbol) => format!("P({})", symbol),
+            })
+            .collect();
+        steps.join("-")
+    }
+}
+
+// Same syntax as in the source file: `q, 0 | 1, R-R, q`
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let condition = match &self.condition {
+            Condition::OR(symbols) => symbols.join(" | "),
+            Condition::Star => "*".to_string(),
+        };
+        write!(
+            f,
+            "{}, {}, {}, {}",
+            self.initial_state,
+            condition,
+            self.steps_string(),
+            self.final_state
+        )
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -58,13 +138,98 @@ pub struct ParseTree {
     pub transitions: Vec<Transition>,
 }
 
+// Options for the generated Rust program
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RustCodegenOptions {
+    pub trace: TraceOptions,
+    // Generate a library module exposing a `TapeMachine` type instead of a
+    // program reading the number of steps and the tape length from stdin
+    pub library: bool,
+    // Ignored for the library module
+    pub dispatch: RustDispatch,
+}
+
+// How the generated program finds the transition to apply
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum RustDispatch {
+    // A `match` on the state and the symbol
+    #[default]
+    Match,
+    // A lookup in static arrays
+    Table,
+}
+
+impl FromStr for RustDispatch {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "match" => Ok(RustDispatch::Match),
+            "table" => Ok(RustDispatch::Table),
+            _ => Err(format!("Unknown dispatch: {}", s)),
+        }
+    }
+}
+
 impl ParseTree {
+    // Index of the transition taken from `state` when reading `symbol`.
+    // Explicit symbols take precedence over `*`, otherwise the first
+    // transition in the source wins, same as the generated code.
+    pub fn transition_for(&self, state: &str, symbol: &str) -> Option<usize> {
+        let from_state = || {
+            self.transitions
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| t.initial_state == state)
+        };
+        from_state()
+            .find(|(_, t)| t.condition != Condition::Star && t.matches(symbol))
+            .or_else(|| from_state().find(|(_, t)| t.condition == Condition::Star))
+            .map(|(i, _)| i)
+    }
+
+    // The transitions in the order the generated code tests them: `*` ones
+    // last, so that they only take the symbols no other transition of their
+    // state takes, like `transition_for`
+    pub fn transitions_star_last(&self) -> Vec<&Transition> {
+        let mut transitions: Vec<&Transition> = self.transitions.iter().collect();
+        transitions.sort_by_key(|transition| transition.condition == Condition::Star);
+        transitions
+    }
+
+    // The transitions taken from `state`, each with the indices of the symbols
+    // it is taken on. Like `transitions_star_last` the star one comes last, it
+    // takes the symbols left. Transitions to an undeclared state are left out.
+    pub fn transitions_from(&self, state: &str) -> Vec<(usize, Vec<usize>)> {
+        let mut used: Vec<(usize, Vec<usize>)> = Vec::new();
+        for (symbol_index, symbol) in self.symbols.iter().enumerate() {
+            let Some(index) = self.transition_for(state, symbol) else {
+                continue;
+            };
+            if !self.states.contains(&self.transitions[index].final_state) {
+                continue;
+            }
+            match used.iter_mut().find(|(i, _)| *i == index) {
+                Some((_, symbols)) => symbols.push(symbol_index),
+                None => used.push((index, vec![symbol_index])),
+            }
+        }
+        used.sort_by_key(|(index, _)| self.transitions[*index].condition == Condition::Star);
+        used
+    }
+
     pub fn to_rust_code(&self) -> String {
+        self.to_rust_code_with(&RustCodegenOptions::default())
+    }
+
+    pub fn to_rust_code_with(&self, options: &RustCodegenOptions) -> String {
+        if options.library {
+            return self.to_rust_library();
+        }
         let mut code = String::new();
 
         // Generate the TapeMachineState enum
         code.push_str(
-            "use std::fmt;\nuse std::io;\n\n#[derive(Debug, PartialEq, Eq)]\nenum TapeMachineState {\n",
+            "use std::io;\n\n#[allow(non_camel_case_types)]\n#[derive(Debug, PartialEq, Eq)]\nenum TapeMachineState {\n",
         );
         for state in &self.states {
             code.push_str(&format!("    {},\n", state));
@@ -122,10 +287,72 @@ impl ParseTree {
 
         code.push_str("    fn l(&mut self) {\n");
         code.push_str("        self.index -= 1;\n");
+        code.push_str("    }\n\n");
+
+        code.push_str("    fn trace(&self, step: usize, level: TraceLevel, compact: bool) {\n");
+        code.push_str("        if level == TraceLevel::None {\n");
+        code.push_str("            return;\n");
+        code.push_str("        }\n");
+        code.push_str("        let symbol = &self.result[self.index];\n");
+        code.push_str("        if compact {\n");
+        code.push_str("            print!(\"{}\\t{:?}\\t{}\\t{}\", step, self.state, self.index, symbol.as_str());\n");
+        code.push_str("            if level == TraceLevel::Full {\n");
+        code.push_str("                let tape: String = self.result.iter().map(|x| x.as_str()).collect();\n");
+        code.push_str("                print!(\"\\t{}\", tape);\n");
+        code.push_str("            }\n");
+        code.push_str("            println!();\n");
+        code.push_str("        } else {\n");
+        code.push_str("            println!(\"Step: {} State: {:?} Symbol: {:?}\", step, self.state, symbol);\n");
+        code.push_str("            if level == TraceLevel::Full {\n");
+        code.push_str("                let tape: String = self.result.iter().map(|x| x.as_str()).collect();\n");
+        code.push_str("                let offset: usize = self.result[..self.index].iter().map(|x| x.as_str().len()).sum();\n");
+        code.push_str("                println!(\"{}\", tape);\n");
+        code.push_str("                println!(\"{}^\", \" \".repeat(offset));\n");
+        code.push_str("            }\n");
+        code.push_str("        }\n");
         code.push_str("    }\n");
 
         code.push_str("}\n\n");
 
+        // Generate the trace settings, the defaults come from the codegen options
+        code.push_str("#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]\n");
+        code.push_str("enum TraceLevel {\n    None,\n    Transitions,\n    Full,\n}\n\n");
+        code.push_str("fn trace_settings() -> (TraceLevel, bool) {\n");
+        code.push_str(&format!(
+            "    let level = match std::env::var(\"{}\").as_deref() {{\n",
+            TRACE_ENV_VAR
+        ));
+        code.push_str("        Ok(\"none\") => TraceLevel::None,\n");
+        code.push_str("        Ok(\"transitions\") => TraceLevel::Transitions,\n");
+        code.push_str("        Ok(\"full\") => TraceLevel::Full,\n");
+        code.push_str(&format!(
+            "        _ => TraceLevel::{},\n",
+            match options.trace.level {
+                TraceLevel::None => "None",
+                TraceLevel::Transitions => "Transitions",
+                TraceLevel::Full => "Full",
+            }
+        ));
+        code.push_str("    };\n");
+        code.push_str(&format!(
+            "    let compact = match std::env::var(\"{}\").as_deref() {{\n",
+            TRACE_FORMAT_ENV_VAR
+        ));
+        code.push_str("        Ok(\"compact\") => true,\n");
+        code.push_str("        Ok(\"human\") => false,\n");
+        code.push_str(&format!(
+            "        _ => {},\n",
+            options.trace.format == TraceFormat::Compact
+        ));
+        code.push_str("    };\n");
+        code.push_str("    (level, compact)\n");
+        code.push_str("}\n\n");
+
+        match options.dispatch {
+            RustDispatch::Match => self.push_match_dispatch(&mut code),
+            RustDispatch::Table => self.push_transition_table(&mut code),
+        }
+
         // Generate the main function
         code.push_str("fn main() {\n");
         code.push_str("    println!(\"Enter the number of steps:\");\n");
@@ -143,29 +370,48 @@ impl ParseTree {
             self.initial_state
         ));
 
+        code.push_str("    let (trace_level, compact_trace) = trace_settings();\n");
+        if options.dispatch == RustDispatch::Table {
+            code.push_str(&format!(
+                "    let mut state: usize = {};\n",
+                self.states
+                    .iter()
+                    .position(|s| s == &self.initial_state)
+                    .unwrap_or(0)
+            ));
+        }
         code.push_str("    for i in 0..steps {\n");
-        code.push_str("        println!(\"Step: {} State: {:?} Symbol: {:?}\",\n");
-        code.push_str(
-            "            i, tape_machine.state, tape_machine.result[tape_machine.index]);\n\n",
-        );
+        code.push_str("        tape_machine.trace(i, trace_level, compact_trace);\n\n");
+
+        match options.dispatch {
+            RustDispatch::Match => {
+                code.push_str("        step(&mut tape_machine, trace_level, compact_trace);\n")
+            }
+            RustDispatch::Table => self.push_table_dispatch(&mut code),
+        }
+        code.push_str("    }\n\n");
+
+        code.push_str("    let binary_result: String = tape_machine.result.iter().map(|x| x.as_str()).collect();\n");
+        code.push_str("    println!(\"{}\", binary_result);\n");
+        code.push_str("    let clean_result: String = tape_machine.result.iter().filter( |&x| x != &TapeMachineSymbol::SymbolX).map(|x| x.as_str()).collect();\n");
+        code.push_str("    println!(\"=========\\n\");\n");
+        code.push_str("    println!(\"{}\", clean_result);\n");
+        code.push_str("}\n");
+
+        code
+    }
 
+    // `step` applying the transition for the current state and symbol, one
+    // `match` arm per transition. The steps loop of `main` calls it, it also
+    // runs the machine without stdin.
+    fn push_match_dispatch(&self, code: &mut String) {
+        code.push_str("// Apply the transition for the current state and symbol\n");
+        code.push_str("fn step(tape_machine: &mut TapeMachine, trace_level: TraceLevel, compact_trace: bool) {\n");
         code.push_str(
-            "        match (tape_machine.state, &tape_machine.result[tape_machine.index]) {\n",
+            "    match (tape_machine.state, &tape_machine.result[tape_machine.index]) {\n",
         );
 
-        // Sort transitions so that the ones with star condition are executed last
-        // This is to ensure compatibility with switch statements
-        let mut sorted_transitions = self.transitions.clone();
-        sorted_transitions.sort_by(|a, b| {
-            if a.condition == Condition::Star {
-                std::cmp::Ordering::Greater
-            } else if b.condition == Condition::Star {
-                std::cmp::Ordering::Less
-            } else {
-                std::cmp::Ordering::Equal
-            }
-        });
-        for transition in sorted_transitions {
+        for transition in self.transitions_star_last() {
             let condition = match &transition.condition {
                 Condition::OR(symbols) => {
                     let mut condition_str = String::new();
@@ -181,21 +427,21 @@ impl ParseTree {
             };
 
             code.push_str(&format!(
-                "            (TapeMachineState::{}, {}) =>",
+                "        (TapeMachineState::{}, {}) =>",
                 transition.initial_state, condition
             ));
             code.push_str("{\n");
 
             for step in &transition.steps {
                 match step {
-                    TransitionStep::R => code.push_str("                tape_machine.r();\n"),
-                    TransitionStep::L => code.push_str("                tape_machine.l();\n"),
+                    TransitionStep::R => code.push_str("            tape_machine.r();\n"),
+                    TransitionStep::L => code.push_str("            tape_machine.l();\n"),
                     TransitionStep::X => {
-                        code.push_str("                // X means do nothing\n");
+                        code.push_str("            // X means do nothing\n");
                     }
                     TransitionStep::P(symbol) => {
                         code.push_str(&format!(
-                            "                tape_machine.p(TapeMachineSymbol::Symbol{});\n",
+                            "            tape_machine.p(TapeMachineSymbol::Symbol{});\n",
                             symbol
                         ));
                     }
@@ -203,41 +449,476 @@ impl ParseTree {
             }
 
             code.push_str(&format!(
-                "                tape_machine.state = &TapeMachineState::{};\n",
+                "            tape_machine.state = &TapeMachineState::{};\n",
                 transition.final_state
             ));
+            code.push_str(
+                "            if trace_level >= TraceLevel::Transitions && !compact_trace {\n",
+            );
             code.push_str(&format!(
                 "                println!(\"Final State: {{:?}}\", TapeMachineState::{});\n",
                 transition.final_state
             ));
             code.push_str("            }\n");
+            code.push_str("        }\n");
+        }
+
+        code.push_str("        (_, _) => {\n");
+        code.push_str("            println!(\"State: {:?} Index: {:?} Symbol: {:?}\", tape_machine.state, tape_machine.index, tape_machine.result[tape_machine.index]);\n");
+        code.push_str("            let binary_result: String = tape_machine.result.iter().map(|x| x.as_str()).collect();\n");
+        code.push_str("            println!(\"{}\", binary_result);\n");
+        code.push_str("            panic!(\"Invalid state reached\");\n");
+        code.push_str("        }\n");
+        code.push_str("    }\n");
+        code.push_str("}\n\n");
+    }
+
+    // Transition table of the table driven program: the actions of every
+    // transition are stored once in `ACTIONS`, `TRANSITIONS` gives for every
+    // (state, symbol) pair the range of its actions and the next state
+    fn push_transition_table(&self, code: &mut String) {
+        let state_index = |name: &str| self.states.iter().position(|s| s == name);
+        let symbol_index = |name: &str| self.symbols.iter().position(|s| s == name);
+
+        let mut actions: Vec<String> = Vec::new();
+        // (first action, number of actions) of every transition
+        let mut ranges = Vec::new();
+        for transition in &self.transitions {
+            let start = actions.len();
+            for step in &transition.steps {
+                match step {
+                    TransitionStep::R => actions.push("Action::R".to_string()),
+                    TransitionStep::L => actions.push("Action::L".to_string()),
+                    TransitionStep::X => {}
+                    TransitionStep::P(symbol) => {
+                        if let Some(symbol) = symbol_index(symbol) {
+                            actions.push(format!("Action::P({})", symbol));
+                        }
+                    }
+                }
+            }
+            ranges.push((start, actions.len() - start));
+        }
+
+        code.push_str("#[derive(Debug, Clone, Copy)]\n");
+        code.push_str("enum Action {\n    R,\n    L,\n    P(u32),\n}\n\n");
+        code.push_str("#[derive(Debug, Clone, Copy)]\n");
+        code.push_str("struct Entry {\n    next: u32,\n    start: u32,\n    len: u32,\n}\n\n");
+        code.push_str("const NO_TRANSITION: u32 = u32::MAX;\n");
+        code.push_str(&format!(
+            "const SYMBOL_COUNT: usize = {};\n\n",
+            self.symbols.len()
+        ));
+
+        code.push_str(&format!(
+            "static STATES: [TapeMachineState; {}] = [\n",
+            self.states.len()
+        ));
+        for state in &self.states {
+            code.push_str(&format!("    TapeMachineState::{},\n", state));
+        }
+        code.push_str("];\n\n");
+
+        code.push_str(&format!(
+            "static SYMBOLS: [TapeMachineSymbol; {}] = [\n",
+            self.symbols.len()
+        ));
+        for symbol in &self.symbols {
+            code.push_str(&format!("    TapeMachineSymbol::Symbol{},\n", symbol));
+        }
+        code.push_str("];\n\n");
+
+        code.push_str(&format!(
+            "static ACTIONS: [Action; {}] = [\n",
+            actions.len()
+        ));
+        for action in &actions {
+            code.push_str(&format!("    {},\n", action));
         }
+        code.push_str("];\n\n");
 
-        code.push_str("            (_, _) => {\n");
-        code.push_str("                println!(\"State: {:?} Index: {:?} Symbol: {:?}\", tape_machine.state, tape_machine.index, tape_machine.result[tape_machine.index]);\n");
-        code.push_str("                let binary_result: String = tape_machine.result.iter().map(|x| x.as_str()).collect();\n");
-        code.push_str("                println!(\"{}\", binary_result);\n");
-        code.push_str("                panic!(\"Invalid state reached\");\n");
+        code.push_str(&format!(
+            "static TRANSITIONS: [Entry; {}] = [\n",
+            self.states.len() * self.symbols.len()
+        ));
+        for state in &self.states {
+            for symbol in &self.symbols {
+                let entry = self.transition_for(state, symbol).and_then(|index| {
+                    let next = state_index(&self.transitions[index].final_state)?;
+                    Some((next, ranges[index]))
+                });
+                match entry {
+                    Some((next, (start, len))) => code.push_str(&format!(
+                        "    Entry {{ next: {}, start: {}, len: {} }}, // {}, {}\n",
+                        next, start, len, state, symbol
+                    )),
+                    None => code.push_str(&format!(
+                        "    Entry {{ next: NO_TRANSITION, start: 0, len: 0 }}, // {}, {}\n",
+                        state, symbol
+                    )),
+                }
+            }
+        }
+        code.push_str("];\n\n");
+    }
+
+    // Table lookup and the actions of the transition, in the body of the
+    // steps loop
+    fn push_table_dispatch(&self, code: &mut String) {
+        code.push_str(
+            "        let symbol = tape_machine.result[tape_machine.index].clone() as usize;\n",
+        );
+        code.push_str("        let entry = TRANSITIONS[state * SYMBOL_COUNT + symbol];\n");
+        code.push_str("        if entry.next == NO_TRANSITION {\n");
+        code.push_str("            println!(\"State: {:?} Index: {:?} Symbol: {:?}\", tape_machine.state, tape_machine.index, tape_machine.result[tape_machine.index]);\n");
+        code.push_str("            let binary_result: String = tape_machine.result.iter().map(|x| x.as_str()).collect();\n");
+        code.push_str("            println!(\"{}\", binary_result);\n");
+        code.push_str("            panic!(\"Invalid state reached\");\n");
+        code.push_str("        }\n");
+        code.push_str("        for action in &ACTIONS[entry.start as usize..(entry.start + entry.len) as usize] {\n");
+        code.push_str("            match *action {\n");
+        code.push_str("                Action::R => tape_machine.r(),\n");
+        code.push_str("                Action::L => tape_machine.l(),\n");
+        code.push_str("                Action::P(symbol) => tape_machine.p(SYMBOLS[symbol as usize].clone()),\n");
         code.push_str("            }\n");
         code.push_str("        }\n");
+        code.push_str("        state = entry.next as usize;\n");
+        code.push_str("        tape_machine.state = &STATES[state];\n");
+        code.push_str("        if trace_level >= TraceLevel::Transitions && !compact_trace {\n");
+        code.push_str("            println!(\"Final State: {:?}\", STATES[state]);\n");
+        code.push_str("        }\n");
+    }
+
+    // Library module to embed a machine in another crate:
+    //
+    //     let mut machine = TapeMachine::new(vec![TapeMachineSymbol::Symbol1]);
+    //     let halt = machine.run(1000);
+    //
+    // The tape grows with blanks to the right, the machine halts instead of
+    // moving the head left of the first cell. `snapshot` and `from_snapshot`
+    // save and restore a run in the format of `snapshot::to_text`.
+    pub fn to_rust_library(&self) -> String {
+        let mut code = String::new();
+
+        code.push_str("// Generated by state_machine_compiler_rust, do not edit\n\n");
+        code.push_str("#[allow(non_camel_case_types)]\n");
+        code.push_str("#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]\n");
+        code.push_str("pub enum TapeMachineState {\n");
+        for state in &self.states {
+            code.push_str(&format!("    {},\n", state));
+        }
+        code.push_str("}\n\n");
+
+        code.push_str("impl TapeMachineState {\n");
+        code.push_str("    pub fn as_str(&self) -> &'static str {\n");
+        code.push_str("        match self {\n");
+        for state in &self.states {
+            code.push_str(&format!(
+                "            TapeMachineState::{} => \"{}\",\n",
+                state, state
+            ));
+        }
+        code.push_str("        }\n");
         code.push_str("    }\n\n");
+        code.push_str("    pub fn from_name(name: &str) -> Option<Self> {\n");
+        code.push_str("        match name {\n");
+        for state in &self.states {
+            code.push_str(&format!(
+                "            \"{}\" => Some(TapeMachineState::{}),\n",
+                state, state
+            ));
+        }
+        code.push_str("            _ => None,\n");
+        code.push_str("        }\n");
+        code.push_str("    }\n");
+        code.push_str("}\n\n");
 
-        code.push_str("    let binary_result: String = tape_machine.result.iter().map(|x| x.as_str()).collect();\n");
-        code.push_str("    println!(\"{}\", binary_result);\n");
-        code.push_str("    let clean_result: String = tape_machine.result.iter().filter( |&x| x != &TapeMachineSymbol::SymbolX).map(|x| x.as_str()).collect();\n");
-        code.push_str("    println!(\"=========\\n\");\n");
-        code.push_str("    println!(\"{}\", clean_result);\n");
+        code.push_str("#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]\n");
+        code.push_str("pub enum TapeMachineSymbol {\n");
+        for symbol in &self.symbols {
+            code.push_str(&format!("    Symbol{},\n", symbol));
+        }
+        code.push_str("}\n\n");
+
+        code.push_str("impl TapeMachineSymbol {\n");
+        code.push_str("    pub fn as_str(&self) -> &'static str {\n");
+        code.push_str("        match self {\n");
+        for symbol in &self.symbols {
+            code.push_str(&format!(
+                "            TapeMachineSymbol::Symbol{} => \"{}\",\n",
+                symbol, symbol
+            ));
+        }
+        code.push_str("        }\n");
+        code.push_str("    }\n\n");
+        code.push_str("    pub fn from_name(name: &str) -> Option<Self> {\n");
+        code.push_str("        match name {\n");
+        for symbol in &self.symbols {
+            code.push_str(&format!(
+                "            \"{}\" => Some(TapeMachineSymbol::Symbol{}),\n",
+                symbol, symbol
+            ));
+        }
+        code.push_str("            _ => None,\n");
+        code.push_str("        }\n");
+        code.push_str("    }\n");
+        code.push_str("}\n\n");
+
+        code.push_str("// Why the machine stopped\n");
+        code.push_str("#[derive(Debug, PartialEq, Eq, Clone, Copy)]\n");
+        code.push_str("pub enum Halt {\n");
+        code.push_str("    // No transition for the current state and symbol\n");
+        code.push_str("    NoTransition,\n");
+        code.push_str("    // The transition would move the head left of the first cell\n");
+        code.push_str("    LeftEdge,\n");
+        code.push_str("}\n\n");
+
+        code.push_str("#[derive(Debug, PartialEq, Eq, Clone)]\n");
+        code.push_str("pub struct TapeMachine {\n");
+        code.push_str("    state: TapeMachineState,\n");
+        code.push_str("    tape: Vec<TapeMachineSymbol>,\n");
+        code.push_str("    head: usize,\n");
+        code.push_str("    steps: usize,\n");
+        code.push_str("}\n\n");
+
+        code.push_str("impl TapeMachine {\n");
+        code.push_str("    pub fn new(mut tape: Vec<TapeMachineSymbol>) -> Self {\n");
+        code.push_str("        if tape.is_empty() {\n");
+        code.push_str("            tape.push(TapeMachineSymbol::SymbolX);\n");
+        code.push_str("        }\n");
+        code.push_str("        TapeMachine {\n");
+        code.push_str(&format!(
+            "            state: TapeMachineState::{},\n",
+            self.initial_state
+        ));
+        code.push_str("            tape,\n");
+        code.push_str("            head: 0,\n");
+        code.push_str("            steps: 0,\n");
+        code.push_str("        }\n");
+        code.push_str("    }\n\n");
+
+        code.push_str("    pub fn state(&self) -> TapeMachineState {\n");
+        code.push_str("        self.state\n");
+        code.push_str("    }\n\n");
+        code.push_str("    pub fn tape(&self) -> &[TapeMachineSymbol] {\n");
+        code.push_str("        &self.tape\n");
+        code.push_str("    }\n\n");
+        code.push_str("    pub fn head(&self) -> usize {\n");
+        code.push_str("        self.head\n");
+        code.push_str("    }\n\n");
+        code.push_str("    pub fn steps(&self) -> usize {\n");
+        code.push_str("        self.steps\n");
+        code.push_str("    }\n\n");
+
+        code.push_str("    // The state, head, steps and tape, one `key: value` line each\n");
+        code.push_str("    pub fn snapshot(&self) -> String {\n");
+        code.push_str("        let tape: Vec<&str> = self.tape.iter().map(|symbol| symbol.as_str()).collect();\n");
+        code.push_str("        format!(\n");
+        code.push_str("            \"state: {}\\nhead: {}\\nsteps: {}\\ntape: {}\\n\",\n");
+        code.push_str("            self.state.as_str(),\n");
+        code.push_str("            self.head,\n");
+        code.push_str("            self.steps,\n");
+        code.push_str("            tape.join(\",\")\n");
+        code.push_str("        )\n");
+        code.push_str("    }\n\n");
+        code.push_str("    // Resume a run saved by `snapshot`\n");
+        code.push_str("    pub fn from_snapshot(snapshot: &str) -> Result<Self, String> {\n");
+        code.push_str(
+            "        let (mut state, mut head, mut steps, mut tape) = (None, None, None, None);\n",
+        );
+        code.push_str("        for line in snapshot.lines() {\n");
+        code.push_str("            let line = line.trim();\n");
+        code.push_str("            if line.is_empty() || line.starts_with('#') {\n");
+        code.push_str("                continue;\n");
+        code.push_str("            }\n");
+        code.push_str("            let (key, value) = line\n");
+        code.push_str("                .split_once(':')\n");
+        code.push_str("                .ok_or(format!(\"Invalid line: {}\", line))?;\n");
+        code.push_str("            let value = value.trim();\n");
+        code.push_str("            match key.trim() {\n");
+        code.push_str("                \"state\" => {\n");
+        code.push_str("                    state = Some(\n");
+        code.push_str("                        TapeMachineState::from_name(value)\n");
+        code.push_str(
+            "                            .ok_or(format!(\"Unknown state: {}\", value))?,\n",
+        );
+        code.push_str("                    )\n");
+        code.push_str("                }\n");
+        code.push_str("                \"head\" => {\n");
+        code.push_str("                    head = Some(\n");
+        code.push_str("                        value\n");
+        code.push_str("                            .parse::<usize>()\n");
+        code.push_str(
+            "                            .map_err(|e| format!(\"Invalid head: {}\", e))?,\n",
+        );
+        code.push_str("                    )\n");
+        code.push_str("                }\n");
+        code.push_str("                \"steps\" => {\n");
+        code.push_str("                    steps = Some(\n");
+        code.push_str("                        value\n");
+        code.push_str("                            .parse::<usize>()\n");
+        code.push_str(
+            "                            .map_err(|e| format!(\"Invalid steps: {}\", e))?,\n",
+        );
+        code.push_str("                    )\n");
+        code.push_str("                }\n");
+        code.push_str("                \"tape\" => {\n");
+        code.push_str("                    tape = Some(\n");
+        code.push_str("                        value\n");
+        code.push_str("                            .split(',')\n");
+        code.push_str("                            .map(|name| {\n");
+        code.push_str(
+            "                                TapeMachineSymbol::from_name(name.trim())\n",
+        );
+        code.push_str("                                    .ok_or(format!(\"Unknown symbol: {}\", name.trim()))\n");
+        code.push_str("                            })\n");
+        code.push_str(
+            "                            .collect::<Result<Vec<TapeMachineSymbol>, String>>()?,\n",
+        );
+        code.push_str("                    )\n");
+        code.push_str("                }\n");
+        code.push_str("                key => return Err(format!(\"Unknown key: {}\", key)),\n");
+        code.push_str("            }\n");
+        code.push_str("        }\n");
+        code.push_str("        let machine = TapeMachine {\n");
+        code.push_str("            state: state.ok_or(\"Missing state\")?,\n");
+        code.push_str("            head: head.ok_or(\"Missing head\")?,\n");
+        code.push_str("            tape: tape.ok_or(\"Missing tape\")?,\n");
+        code.push_str("            steps: steps.ok_or(\"Missing steps\")?,\n");
+        code.push_str("        };\n");
+        code.push_str("        if machine.head >= machine.tape.len() {\n");
+        code.push_str(
+            "            return Err(\"The head is past the end of the tape\".to_string());\n",
+        );
+        code.push_str("        }\n");
+        code.push_str("        Ok(machine)\n");
+        code.push_str("    }\n\n");
+
+        code.push_str("    fn p(&mut self, symbol: TapeMachineSymbol) {\n");
+        code.push_str("        self.tape[self.head] = symbol;\n");
+        code.push_str("    }\n\n");
+        code.push_str("    fn r(&mut self) {\n");
+        code.push_str("        self.head += 1;\n");
+        code.push_str("        if self.head == self.tape.len() {\n");
+        code.push_str("            self.tape.push(TapeMachineSymbol::SymbolX);\n");
+        code.push_str("        }\n");
+        code.push_str("    }\n\n");
+        code.push_str("    fn l(&mut self) {\n");
+        code.push_str("        self.head -= 1;\n");
+        code.push_str("    }\n\n");
+
+        code.push_str("    // Apply one transition, nothing changes if the machine halts\n");
+        code.push_str("    #[allow(unreachable_patterns)]\n");
+        code.push_str("    pub fn step(&mut self) -> Result<(), Halt> {\n");
+        code.push_str("        let next = match (self.state, self.tape[self.head]) {\n");
+        for transition in self.transitions_star_last() {
+            let condition = match &transition.condition {
+                Condition::OR(symbols) => symbols
+                    .iter()
+                    .map(|symbol| format!("TapeMachineSymbol::Symbol{}", symbol))
+                    .collect::<Vec<String>>()
+                    .join(" | "),
+                Condition::Star => "_".to_string(),
+            };
+            code.push_str(&format!(
+                "            (TapeMachineState::{}, {}) => {{\n",
+                transition.initial_state, condition
+            ));
+            let left_reach = transition.left_reach();
+            if left_reach > 0 {
+                code.push_str(&format!(
+                    "                if self.head < {} {{\n",
+                    left_reach
+                ));
+                code.push_str("                    return Err(Halt::LeftEdge);\n");
+                code.push_str("                }\n");
+            }
+            for step in &transition.steps {
+                match step {
+                    TransitionStep::R => code.push_str("                self.r();\n"),
+                    TransitionStep::L => code.push_str("                self.l();\n"),
+                    TransitionStep::X => {}
+                    TransitionStep::P(symbol) => code.push_str(&format!(
+                        "                self.p(TapeMachineSymbol::Symbol{});\n",
+                        symbol
+                    )),
+                }
+            }
+            code.push_str(&format!(
+                "                TapeMachineState::{}\n",
+                transition.final_state
+            ));
+            code.push_str("            }\n");
+        }
+        code.push_str("            _ => return Err(Halt::NoTransition),\n");
+        code.push_str("        };\n");
+        code.push_str("        self.state = next;\n");
+        code.push_str("        self.steps += 1;\n");
+        code.push_str("        Ok(())\n");
+        code.push_str("    }\n\n");
+
+        code.push_str(
+            "    // Run until the machine halts or `max_steps` steps have been applied\n",
+        );
+        code.push_str("    pub fn run(&mut self, max_steps: usize) -> Option<Halt> {\n");
+        code.push_str("        for _ in 0..max_steps {\n");
+        code.push_str("            if let Err(halt) = self.step() {\n");
+        code.push_str("                return Some(halt);\n");
+        code.push_str("            }\n");
+        code.push_str("        }\n");
+        code.push_str("        None\n");
+        code.push_str("    }\n");
         code.push_str("}\n");
 
         code
     }
 }
+
+// Options for the generated dot file
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DotOptions {
+    // Colour and thicken the edges by how often the transition was applied
+    pub coverage: Option<Coverage>,
+    // One edge per pair of states, with the label of every transition
+    // between them on its own line
+    pub merge_edges: bool,
+    // Draw the states without any transition out of them differently
+    pub style_halting: bool,
+    // Group the states whose names only differ by their trailing digits,
+    // e.g. `add0` and `add1`, into a cluster named after the prefix
+    pub clusters: bool,
+    // Active state and firing transition of one step of a run
+    pub highlight: Option<DotHighlight>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DotHighlight {
+    pub state: String,
+    // Index in ParseTree::transitions of the transition about to be applied
+    pub transition: Option<usize>,
+    // Shown as the title of the graph
+    pub caption: String,
+}
+
 pub trait ToDot {
-    fn to_dot(&self) -> String;
+    fn to_dot(&self) -> String {
+        self.to_dot_with(&DotOptions::default())
+    }
+    fn to_dot_with(&self, options: &DotOptions) -> String;
+}
+
+// Edge of the dot file, for one or more transitions between two states
+struct DotEdge<'a> {
+    from: &'a str,
+    to: &'a str,
+    labels: Vec<String>,
+    hits: usize,
+    // Indices of the transitions drawn by this edge
+    transitions: Vec<usize>,
 }
 
 impl ToDot for ParseTree {
-    fn to_dot(&self) -> String {
+    fn to_dot_with(&self, options: &DotOptions) -> String {
         let mut dot = String::from(
             "digraph {
                 rankdir=LR;
@@ -246,57 +927,144 @@ impl ToDot for ParseTree {
                 edge [fontcolor=blue, fontname=\"Arial\"];
                 ",
         );
+        if let Some(highlight) = &options.highlight {
+            dot.push_str(&format!("  label=\"{}\"; ", dot_escape(&highlight.caption)));
+        }
 
-        // Define states
+        // Define states, grouped by prefix in the order they first appear
+        let mut groups: Vec<(&str, Vec<&String>)> = Vec::new();
         for state in &self.states {
-            let shape = if state == &self.initial_state {
-                "doublecircle"
-            } else {
-                "circle"
-            };
-            let fillcolor = if state == &self.initial_state {
-                "lightgreen"
-            } else {
-                "lightblue"
-            };
-            let width = if state == &self.initial_state {
-                "1.5"
-            } else {
-                "1.2"
-            };
-            let height = if state == &self.initial_state {
-                "1.5"
-            } else {
-                "1.2"
-            };
-            dot.push_str(&format!(
-                "  \"{}\" [shape={}, fillcolor={}, width={}, height={}]; ",
-                state, shape, fillcolor, width, height
-            ));
+            let prefix = state.trim_end_matches(|c: char| c.is_ascii_digit());
+            match groups
+                .iter_mut()
+                .find(|(p, _)| options.clusters && *p == prefix)
+            {
+                Some((_, states)) => states.push(state),
+                None => groups.push((prefix, vec![state])),
+            }
+        }
+        for (prefix, states) in groups {
+            let cluster = states.len() > 1 && !prefix.is_empty();
+            if cluster {
+                dot.push_str(&format!(
+                    "  subgraph \"cluster_{}\" {{ label=\"{}\"; ",
+                    dot_escape(prefix),
+                    dot_escape(prefix)
+                ));
+            }
+            for state in states {
+                let initial = state == &self.initial_state;
+                let halting = options.style_halting
+                    && self
+                        .symbols
+                        .iter()
+                        .all(|symbol| self.transition_for(state, symbol).is_none());
+                let shape = if initial {
+                    "doublecircle"
+                } else if halting {
+                    "doubleoctagon"
+                } else {
+                    "circle"
+                };
+                let active = options
+                    .highlight
+                    .as_ref()
+                    .is_some_and(|highlight| &highlight.state == state);
+                let fillcolor = if active {
+                    "gold"
+                } else if halting {
+                    "lightcoral"
+                } else if initial {
+                    "lightgreen"
+                } else {
+                    "lightblue"
+                };
+                let width = if initial { "1.5" } else { "1.2" };
+                let height = if initial { "1.5" } else { "1.2" };
+                let penwidth = if active { ", penwidth=3" } else { "" };
+                dot.push_str(&format!(
+                    "  \"{}\" [shape={}, fillcolor={}, width={}, height={}{}]; ",
+                    dot_escape(state),
+                    shape,
+                    fillcolor,
+                    width,
+                    height,
+                    penwidth
+                ));
+            }
+            if cluster {
+                dot.push_str(" } ");
+            }
         }
 
         // Define transitions
-        for transition in &self.transitions {
-            let condition = match &transition.condition {
-                Condition::OR(symbols) => format!("[{}]", symbols.join(",")),
-                Condition::Star => "*".to_string(),
-            };
-            let steps: Vec<String> = transition
-                .steps
+        let mut edges: Vec<DotEdge> = Vec::new();
+        for (index, transition) in self.transitions.iter().enumerate() {
+            let mut label = transition.label();
+            let mut hits = 0;
+            if let Some(coverage) = &options.coverage {
+                hits = coverage.hits[index];
+                label.push_str(&format!(" ({})", hits));
+            }
+            let from = transition.initial_state.as_str();
+            let to = transition.final_state.as_str();
+            let merged = edges
+                .iter_mut()
+                .find(|edge| options.merge_edges && edge.from == from && edge.to == to);
+            match merged {
+                Some(edge) => {
+                    edge.labels.push(label);
+                    edge.hits += hits;
+                    edge.transitions.push(index);
+                }
+                None => edges.push(DotEdge {
+                    from,
+                    to,
+                    labels: vec![label],
+                    hits,
+                    transitions: vec![index],
+                }),
+            }
+        }
+
+        let max_hits = edges.iter().map(|edge| edge.hits).max().unwrap_or(0);
+        for edge in edges {
+            let mut color = "black";
+            let mut style = String::new();
+            if options.coverage.is_some() {
+                if edge.hits == 0 {
+                    color = "red";
+                    style = ", style=dashed".to_string();
+                } else {
+                    // Between 1 and 5 depending on the share of the busiest edge
+                    let width = 1.0 + 4.0 * edge.hits as f64 / max_hits as f64;
+                    color = "darkgreen";
+                    style = format!(", penwidth={:.1}", width);
+                }
+            }
+            let firing = options
+                .highlight
+                .as_ref()
+                .and_then(|highlight| highlight.transition)
+                .is_some_and(|transition| edge.transitions.contains(&transition));
+            if firing {
+                color = "orange";
+                style = ", penwidth=3, fontcolor=orange".to_string();
+            }
+            let label = edge
+                .labels
                 .iter()
-                .map(|step| match step {
-                    TransitionStep::R => "R".to_string(),
-                    TransitionStep::L => "L".to_string(),
-                    TransitionStep::X => "X".to_string(),
-                    TransitionStep::P(func) => format!("P({})", func),
-                })
-                .collect();
-            let label = format!("{} / {}", condition, steps.join("-"));
-            let color = "black";
+                .map(|label| dot_escape(label))
+                .collect::<Vec<String>>()
+                .join("\\n");
             dot.push_str(&format!(
-                "  \"{}\" -> \"{}\" [label=\"{}\", color={}];
+                "  \"{}\" -> \"{}\" [label=\"{}\", color={}{}];
 ",
-                transition.initial_state, transition.final_state, label, color
+                dot_escape(edge.from),
+                dot_escape(edge.to),
+                label,
+                color,
+                style
             ));
         }
 
@@ -310,13 +1078,15 @@ pub struct Parser {
     lexer: Lexer,
     cur_token: Token,
     peek_token: Token,
+    cur_span: Span,
+    peek_span: Span,
     pub tree: ParseTree,
 }
 
 impl Parser {
     pub fn new(lexer: Lexer) -> Self {
         info!("Initializing Parser");
-        let mut parser = Parser {
+        Parser {
             lexer,
             cur_token: Token {
                 text: "\0".to_string(),
@@ -326,16 +1096,20 @@ impl Parser {
                 text: "\0".to_string(),
                 kind: TokenType::EOF,
             },
+            cur_span: Span::new(1, 1, 0),
+            peek_span: Span::new(1, 1, 0),
             tree: ParseTree {
                 states: Vec::new(),
                 initial_state: "".to_string(),
                 symbols: Vec::new(),
                 transitions: Vec::new(),
             },
-        };
-        parser.next_token(); // Initialize peek_token
-        parser.next_token(); // Initialize cur_token
-        parser
+        }
+    }
+
+    // Errors and warnings reported while lexing and parsing
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.lexer.diagnostics
     }
 
     // Check if the current token matches the expected token type
@@ -349,29 +1123,51 @@ impl Parser {
     }
 
     // Advance to the next token
-    fn next_token(&mut self) {
+    fn next_token(&mut self) -> Result<(), Diagnostic> {
         self.cur_token = self.peek_token.clone();
-        self.peek_token = self.lexer.get_token().unwrap_or(Token {
-            text: "\0".to_string(),
-            kind: TokenType::EOF,
-        });
+        self.cur_span = self.peek_span;
+        self.peek_token = self.lexer.try_get_token()?;
+        self.peek_span = self.lexer.span();
 
         // If both current and peek token are newline, skip the newline
         if self.check_token(TokenType::NEWLINE) && self.check_peek(TokenType::NEWLINE) {
-            self.next_token();
+            self.next_token()?;
         }
+        Ok(())
     }
 
-    // Abort the parsing process with an error message
-    fn abort(&self, message: &str) {
-        error!("Parsing error: {}", message);
-        panic!("Parsing error: {}", message);
+    // Report an error pointing at the current token, returned to abort the
+    // parsing process
+    fn abort(&mut self, message: &str) -> Diagnostic {
+        self.abort_at(self.cur_span, message, None)
+    }
+
+    // Report an error pointing at `span`, returned to abort the parsing process
+    fn abort_at(&mut self, span: Span, message: &str, help: Option<String>) -> Diagnostic {
+        let diagnostic = Diagnostic::error(message, span).with_help(help);
+        self.lexer.report(diagnostic.clone());
+        diagnostic
+    }
+
+    // Report a problem which does not stop the parsing
+    fn warn_at(&mut self, span: Span, message: &str, help: Option<String>) {
+        self.lexer
+            .report(Diagnostic::warning(message, span).with_help(help));
+    }
+
+    // Warn if `state` is used in a transition without being declared
+    fn check_state_declared(&mut self, state: &str, span: Span) {
+        if !self.tree.states.iter().any(|s| s == state) {
+            let help = closest_match(state, &self.tree.states)
+                .map(|s| format!("did you mean state `{}`?", s));
+            self.warn_at(span, &format!("State {} is not declared.", state), help);
+        }
     }
 
     // Try to consume the current token if it matches the expected token type
     // If successful, print the token type and text (if available) and execute the optional action
     // Return true if the token was consumed, false otherwise
-    fn try_consume<F>(&mut self, kind: TokenType, action: Option<F>) -> bool
+    fn try_consume<F>(&mut self, kind: TokenType, action: Option<F>) -> Result<bool, Diagnostic>
     where
         F: FnMut(&Token),
     {
@@ -385,263 +1181,270 @@ impl Parser {
                 action(&self.cur_token);
             }
 
-            self.next_token();
-            true
+            self.next_token()?;
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
 
     // Consume the current token if it matches the expected token type
     // If not, abort with an error message
     // Execute the optional action if provided
-    fn consume<F>(&mut self, expected: TokenType, action: Option<F>)
+    fn consume<F>(&mut self, expected: TokenType, action: Option<F>) -> Result<(), Diagnostic>
     where
         F: FnMut(&Token),
     {
-        if !self.try_consume(expected, action) {
-            self.abort(&format!(
+        if !self.try_consume(expected, action)? {
+            return Err(self.abort(&format!(
                 "Expected {:?}, got {:?}",
                 expected, self.cur_token.kind
-            ));
+            )));
         }
+        Ok(())
     }
 
     // Parse an initial state identifier: [IDENT]
-    fn initial_state_identifier(&mut self) {
-        self.consume(TokenType::LeftBracket, None::<fn(&Token)>);
+    fn initial_state_identifier(&mut self) -> Result<(), Diagnostic> {
+        self.consume(TokenType::LeftBracket, None::<fn(&Token)>)?;
+        let span = self.cur_span;
         let mut initial_state = String::new();
         self.consume(
             TokenType::IDENT,
             Some(|token: &Token| {
                 initial_state.push_str(&token.text);
             }),
-        );
+        )?;
         if self.tree.initial_state.is_empty() {
             self.tree.initial_state = initial_state.clone();
             self.tree.states.push(initial_state);
         } else {
-            self.abort("Initial state already defined.");
+            return Err(self.abort_at(span, "Initial state already defined.", None));
         }
-        self.consume(TokenType::RightBracket, None::<fn(&Token)>);
+        self.consume(TokenType::RightBracket, None::<fn(&Token)>)?;
         debug!("INITIAL_STATE_IDENTIFIER");
+        Ok(())
     }
 
     // Parse a list of state identifiers: IDENT (',' IDENT)*
-    fn state_identifier_list(&mut self) {
+    fn state_identifier_list(&mut self) -> Result<(), Diagnostic> {
         let mut state_identifiers = Vec::new();
 
         // Consume all tokens
         while self.check_token(TokenType::IDENT) || self.check_token(TokenType::LeftBracket) {
+            let span = self.cur_span;
             if self.check_token(TokenType::LeftBracket) {
-                self.initial_state_identifier();
+                self.initial_state_identifier()?;
             } else if !self.try_consume(
                 TokenType::IDENT,
                 Some(|token: &Token| {
-                    state_identifiers.push(token.text.clone());
+                    state_identifiers.push((token.text.clone(), span));
                 }),
-            ) {
+            )? {
                 break;
             }
-            if !self.try_consume(TokenType::COMMA, None::<fn(&Token)>) {
+            if !self.try_consume(TokenType::COMMA, None::<fn(&Token)>)? {
                 debug!("STATE_IDENTIFIER_LIST");
                 break;
             }
         }
 
         if self.tree.initial_state.is_empty() {
-            self.abort("Initial state not defined.");
+            return Err(self.abort("Initial state not defined."));
         }
 
         // If state identifiers have duplicates, abort with an error message
-        state_identifiers.iter().for_each(|state_identifier| {
-            if self.tree.states.contains(state_identifier) {
-                self.abort(&format!("State {} already defined.", state_identifier));
+        for (state_identifier, span) in state_identifiers {
+            if self.tree.states.contains(&state_identifier) {
+                return Err(self.abort_at(
+                    span,
+                    &format!("State {} already defined.", state_identifier),
+                    None,
+                ));
             } else {
-                self.tree.states.push(state_identifier.clone());
+                self.tree.states.push(state_identifier);
             }
-        });
+        }
+        Ok(())
     }
 
     // Parse a states declaration: STATES ':' state_identifier_list NEWLINE
-    fn states_declaration(&mut self) {
-        self.consume(TokenType::STATES, None::<fn(&Token)>);
-        self.consume(TokenType::COLON, None::<fn(&Token)>);
-        self.state_identifier_list();
-        self.consume(TokenType::NEWLINE, None::<fn(&Token)>);
+    fn states_declaration(&mut self) -> Result<(), Diagnostic> {
+        self.consume(TokenType::STATES, None::<fn(&Token)>)?;
+        self.consume(TokenType::COLON, None::<fn(&Token)>)?;
+        self.state_identifier_list()?;
+        self.consume(TokenType::NEWLINE, None::<fn(&Token)>)?;
         debug!("STATES_DECLARATION");
+        Ok(())
     }
 
     // Parse a list of symbol identifiers: IDENT (',' IDENT)*
-    fn symbol_identifiers(&mut self) {
+    fn symbol_identifiers(&mut self) -> Result<(), Diagnostic> {
         let mut symbol_identifiers = Vec::new();
 
+        let span = self.cur_span;
         self.consume(
             TokenType::IDENT,
             Some(|token: &Token| {
-                symbol_identifiers.push(token.text.clone());
+                symbol_identifiers.push((token.text.clone(), span));
             }),
-        );
+        )?;
 
-        while self.try_consume(TokenType::COMMA, None::<fn(&Token)>) {
+        while self.try_consume(TokenType::COMMA, None::<fn(&Token)>)? {
+            let span = self.cur_span;
             self.consume(
                 TokenType::IDENT,
                 Some(|token: &Token| {
-                    symbol_identifiers.push(token.text.clone());
+                    symbol_identifiers.push((token.text.clone(), span));
                 }),
-            );
+            )?;
         }
-        symbol_identifiers.iter().for_each(|symbol_identifier| {
-            if self.tree.symbols.contains(symbol_identifier) {
-                self.abort(&format!("Symbol {} already defined.", symbol_identifier));
+        for (symbol_identifier, span) in symbol_identifiers {
+            if self.tree.symbols.contains(&symbol_identifier) {
+                return Err(self.abort_at(
+                    span,
+                    &format!("Symbol {} already defined.", symbol_identifier),
+                    None,
+                ));
             } else {
-                self.tree.symbols.push(symbol_identifier.clone());
+                self.tree.symbols.push(symbol_identifier);
             }
-        });
+        }
 
         // X is a special symbol
         self.tree.symbols.push("X".to_string());
         debug!("SYMBOL_IDENTIFIERS");
+        Ok(())
     }
 
     // Parse a symbols declaration: SYMBOLS ':' symbol_identifiers NEWLINE
-    fn symbols_declaration(&mut self) {
-        self.consume(TokenType::SYMBOLS, None::<fn(&Token)>);
-        self.consume(TokenType::COLON, None::<fn(&Token)>);
-        self.symbol_identifiers();
-        self.consume(TokenType::NEWLINE, None::<fn(&Token)>);
+    fn symbols_declaration(&mut self) -> Result<(), Diagnostic> {
+        self.consume(TokenType::SYMBOLS, None::<fn(&Token)>)?;
+        self.consume(TokenType::COLON, None::<fn(&Token)>)?;
+        self.symbol_identifiers()?;
+        self.consume(TokenType::NEWLINE, None::<fn(&Token)>)?;
         debug!("SYMBOLS_DECLARATION");
+        Ok(())
     }
 
     // Parse a transition step: R | L | P '(' IDENT ')' | X
-    fn transition_step(&mut self) {
+    fn transition_step(&mut self) -> Result<(), Diagnostic> {
         // By default, do nothing
         let mut step: TransitionStep = TransitionStep::X;
-        if self.try_consume(
-            TokenType::R,
-            Some(|token: &Token| {
-                step = FromTokenAndValue::from_token_and_value(&token.clone(), None);
-            }),
-        ) {
-        } else if self.try_consume(
-            TokenType::L,
-            Some(|token: &Token| {
-                step = FromTokenAndValue::from_token_and_value(&token.clone(), None);
-            }),
-        ) {
-        } else if self.try_consume(
-            TokenType::X,
-            Some(|token: &Token| {
-                step = FromTokenAndValue::from_token_and_value(&token.clone(), None);
-            }),
-        ) {
-        } else if self.try_consume(TokenType::P, None::<fn(&Token)>) {
-            self.consume(TokenType::LeftParen, None::<fn(&Token)>);
-            let mut print_string = String::new();
-            // Either X or a symbol identifier
-            if self.try_consume(
-                TokenType::X,
-                Some(|token: &Token| {
-                    print_string.push_str(&token.text);
-                }),
-            ) {
-            } else {
+        match self.cur_token.kind {
+            kind @ (TokenType::R | TokenType::L | TokenType::X) => {
                 self.consume(
-                    TokenType::IDENT,
-                    Some(|step: &Token| {
-                        print_string.push_str(&step.text);
+                    kind,
+                    Some(|token: &Token| {
+                        step = FromTokenAndValue::from_token_and_value(&token.clone(), None);
                     }),
-                )
-            };
-
-            if !self.tree.symbols.contains(&print_string) {
-                self.abort(&format!(
-                    "Symbol {} not defined, So cannot be printed.",
-                    print_string
-                ));
+                )?;
             }
-            step = FromTokenAndValue::from_token_and_value(
-                &Token {
-                    text: "P".to_string(),
-                    kind: TokenType::P,
-                },
-                Some(print_string),
-            );
+            TokenType::P => {
+                self.consume(TokenType::P, None::<fn(&Token)>)?;
+                self.print_step(&mut step)?;
+            }
+            _ => {
+                return Err(self.abort(&format!(
+                    "Expected {:?} or {:?} or {:?} or {:?} as an action step, got {:?}: {:?}",
+                    TokenType::R,
+                    TokenType::L,
+                    TokenType::P,
+                    TokenType::X,
+                    self.cur_token.kind,
+                    self.cur_token.text
+                )));
+            }
+        }
+        self.tree.transitions.last_mut().unwrap().steps.push(step);
+        Ok(())
+    }
 
-            self.consume(TokenType::RightParen, None::<fn(&Token)>);
-        } else {
-            self.abort(&format!(
-                "Expected {:?} or {:?} or {:?} or {:?} as an action step, got {:?}: {:?}",
-                TokenType::R,
-                TokenType::L,
-                TokenType::P,
-                TokenType::X,
-                self.cur_token.kind,
-                self.cur_token.text
+    // Parse the argument of a print step: '(' (X | IDENT) ')'
+    fn print_step(&mut self, step: &mut TransitionStep) -> Result<(), Diagnostic> {
+        self.consume(TokenType::LeftParen, None::<fn(&Token)>)?;
+        let span = self.cur_span;
+        let mut print_string = String::new();
+        self.condition_symbol(|symbol| print_string.push_str(symbol))?;
+
+        if !self.tree.symbols.contains(&print_string) {
+            let help = closest_match(&print_string, &self.tree.symbols)
+                .map(|s| format!("did you mean symbol `{}`?", s));
+            return Err(self.abort_at(
+                span,
+                &format!("Symbol {} not defined, So cannot be printed.", print_string),
+                help,
             ));
         }
-        self.tree.transitions.last_mut().unwrap().steps.push(step);
+        *step = FromTokenAndValue::from_token_and_value(
+            &Token {
+                text: "P".to_string(),
+                kind: TokenType::P,
+            },
+            Some(print_string),
+        );
+
+        self.consume(TokenType::RightParen, None::<fn(&Token)>)?;
+        Ok(())
     }
 
-    fn transition_steps(&mut self) {
-        self.transition_step();
-        while self.try_consume(TokenType::DASH, None::<fn(&Token)>) {
-            self.transition_step();
+    // Parse a symbol of a condition or a print step, X included: X | IDENT
+    fn condition_symbol<F>(&mut self, mut action: F) -> Result<(), Diagnostic>
+    where
+        F: FnMut(&str),
+    {
+        let kind = match self.cur_token.kind {
+            TokenType::X => TokenType::X,
+            _ => TokenType::IDENT,
+        };
+        self.consume(kind, Some(|token: &Token| action(&token.text)))
+    }
+
+    fn transition_steps(&mut self) -> Result<(), Diagnostic> {
+        self.transition_step()?;
+        while self.try_consume(TokenType::DASH, None::<fn(&Token)>)? {
+            self.transition_step()?;
         }
         debug!("TRANSITION_STEPS");
+        Ok(())
     }
 
     // Parse a list of transition conditions: IDENT ('|' IDENT)*
-    fn transition_condition_list(&mut self) {
+    fn transition_condition_list(&mut self) -> Result<(), Diagnostic> {
         let mut conditions: Vec<String> = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
 
         // Consume X as well
-        if self.try_consume(
-            TokenType::X,
-            Some(|token: &Token| {
-                conditions.push(token.text.clone());
-            }),
-        ) {
-        } else {
-            self.consume(
-                TokenType::IDENT,
-                Some(|token: &Token| {
-                    conditions.push(token.text.clone());
-                }),
-            );
-        }
+        spans.push(self.cur_span);
+        self.condition_symbol(|symbol| conditions.push(symbol.to_string()))?;
 
-        while self.try_consume(TokenType::OR, None::<fn(&Token)>) {
+        while self.try_consume(TokenType::OR, None::<fn(&Token)>)? {
             // Consume X as well
-            if self.try_consume(
-                TokenType::X,
-                Some(|token: &Token| {
-                    conditions.push(token.text.clone());
-                }),
-            ) {
-            } else {
-                self.consume(
-                    TokenType::IDENT,
-                    Some(|token: &Token| {
-                        conditions.push(token.text.clone());
-                    }),
-                );
+            spans.push(self.cur_span);
+            self.condition_symbol(|symbol| conditions.push(symbol.to_string()))?;
+        }
+        for (symbol, span) in conditions.iter().zip(spans) {
+            if !self.tree.symbols.contains(symbol) {
+                let help = closest_match(symbol, &self.tree.symbols)
+                    .map(|s| format!("did you mean symbol `{}`?", s));
+                self.warn_at(span, &format!("Symbol {} is not declared.", symbol), help);
             }
         }
         self.tree.transitions.last_mut().unwrap().condition = Condition::OR(conditions);
         debug!("TRANSITION_CONDITION_LIST");
+        Ok(())
     }
 
     // Parse transition conditions: '*' | transition_condition_list
-    fn transition_conditions(&mut self) {
+    fn transition_conditions(&mut self) -> Result<(), Diagnostic> {
         let mut star_condition = false;
         if !self.try_consume(
             TokenType::STAR,
             Some(|_token: &Token| {
                 star_condition = true;
             }),
-        ) {
-            self.transition_condition_list();
+        )? {
+            self.transition_condition_list()?;
         }
 
         // Override all other conditions with the star condition
@@ -649,74 +1452,94 @@ impl Parser {
             self.tree.transitions.last_mut().unwrap().condition = Condition::Star;
         }
         debug!("TRANSITION_CONDITIONS");
+        Ok(())
     }
 
     // Parse a transition declaration:
     // IDENT ',' transition_conditions ',' transition_steps ',' IDENT
-    fn transition_declaration(&mut self) {
+    fn transition_declaration(&mut self) -> Result<(), Diagnostic> {
         // Initialize a new transition
         self.tree.transitions.push(Transition::new());
 
         // Initial state
+        let span = self.cur_span;
+        self.tree.transitions.last_mut().unwrap().line = span.line;
         let mut initial_state = String::new();
         self.consume(
             TokenType::IDENT,
             Some(|token: &Token| {
                 initial_state.push_str(&token.text);
             }),
-        );
+        )?;
+        self.check_state_declared(&initial_state, span);
         self.tree.transitions.last_mut().unwrap().initial_state = initial_state;
 
         debug!("INITIAL_STATE_IDENTIFIER");
-        self.consume(TokenType::COMMA, None::<fn(&Token)>);
+        self.consume(TokenType::COMMA, None::<fn(&Token)>)?;
 
         // Conditions
-        self.transition_conditions();
-        self.consume(TokenType::COMMA, None::<fn(&Token)>);
+        self.transition_conditions()?;
+        self.consume(TokenType::COMMA, None::<fn(&Token)>)?;
 
         // Actions
-        self.transition_steps();
-        self.consume(TokenType::COMMA, None::<fn(&Token)>);
+        self.transition_steps()?;
+        self.consume(TokenType::COMMA, None::<fn(&Token)>)?;
 
         // Final state
+        let span = self.cur_span;
         let mut final_state = String::new();
         self.consume(
             TokenType::IDENT,
             Some(|token: &Token| {
                 final_state.push_str(&token.text);
             }),
-        );
+        )?;
+        self.check_state_declared(&final_state, span);
         self.tree.transitions.last_mut().unwrap().final_state = final_state;
         debug!("FINAL_STATE_IDENTIFIER");
         debug!("TRANSITION_DECLARATION");
+        Ok(())
     }
 
     // Parse transitions declarations:
     // TRANSITIONS ':' (NEWLINE transition_declaration)*
-    fn transitions_declaration(&mut self) {
-        self.consume(TokenType::TRANSITIONS, None::<fn(&Token)>);
-        self.consume(TokenType::COLON, None::<fn(&Token)>);
+    fn transitions_declaration(&mut self) -> Result<(), Diagnostic> {
+        self.consume(TokenType::TRANSITIONS, None::<fn(&Token)>)?;
+        self.consume(TokenType::COLON, None::<fn(&Token)>)?;
 
-        while self.try_consume(TokenType::NEWLINE, None::<fn(&Token)>) {
+        while self.try_consume(TokenType::NEWLINE, None::<fn(&Token)>)? {
             if self.check_token(TokenType::EOF) {
                 break;
             }
-            self.transition_declaration();
+            self.transition_declaration()?;
         }
         debug!("TRANSITION_DECLARATIONS");
+        Ok(())
+    }
+
+    // Parse the entire program, panicking on the first error
+    pub fn program(&mut self) {
+        if let Err(diagnostic) = self.try_program() {
+            panic!("Parsing error: {}", diagnostic.message);
+        }
     }
 
     // Parse the entire program:
     // NEWLINE? states_declaration symbols_declaration transitions_declaration NEWLINE? EOF
-    pub fn program(&mut self) {
+    // The first error is returned, after being reported like the warnings
+    pub fn try_program(&mut self) -> Result<(), Diagnostic> {
+        self.next_token()?; // Initialize peek_token
+        self.next_token()?; // Initialize cur_token
+
         // Consume newlines
-        while self.try_consume(TokenType::NEWLINE, None::<fn(&Token)>) {}
-        self.states_declaration();
-        self.symbols_declaration();
-        self.transitions_declaration();
+        while self.try_consume(TokenType::NEWLINE, None::<fn(&Token)>)? {}
+        self.states_declaration()?;
+        self.symbols_declaration()?;
+        self.transitions_declaration()?;
         // Consume newlines
-        while self.try_consume(TokenType::NEWLINE, None::<fn(&Token)>) {}
-        self.consume(TokenType::EOF, None::<fn(&Token)>);
+        while self.try_consume(TokenType::NEWLINE, None::<fn(&Token)>)? {}
+        self.consume(TokenType::EOF, None::<fn(&Token)>)?;
         debug!("PROGRAM");
+        Ok(())
     }
 }
@@ -0,0 +1,124 @@
+use crate::escape::{quote, quote_list};
+use crate::parser::{Condition, ParseTree, TransitionStep};
+
+// Trait for converting to a self-contained Python module with the same
+// `TapeMachine` API as `ParseTree::to_rust_library`:
+//
+//     machine = TapeMachine("0110")
+//     halt = machine.run(1000)
+//     print(halt, machine.state, machine.tape_string())
+pub trait ToPython {
+    fn to_python(&self) -> String;
+}
+
+impl ToPython for ParseTree {
+    fn to_python(&self) -> String {
+        let mut code = String::new();
+        code.push_str("# Generated by state_machine_compiler_rust, do not edit\n\n");
+        code.push_str("import enum\n\n");
+        code.push_str(&format!("STATES = ({},)\n", quote_list(&self.states)));
+        code.push_str(&format!("SYMBOLS = ({},)\n", quote_list(&self.symbols)));
+        code.push_str("BLANK = \"X\"\n\n\n");
+
+        code.push_str("class Halt(enum.Enum):\n");
+        code.push_str("    \"\"\"Why the machine stopped\"\"\"\n\n");
+        code.push_str("    # No transition for the current state and symbol\n");
+        code.push_str("    NO_TRANSITION = \"NoTransition\"\n");
+        code.push_str("    # The transition would move the head left of the first cell\n");
+        code.push_str("    LEFT_EDGE = \"LeftEdge\"\n\n\n");
+
+        code.push_str("class TapeMachine:\n");
+        code.push_str("    def __init__(self, tape=()):\n");
+        code.push_str("        # A string of one character symbols, or any iterable of symbols\n");
+        code.push_str("        self._tape = list(tape) or [BLANK]\n");
+        code.push_str("        for symbol in self._tape:\n");
+        code.push_str("            if symbol not in SYMBOLS:\n");
+        code.push_str("                raise ValueError(f\"Unknown symbol: {symbol}\")\n");
+        code.push_str(&format!(
+            "        self._state = {}\n",
+            quote(&self.initial_state)
+        ));
+        code.push_str("        self._head = 0\n");
+        code.push_str("        self._steps = 0\n\n");
+
+        code.push_str("    @property\n");
+        code.push_str("    def state(self):\n");
+        code.push_str("        return self._state\n\n");
+        code.push_str("    @property\n");
+        code.push_str("    def tape(self):\n");
+        code.push_str("        return list(self._tape)\n\n");
+        code.push_str("    @property\n");
+        code.push_str("    def head(self):\n");
+        code.push_str("        return self._head\n\n");
+        code.push_str("    @property\n");
+        code.push_str("    def steps(self):\n");
+        code.push_str("        return self._steps\n\n");
+        code.push_str("    def tape_string(self):\n");
+        code.push_str("        return \"\".join(self._tape)\n\n");
+
+        code.push_str("    def _p(self, symbol):\n");
+        code.push_str("        self._tape[self._head] = symbol\n\n");
+        code.push_str("    def _r(self):\n");
+        code.push_str("        self._head += 1\n");
+        code.push_str("        if self._head == len(self._tape):\n");
+        code.push_str("            self._tape.append(BLANK)\n\n");
+        code.push_str("    def _l(self):\n");
+        code.push_str("        self._head -= 1\n\n");
+
+        code.push_str("    def step(self):\n");
+        code.push_str("        \"\"\"Apply one transition and return None, or the Halt and change nothing\"\"\"\n");
+        code.push_str("        state, symbol = self._state, self._tape[self._head]\n");
+        let sorted_transitions = self.transitions_star_last();
+        for (index, transition) in sorted_transitions.iter().enumerate() {
+            let condition = match &transition.condition {
+                Condition::OR(symbols) => {
+                    format!(" and symbol in ({},)", quote_list(symbols))
+                }
+                Condition::Star => String::new(),
+            };
+            code.push_str(&format!(
+                "        {} state == {}{}:\n",
+                if index == 0 { "if" } else { "elif" },
+                quote(&transition.initial_state),
+                condition
+            ));
+            let left_reach = transition.left_reach();
+            if left_reach > 0 {
+                code.push_str(&format!("            if self._head < {}:\n", left_reach));
+                code.push_str("                return Halt.LEFT_EDGE\n");
+            }
+            for step in &transition.steps {
+                match step {
+                    TransitionStep::R => code.push_str("            self._r()\n"),
+                    TransitionStep::L => code.push_str("            self._l()\n"),
+                    TransitionStep::X => {}
+                    TransitionStep::P(symbol) => {
+                        code.push_str(&format!("            self._p({})\n", quote(symbol)))
+                    }
+                }
+            }
+            code.push_str(&format!(
+                "            next_state = {}\n",
+                quote(&transition.final_state)
+            ));
+        }
+        if sorted_transitions.is_empty() {
+            code.push_str("        return Halt.NO_TRANSITION\n\n");
+        } else {
+            code.push_str("        else:\n");
+            code.push_str("            return Halt.NO_TRANSITION\n");
+            code.push_str("        self._state = next_state\n");
+            code.push_str("        self._steps += 1\n");
+            code.push_str("        return None\n\n");
+        }
+
+        code.push_str("    def run(self, max_steps):\n");
+        code.push_str("        \"\"\"Run until the machine halts or max_steps steps have been applied\"\"\"\n");
+        code.push_str("        for _ in range(max_steps):\n");
+        code.push_str("            halt = self.step()\n");
+        code.push_str("            if halt is not None:\n");
+        code.push_str("                return halt\n");
+        code.push_str("        return None\n");
+        code
+    }
+}
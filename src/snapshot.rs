@@ -0,0 +1,93 @@
+use crate::interpreter::Configuration;
+use crate::parser::ParseTree;
+
+// A configuration saved to a file, one `key: value` line per field with the
+// state and symbols by name. The generated Rust `TapeMachine` reads and
+// writes the same format.
+//
+//     state: q
+//     head: 3
+//     steps: 1000
+//     tape: e,e,0,X,1
+pub fn to_text(tree: &ParseTree, configuration: &Configuration) -> String {
+    let tape = configuration
+        .tape
+        .iter()
+        .map(|&symbol| tree.symbols[symbol].as_str())
+        .collect::<Vec<&str>>();
+    format!(
+        "state: {}\nhead: {}\nsteps: {}\ntape: {}\n",
+        tree.states[configuration.state],
+        configuration.head,
+        configuration.steps,
+        tape.join(",")
+    )
+}
+
+// Read a configuration written by `to_text`. Empty lines and lines starting
+// with `#` are skipped.
+pub fn from_text(tree: &ParseTree, text: &str) -> Result<Configuration, String> {
+    let mut state = None;
+    let mut head = None;
+    let mut steps = None;
+    let mut tape = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(':')
+            .ok_or(format!("Invalid line: {}", line))?;
+        let value = value.trim();
+        match key.trim() {
+            "state" => {
+                state = Some(
+                    tree.states
+                        .iter()
+                        .position(|s| s == value)
+                        .ok_or(format!("Unknown state: {}", value))?,
+                )
+            }
+            "head" => {
+                head = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|e| format!("Invalid head: {}", e))?,
+                )
+            }
+            "steps" => {
+                steps = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|e| format!("Invalid steps: {}", e))?,
+                )
+            }
+            "tape" => {
+                tape = Some(
+                    value
+                        .split(',')
+                        .map(|name| {
+                            let name = name.trim();
+                            tree.symbols
+                                .iter()
+                                .position(|s| s == name)
+                                .ok_or(format!("Unknown symbol: {}", name))
+                        })
+                        .collect::<Result<Vec<usize>, String>>()?,
+                )
+            }
+            key => return Err(format!("Unknown key: {}", key)),
+        }
+    }
+    let configuration = Configuration {
+        state: state.ok_or("Missing state")?,
+        head: head.ok_or("Missing head")?,
+        tape: tape.ok_or("Missing tape")?,
+        steps: steps.ok_or("Missing steps")?,
+    };
+    if configuration.head >= configuration.tape.len() {
+        return Err("The head is past the end of the tape".to_string());
+    }
+    Ok(configuration)
+}
@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+use crate::escape::xml_escape;
+use crate::interpreter::{Configuration, Machine, StepResult};
+use crate::parser::ParseTree;
+
+// Size of the SVG cells, in pixels
+const ROW_HEIGHT: usize = 24;
+const CHAR_WIDTH: usize = 9;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DiagramFormat {
+    Text,
+    Svg,
+}
+
+impl FromStr for DiagramFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(DiagramFormat::Text),
+            "svg" => Ok(DiagramFormat::Svg),
+            _ => Err(format!("Unknown diagram format: {}", s)),
+        }
+    }
+}
+
+// Configurations of a run, one per step starting with the initial one
+pub fn record_run(machine: &mut Machine, max_steps: usize) -> Vec<Configuration> {
+    let mut rows = vec![machine.configuration()];
+    for _ in 0..max_steps {
+        if let StepResult::Halted(_) = machine.step() {
+            break;
+        }
+        rows.push(machine.configuration());
+    }
+    rows
+}
+
+// Space-time diagram: one row per step with the state and the tape, the
+// cell under the head between brackets. Every row is padded with blanks to
+// the longest tape of the run.
+pub fn to_text(tree: &ParseTree, rows: &[Configuration]) -> String {
+    let blank = blank_index(tree);
+    let columns = columns(rows);
+    let cell_width = symbol_width(tree);
+    let step_width = step_width(rows);
+    let state_width = state_width(tree, rows);
+
+    let mut text = String::new();
+    for row in rows {
+        let mut line = format!(
+            "{:>step_width$}  {:<state_width$} ",
+            row.steps, tree.states[row.state]
+        );
+        for cell in 0..columns {
+            let symbol = &tree.symbols[row.tape.get(cell).copied().unwrap_or(blank)];
+            if cell == row.head {
+                line.push_str(&format!("[{:^cell_width$}]", symbol));
+            } else {
+                line.push_str(&format!(" {:^cell_width$} ", symbol));
+            }
+        }
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+// Same diagram as `to_text`, with the head cell highlighted
+pub fn to_svg(tree: &ParseTree, rows: &[Configuration]) -> String {
+    let blank = blank_index(tree);
+    let columns = columns(rows);
+    let cell_width = CHAR_WIDTH * symbol_width(tree) + 12;
+    let label_width = CHAR_WIDTH * (step_width(rows) + state_width(tree, rows) + 2) + 12;
+    let width = label_width + columns * cell_width;
+    let height = rows.len() * ROW_HEIGHT;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"14\">\n",
+        width, height
+    );
+    svg.push_str(&format!(
+        "  <rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n",
+        width, height
+    ));
+    for (i, row) in rows.iter().enumerate() {
+        let y = i * ROW_HEIGHT;
+        let text_y = y + ROW_HEIGHT - 7;
+        svg.push_str(&format!(
+            "  <text x=\"6\" y=\"{}\">{} {}</text>\n",
+            text_y,
+            row.steps,
+            xml_escape(&tree.states[row.state])
+        ));
+        for cell in 0..columns {
+            let x = label_width + cell * cell_width;
+            let symbol = &tree.symbols[row.tape.get(cell).copied().unwrap_or(blank)];
+            let fill = if cell == row.head { "#ffd54f" } else { "white" };
+            svg.push_str(&format!(
+                "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#999999\"/>\n",
+                x, y, cell_width, ROW_HEIGHT, fill
+            ));
+            svg.push_str(&format!(
+                "  <text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+                x + cell_width / 2,
+                text_y,
+                xml_escape(symbol)
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn blank_index(tree: &ParseTree) -> usize {
+    tree.symbols.iter().position(|s| s == "X").unwrap()
+}
+
+// Number of cells shown on every row
+fn columns(rows: &[Configuration]) -> usize {
+    rows.iter()
+        .map(|row| row.tape.len().max(row.head + 1))
+        .max()
+        .unwrap_or(0)
+}
+
+fn symbol_width(tree: &ParseTree) -> usize {
+    tree.symbols
+        .iter()
+        .map(|s| s.chars().count())
+        .max()
+        .unwrap_or(1)
+}
+
+fn step_width(rows: &[Configuration]) -> usize {
+    rows.iter()
+        .map(|row| row.steps.to_string().len())
+        .max()
+        .unwrap_or(1)
+}
+
+fn state_width(tree: &ParseTree, rows: &[Configuration]) -> usize {
+    rows.iter()
+        .map(|row| tree.states[row.state].chars().count())
+        .max()
+        .unwrap_or(1)
+}
@@ -0,0 +1,87 @@
+use std::str::FromStr;
+
+// Name of the environment variables read by the generated programs to
+// override the trace settings chosen at compile time
+pub const TRACE_ENV_VAR: &str = "TAPE_MACHINE_TRACE";
+pub const TRACE_FORMAT_ENV_VAR: &str = "TAPE_MACHINE_TRACE_FORMAT";
+
+// How much of every step is printed by the generated programs
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum TraceLevel {
+    // Nothing is printed while running
+    None = 0,
+    // State and symbol of every step
+    Transitions = 1,
+    // Transitions along with the whole tape and a marker under the head
+    Full = 2,
+}
+
+impl TraceLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TraceLevel::None => "none",
+            TraceLevel::Transitions => "transitions",
+            TraceLevel::Full => "full",
+        }
+    }
+}
+
+impl FromStr for TraceLevel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(TraceLevel::None),
+            "transitions" => Ok(TraceLevel::Transitions),
+            "full" => Ok(TraceLevel::Full),
+            _ => Err(format!("Unknown trace level: {}", s)),
+        }
+    }
+}
+
+// Human readable lines or one tab separated line per step:
+//
+// <step>\t<state>\t<head>\t<symbol>[\t<tape>]
+//
+// The tape column is only present for TraceLevel::Full
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TraceFormat {
+    Human = 0,
+    Compact = 1,
+}
+
+impl TraceFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TraceFormat::Human => "human",
+            TraceFormat::Compact => "compact",
+        }
+    }
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(TraceFormat::Human),
+            "compact" => Ok(TraceFormat::Compact),
+            _ => Err(format!("Unknown trace format: {}", s)),
+        }
+    }
+}
+
+// Trace settings baked into the generated code, the runtime environment
+// variables take precedence over them
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TraceOptions {
+    pub level: TraceLevel,
+    pub format: TraceFormat,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        TraceOptions {
+            level: TraceLevel::Transitions,
+            format: TraceFormat::Human,
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use crate::parser::{Condition, ParseTree, TransitionStep};
+
+// Values returned by the exported `step` function, same as the C backend
+pub const WASM_OK: i32 = 0;
+pub const WASM_NO_TRANSITION: i32 = 1;
+pub const WASM_OUT_OF_TAPE: i32 = 2;
+
+// Indices of the globals and of the locals of `step`
+const STATE: u32 = 0;
+const TAPE_PTR: u32 = 1;
+const TAPE_LEN: u32 = 2;
+const HEAD: u32 = 3;
+const SYMBOL: u32 = 0;
+
+// The few instructions the machines need, rendered both as WAT and as the
+// binary format so no assembler is needed to load the module
+#[derive(Debug, PartialEq, Clone)]
+enum Instr {
+    // Only in the WAT output
+    Comment(String),
+    Block,
+    If,
+    End,
+    Br(u32),
+    Return,
+    LocalGet(u32),
+    LocalSet(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    I32Load8U,
+    I32Store8,
+    I32Const(i32),
+    I32Eq,
+    I32GeU,
+    I32Add,
+    I32Sub,
+    I32Or,
+}
+
+impl Instr {
+    fn wat(&self) -> String {
+        match self {
+            Instr::Comment(comment) => format!(";; {}", comment),
+            Instr::Block => "block".to_string(),
+            Instr::If => "if".to_string(),
+            Instr::End => "end".to_string(),
+            Instr::Br(depth) => format!("br {}", depth),
+            Instr::Return => "return".to_string(),
+            Instr::LocalGet(index) => format!("local.get {}", index),
+            Instr::LocalSet(index) => format!("local.set {}", index),
+            Instr::GlobalGet(index) => format!("global.get {}", index),
+            Instr::GlobalSet(index) => format!("global.set {}", index),
+            Instr::I32Load8U => "i32.load8_u".to_string(),
+            Instr::I32Store8 => "i32.store8".to_string(),
+            Instr::I32Const(value) => format!("i32.const {}", value),
+            Instr::I32Eq => "i32.eq".to_string(),
+            Instr::I32GeU => "i32.ge_u".to_string(),
+            Instr::I32Add => "i32.add".to_string(),
+            Instr::I32Sub => "i32.sub".to_string(),
+            Instr::I32Or => "i32.or".to_string(),
+        }
+    }
+
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Instr::Comment(_) => {}
+            // Blocks without any result
+            Instr::Block => bytes.extend([0x02, 0x40]),
+            Instr::If => bytes.extend([0x04, 0x40]),
+            Instr::End => bytes.push(0x0b),
+            Instr::Br(depth) => {
+                bytes.push(0x0c);
+                unsigned_leb128(*depth, bytes);
+            }
+            Instr::Return => bytes.push(0x0f),
+            Instr::LocalGet(index) => {
+                bytes.push(0x20);
+                unsigned_leb128(*index, bytes);
+            }
+            Instr::LocalSet(index) => {
+                bytes.push(0x21);
+                unsigned_leb128(*index, bytes);
+            }
+            Instr::GlobalGet(index) => {
+                bytes.push(0x23);
+                unsigned_leb128(*index, bytes);
+            }
+            Instr::GlobalSet(index) => {
+                bytes.push(0x24);
+                unsigned_leb128(*index, bytes);
+            }
+            // Alignment and offset of the memory access
+            Instr::I32Load8U => bytes.extend([0x2d, 0x00, 0x00]),
+            Instr::I32Store8 => bytes.extend([0x3a, 0x00, 0x00]),
+            Instr::I32Const(value) => {
+                bytes.push(0x41);
+                signed_leb128(*value, bytes);
+            }
+            Instr::I32Eq => bytes.push(0x46),
+            Instr::I32GeU => bytes.push(0x4f),
+            Instr::I32Add => bytes.push(0x6a),
+            Instr::I32Sub => bytes.push(0x6b),
+            Instr::I32Or => bytes.push(0x72),
+        }
+    }
+}
+
+struct Function {
+    name: &'static str,
+    params: Vec<&'static str>,
+    result: bool,
+    locals: Vec<&'static str>,
+    body: Vec<Instr>,
+}
+
+// Trait for converting to a WebAssembly module without any import, to drive
+// the machine from a browser:
+//
+//     const { instance } = await WebAssembly.instantiate(bytes);
+//     const { memory, init, step, state, head } = instance.exports;
+//     new Uint8Array(memory.buffer, 0, 100).fill(blank);
+//     init(0, 100);
+//     while (step() === 0) {}
+//
+// Every tape cell is one byte holding the index of its symbol, in the order
+// of the SYMBOLS line with X last. `state()` returns the index of the state.
+pub trait ToWasm {
+    fn to_wat(&self) -> String;
+    fn to_wasm(&self) -> Vec<u8>;
+}
+
+impl ToWasm for ParseTree {
+    fn to_wat(&self) -> String {
+        let mut wat = String::from("(module\n");
+        wat.push_str(&format!(
+            "  ;; states: {}\n  ;; symbols: {}\n",
+            self.states.join(", "),
+            self.symbols.join(", ")
+        ));
+        wat.push_str("  (memory (export \"memory\") 1)\n");
+        for name in ["state", "tape_ptr", "tape_len", "head"] {
+            wat.push_str(&format!("  (global ${} (mut i32) (i32.const 0))\n", name));
+        }
+        for function in functions(self) {
+            wat.push_str(&format!("  (func (export \"{}\")", function.name));
+            for param in &function.params {
+                wat.push_str(&format!(" (param ${} i32)", param));
+            }
+            if function.result {
+                wat.push_str(" (result i32)");
+            }
+            wat.push('\n');
+            for local in &function.locals {
+                wat.push_str(&format!("    (local ${} i32)\n", local));
+            }
+            let mut depth = 2;
+            for instr in &function.body {
+                if *instr == Instr::End {
+                    depth -= 1;
+                }
+                wat.push_str(&format!("{}{}\n", "  ".repeat(depth), instr.wat()));
+                if matches!(instr, Instr::Block | Instr::If) {
+                    depth += 1;
+                }
+            }
+            wat.push_str("  )\n");
+        }
+        wat.push_str(")\n");
+        wat
+    }
+
+    fn to_wasm(&self) -> Vec<u8> {
+        let functions = functions(self);
+        let mut wasm = b"\0asm".to_vec();
+        wasm.extend([0x01, 0x00, 0x00, 0x00]);
+
+        // Every function signature, only i32 are used
+        let mut types: Vec<(usize, bool)> = Vec::new();
+        for function in &functions {
+            let signature = (function.params.len(), function.result);
+            if !types.contains(&signature) {
+                types.push(signature);
+            }
+        }
+        let mut section = Vec::new();
+        unsigned_leb128(types.len() as u32, &mut section);
+        for (params, result) in &types {
+            section.push(0x60);
+            unsigned_leb128(*params as u32, &mut section);
+            section.extend(vec![0x7f; *params]);
+            unsigned_leb128(*result as u32, &mut section);
+            if *result {
+                section.push(0x7f);
+            }
+        }
+        push_section(1, &section, &mut wasm);
+
+        let mut section = Vec::new();
+        unsigned_leb128(functions.len() as u32, &mut section);
+        for function in &functions {
+            let signature = (function.params.len(), function.result);
+            let index = types.iter().position(|t| *t == signature).unwrap();
+            unsigned_leb128(index as u32, &mut section);
+        }
+        push_section(3, &section, &mut wasm);
+
+        // One page of memory at least, the host may grow it for longer tapes
+        push_section(5, &[0x01, 0x00, 0x01], &mut wasm);
+
+        let mut section = vec![0x04];
+        for _ in 0..4 {
+            section.extend([0x7f, 0x01]);
+            Instr::I32Const(0).encode(&mut section);
+            Instr::End.encode(&mut section);
+        }
+        push_section(6, &section, &mut wasm);
+
+        let mut section = Vec::new();
+        unsigned_leb128(functions.len() as u32 + 1, &mut section);
+        push_name("memory", &mut section);
+        section.extend([0x02, 0x00]);
+        for (index, function) in functions.iter().enumerate() {
+            push_name(function.name, &mut section);
+            section.push(0x00);
+            unsigned_leb128(index as u32, &mut section);
+        }
+        push_section(7, &section, &mut wasm);
+
+        let mut section = Vec::new();
+        unsigned_leb128(functions.len() as u32, &mut section);
+        for function in &functions {
+            let mut body = Vec::new();
+            if function.locals.is_empty() {
+                body.push(0x00);
+            } else {
+                body.push(0x01);
+                unsigned_leb128(function.locals.len() as u32, &mut body);
+                body.push(0x7f);
+            }
+            for instr in &function.body {
+                instr.encode(&mut body);
+            }
+            Instr::End.encode(&mut body);
+            unsigned_leb128(body.len() as u32, &mut section);
+            section.extend(body);
+        }
+        push_section(10, &section, &mut wasm);
+        wasm
+    }
+}
+
+fn functions(tree: &ParseTree) -> Vec<Function> {
+    let initial_state = tree
+        .states
+        .iter()
+        .position(|s| s == &tree.initial_state)
+        .unwrap_or(0);
+    vec![
+        Function {
+            name: "init",
+            params: vec!["tape_ptr", "tape_len"],
+            result: false,
+            locals: Vec::new(),
+            body: vec![
+                Instr::LocalGet(0),
+                Instr::GlobalSet(TAPE_PTR),
+                Instr::LocalGet(1),
+                Instr::GlobalSet(TAPE_LEN),
+                Instr::I32Const(0),
+                Instr::GlobalSet(HEAD),
+                Instr::I32Const(initial_state as i32),
+                Instr::GlobalSet(STATE),
+            ],
+        },
+        Function {
+            name: "step",
+            params: Vec::new(),
+            result: true,
+            locals: vec!["symbol"],
+            body: step_body(tree),
+        },
+        Function {
+            name: "state",
+            params: Vec::new(),
+            result: true,
+            locals: Vec::new(),
+            body: vec![Instr::GlobalGet(STATE)],
+        },
+        Function {
+            name: "head",
+            params: Vec::new(),
+            result: true,
+            locals: Vec::new(),
+            body: vec![Instr::GlobalGet(HEAD)],
+        },
+    ]
+}
+
+// Apply one transition and return WASM_OK, or why the machine stopped
+fn step_body(tree: &ParseTree) -> Vec<Instr> {
+    let mut body = Vec::new();
+    out_of_tape_check(&mut body);
+    body.extend([
+        Instr::GlobalGet(TAPE_PTR),
+        Instr::GlobalGet(HEAD),
+        Instr::I32Add,
+        Instr::I32Load8U,
+        Instr::LocalSet(SYMBOL),
+    ]);
+
+    // Every state branches out of this block once its transition is applied
+    body.push(Instr::Block);
+    for (state_index, state) in tree.states.iter().enumerate() {
+        body.extend([
+            Instr::Comment(format!("state {}", state)),
+            Instr::GlobalGet(STATE),
+            Instr::I32Const(state_index as i32),
+            Instr::I32Eq,
+            Instr::If,
+        ]);
+
+        for (index, symbols) in tree.transitions_from(state) {
+            let transition = &tree.transitions[index];
+            body.push(Instr::Comment(transition.to_string()));
+            let star = transition.condition == Condition::Star;
+            if !star {
+                for (i, symbol) in symbols.iter().enumerate() {
+                    body.extend([
+                        Instr::LocalGet(SYMBOL),
+                        Instr::I32Const(*symbol as i32),
+                        Instr::I32Eq,
+                    ]);
+                    if i > 0 {
+                        body.push(Instr::I32Or);
+                    }
+                }
+                body.push(Instr::If);
+            }
+            for step in &transition.steps {
+                match step {
+                    TransitionStep::R => body.extend([
+                        Instr::GlobalGet(HEAD),
+                        Instr::I32Const(1),
+                        Instr::I32Add,
+                        Instr::GlobalSet(HEAD),
+                    ]),
+                    // Left of the first cell wraps around, past the end of the tape
+                    TransitionStep::L => body.extend([
+                        Instr::GlobalGet(HEAD),
+                        Instr::I32Const(1),
+                        Instr::I32Sub,
+                        Instr::GlobalSet(HEAD),
+                    ]),
+                    TransitionStep::X => {}
+                    TransitionStep::P(symbol) => {
+                        let symbol_index = tree.symbols.iter().position(|s| s == symbol).unwrap();
+                        out_of_tape_check(&mut body);
+                        body.extend([
+                            Instr::GlobalGet(TAPE_PTR),
+                            Instr::GlobalGet(HEAD),
+                            Instr::I32Add,
+                            Instr::I32Const(symbol_index as i32),
+                            Instr::I32Store8,
+                        ]);
+                    }
+                }
+            }
+            let final_state = tree
+                .states
+                .iter()
+                .position(|s| s == &transition.final_state)
+                .unwrap();
+            body.extend([Instr::I32Const(final_state as i32), Instr::GlobalSet(STATE)]);
+            if star {
+                body.push(Instr::Br(1));
+            } else {
+                body.extend([Instr::Br(2), Instr::End]);
+            }
+        }
+        body.extend([
+            Instr::I32Const(WASM_NO_TRANSITION),
+            Instr::Return,
+            Instr::End,
+        ]);
+    }
+    body.extend([
+        Instr::I32Const(WASM_NO_TRANSITION),
+        Instr::Return,
+        Instr::End,
+        Instr::I32Const(WASM_OK),
+    ]);
+    body
+}
+
+fn out_of_tape_check(body: &mut Vec<Instr>) {
+    body.extend([
+        Instr::GlobalGet(HEAD),
+        Instr::GlobalGet(TAPE_LEN),
+        Instr::I32GeU,
+        Instr::If,
+        Instr::I32Const(WASM_OUT_OF_TAPE),
+        Instr::Return,
+        Instr::End,
+    ]);
+}
+
+fn push_section(id: u8, content: &[u8], wasm: &mut Vec<u8>) {
+    wasm.push(id);
+    unsigned_leb128(content.len() as u32, wasm);
+    wasm.extend(content);
+}
+
+fn push_name(name: &str, bytes: &mut Vec<u8>) {
+    unsigned_leb128(name.len() as u32, bytes);
+    bytes.extend(name.as_bytes());
+}
+
+fn unsigned_leb128(mut value: u32, bytes: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn signed_leb128(mut value: i32, bytes: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
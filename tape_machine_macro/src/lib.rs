@@ -0,0 +1,164 @@
+use proc_macro::{Delimiter, Span, TokenStream, TokenTree};
+use std::path::PathBuf;
+
+use state_machine_compiler_rust::diagnostics::Diagnostic;
+use state_machine_compiler_rust::lexer::Lexer;
+use state_machine_compiler_rust::parser::Parser;
+
+// Compile a machine written inline, expands to the same code as
+// `ParseTree::to_rust_code`:
+//
+//     tape_machine! {
+//         STATES: [a], b
+//         SYMBOLS: 0, 1
+//         TRANSITIONS:
+//         a, 0, P(1)-R, b
+//     }
+//
+// Parse errors are reported on the offending token.
+#[proc_macro]
+pub fn tape_machine(input: TokenStream) -> TokenStream {
+    let mut source = Source::default();
+    source.push_stream(input);
+    match compile(&source.text) {
+        Ok(code) => code.parse().unwrap(),
+        Err(diagnostic) => {
+            let span = source
+                .span_at(diagnostic.span.line, diagnostic.span.column)
+                .unwrap_or_else(Span::call_site);
+            compile_error(&diagnostic.message, span)
+        }
+    }
+}
+
+// Compile a machine from a file, relative to the directory of the crate's
+// Cargo.toml:
+//
+//     include_machine!("examples/example.txt");
+//
+// Parse errors are reported on the path with their position in the file.
+#[proc_macro]
+pub fn include_machine(input: TokenStream) -> TokenStream {
+    let mut tokens = input.into_iter();
+    let (literal, span) = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => (literal.to_string(), literal.span()),
+        (Some(token), _) => return compile_error("expected a file path", token.span()),
+        (None, _) => return compile_error("expected a file path", Span::call_site()),
+    };
+    let Some(relative_path) = literal.strip_prefix('"').and_then(|l| l.strip_suffix('"')) else {
+        return compile_error("expected a file path", span);
+    };
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = PathBuf::from(manifest_dir).join(relative_path);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => return compile_error(&format!("Failed to read {}: {}", path.display(), e), span),
+    };
+    match compile(&text) {
+        // Depend on the file so the crate is rebuilt when it changes
+        Ok(code) => format!(
+            "const _: &str = include_str!({:?});\n{}",
+            path.display().to_string(),
+            code
+        )
+        .parse()
+        .unwrap(),
+        Err(diagnostic) => compile_error(
+            &format!(
+                "{} ({}:{}:{})",
+                diagnostic.message, relative_path, diagnostic.span.line, diagnostic.span.column
+            ),
+            span,
+        ),
+    }
+}
+
+// Run the lexer and parser, the first error is returned. Undeclared states
+// and symbols are only warnings for the parser, but the expanded code would
+// not compile with them.
+fn compile(text: &str) -> Result<String, Diagnostic> {
+    let mut parser = Parser::new(Lexer::new(text));
+    parser.try_program()?;
+    if let Some(warning) = parser.diagnostics().first() {
+        return Err(warning.clone());
+    }
+    Ok(parser.tree.to_rust_code())
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let tokens: TokenStream = format!("compile_error!({:?});", message).parse().unwrap();
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
+}
+
+// Source text rebuilt from the macro input, keeping the line breaks the DSL
+// relies on, and the span of every token to map diagnostics back
+#[derive(Default)]
+struct Source {
+    text: String,
+    // 1-based line and column of the last character written
+    line: usize,
+    column: usize,
+    // (line, column, span) of every token in `text`
+    tokens: Vec<(usize, usize, Span)>,
+    // Line of the first token, the text starts at line 1
+    first_line: Option<usize>,
+}
+
+impl Source {
+    fn push_stream(&mut self, stream: TokenStream) {
+        for token in stream {
+            match token {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::None => ("", ""),
+                    };
+                    self.push(open, group.span_open());
+                    self.push_stream(group.stream());
+                    self.push(close, group.span_close());
+                }
+                token => self.push(&token.to_string(), token.span()),
+            }
+        }
+    }
+
+    fn push(&mut self, text: &str, span: Span) {
+        if text.is_empty() {
+            return;
+        }
+        let first_line = *self.first_line.get_or_insert(span.line());
+        let line = span.line().saturating_sub(first_line) + 1;
+        // Tokens of macro_rules! expansions may not be in source order
+        if line > self.line {
+            if self.line > 0 {
+                self.text.push_str(&"\n".repeat(line - self.line));
+            }
+            self.line = line;
+            self.column = 0;
+        }
+        let column = span.column().max(self.column + 1);
+        self.text.push_str(&" ".repeat(column - self.column - 1));
+        self.text.push_str(text);
+        self.tokens.push((self.line, column, span));
+        self.column = column + text.chars().count() - 1;
+    }
+
+    // Span of the token starting at, or containing, the given position
+    fn span_at(&self, line: usize, column: usize) -> Option<Span> {
+        self.tokens
+            .iter()
+            .rev()
+            .find(|(l, c, _)| *l == line && *c <= column)
+            .or_else(|| self.tokens.iter().rev().find(|(l, _, _)| *l <= line))
+            .map(|(_, _, span)| *span)
+    }
+}
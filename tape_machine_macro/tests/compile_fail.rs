@@ -0,0 +1,5 @@
+// Machines that must not compile, the expected errors are in tests/ui/*.stderr
+#[test]
+fn test_compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,52 @@
+use tape_machine_macro::{include_machine, tape_machine};
+
+#[allow(dead_code)]
+mod inline {
+    use super::*;
+
+    tape_machine! {
+        STATES: [a], b
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, 0 | X, P(1)-R, b
+        b, *, L, a
+    }
+
+    #[test]
+    fn test_inline_machine() {
+        assert_eq!(TapeMachineSymbol::Symbol1.as_str(), "1");
+
+        let mut tape = vec![TapeMachineSymbol::SymbolX; 4];
+        tape[0] = TapeMachineSymbol::Symbol0;
+        let mut machine = TapeMachine::new(&TapeMachineState::a, &mut tape);
+
+        // a, 0 prints 1 and moves right, b, * moves back left
+        step(&mut machine, TraceLevel::None, true);
+        assert_eq!(machine.state, &TapeMachineState::b);
+        assert_eq!(machine.index, 1);
+        step(&mut machine, TraceLevel::None, true);
+        assert_eq!(machine.state, &TapeMachineState::a);
+        assert_eq!(machine.index, 0);
+
+        // a, 1 has no transition
+        let stuck = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            step(&mut machine, TraceLevel::None, true)
+        }));
+        assert!(stuck.is_err());
+        assert_eq!(tape[0], TapeMachineSymbol::Symbol1);
+        assert_eq!(tape[1], TapeMachineSymbol::SymbolX);
+    }
+}
+
+#[allow(dead_code)]
+mod included {
+    use super::*;
+
+    include_machine!("../examples/example.txt");
+
+    #[test]
+    fn test_included_machine() {
+        assert_eq!(TapeMachineSymbol::Symbole.as_str(), "e");
+        assert_eq!(format!("{:?}", TapeMachineState::b), "b".to_string());
+    }
+}
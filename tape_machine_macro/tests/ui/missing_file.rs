@@ -0,0 +1,7 @@
+mod machine {
+    use tape_machine_macro::include_machine;
+
+    include_machine!("examples/missing.txt");
+}
+
+fn main() {}
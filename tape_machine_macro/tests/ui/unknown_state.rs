@@ -0,0 +1,12 @@
+mod machine {
+    use tape_machine_macro::tape_machine;
+
+    tape_machine! {
+        STATES: [a], b
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, 0, P(1)-R, c
+    }
+}
+
+fn main() {}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::analysis::{Analysis, StateGraph};
+
+const CODE: &str = "
+    STATES: [a], b, c, d, e
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, 0, R, b
+    a, 1 | X, R, a
+    b, *, L, a
+    c, *, R, d
+    d, 0, R, e
+";
+
+#[test]
+fn test_state_graph() {
+    let tree = parse(CODE);
+    let graph = StateGraph::new(&tree);
+
+    assert_eq!(
+        graph.edges,
+        vec![vec![1, 0], vec![0], vec![3], vec![4], vec![]]
+    );
+    assert_eq!(graph.reachable(2), vec![false, false, true, true, true]);
+}
+
+#[test]
+fn test_strongly_connected_components() {
+    let tree = parse(CODE);
+    let graph = StateGraph::new(&tree);
+
+    assert_eq!(
+        graph.strongly_connected_components(),
+        vec![vec![0, 1], vec![2], vec![3], vec![4]]
+    );
+}
+
+#[test]
+fn test_analysis() {
+    let tree = parse(CODE);
+    let analysis = Analysis::new(&tree);
+
+    assert_eq!(analysis.unreachable_states, vec!["c", "d", "e"]);
+    assert_eq!(analysis.halting_states, vec!["e"]);
+    assert_eq!(
+        analysis.missing_transitions,
+        vec![
+            ("d".to_string(), "1".to_string()),
+            ("d".to_string(), "X".to_string())
+        ]
+    );
+    assert_eq!(
+        analysis.to_string(),
+        "\
+Unreachable states: c, d, e
+Halting states: e
+Missing transitions:
+  d reading 1
+  d reading X
+Strongly connected components:
+  {a, b}
+  {c}
+  {d}
+  {e}
+"
+    );
+}
+
+#[test]
+fn test_shadowed_transition_adds_no_edge() {
+    let tree = parse(
+        "
+        STATES: [a], b
+        SYMBOLS: 0
+        TRANSITIONS:
+        a, 0 | X, R, a
+        a, *, R, b
+        ",
+    );
+    let analysis = Analysis::new(&tree);
+
+    assert_eq!(analysis.unreachable_states, vec!["b"]);
+    assert!(analysis.missing_transitions.is_empty());
+}
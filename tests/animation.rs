@@ -0,0 +1,63 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::animation::dot_frames;
+use state_machine_compiler_rust::interpreter::{parse_tape, Machine};
+use state_machine_compiler_rust::parser::{DotHighlight, DotOptions, ToDot};
+
+const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, 0, P(1)-R, a
+    a, X, L, b
+";
+
+#[test]
+fn test_highlight() {
+    let tree = parse(CODE);
+    let dot = tree.to_dot_with(&DotOptions {
+        highlight: Some(DotHighlight {
+            state: "a".to_string(),
+            transition: Some(1),
+            caption: "Step 1".to_string(),
+        }),
+        ..Default::default()
+    });
+
+    assert!(dot.contains("label=\"Step 1\";"));
+    assert!(dot.contains(
+        "\"a\" [shape=doublecircle, fillcolor=gold, width=1.5, height=1.5, penwidth=3];"
+    ));
+    assert!(dot.contains("\"b\" [shape=circle, fillcolor=lightblue, width=1.2, height=1.2];"));
+    assert!(dot.contains(
+        "\"a\" -> \"b\" [label=\"[X] / L\", color=orange, penwidth=3, fontcolor=orange];"
+    ));
+    assert!(dot.contains("\"a\" -> \"a\" [label=\"[0] / P(1)-R\", color=black];"));
+}
+
+#[test]
+fn test_frames_until_halt() {
+    let tree = parse(CODE);
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, "00").unwrap());
+    let frames = dot_frames(&mut machine, 100, &DotOptions::default());
+
+    // Two P(1)-R steps, the L step, then the halted state
+    assert_eq!(frames.len(), 4);
+    assert!(frames[0].contains("label=\"Step 0: a reading 0\";"));
+    assert!(frames[2].contains("label=\"Step 2: a reading X\";"));
+    assert!(frames[2].contains("color=orange"));
+    assert!(frames[3].contains("label=\"Step 3: b (halted)\";"));
+    assert!(frames[3].contains("\"b\" [shape=circle, fillcolor=gold"));
+    assert!(!frames[3].contains("color=orange"));
+}
+
+#[test]
+fn test_frames_stop_after_max_steps() {
+    let tree = parse(CODE);
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, "00").unwrap());
+    let frames = dot_frames(&mut machine, 1, &DotOptions::default());
+
+    assert_eq!(frames.len(), 2);
+    assert!(frames[1].contains("label=\"Step 1: a (stopped)\";"));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::{parse, EXAMPLE};
+use state_machine_compiler_rust::bench::{self, Backend, BenchOptions};
+use state_machine_compiler_rust::interpreter::Machine;
+
+const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, *, P(0)-R, b
+    b, *, P(1)-R, a
+";
+
+#[test]
+fn test_backend_from_str() {
+    assert_eq!("interpreter".parse::<Backend>(), Ok(Backend::Interpreter));
+    assert_eq!("llvm".parse::<Backend>(), Ok(Backend::Llvm));
+    assert_eq!(
+        "java".parse::<Backend>(),
+        Err("Unknown backend: java".to_string())
+    );
+    for backend in Backend::ALL {
+        assert_eq!(backend.as_str().parse::<Backend>(), Ok(backend));
+    }
+}
+
+#[test]
+fn test_bench_interpreter() {
+    let tree = parse(CODE);
+    let options = BenchOptions {
+        steps: 1000,
+        ..Default::default()
+    };
+    let results = bench::bench(&tree, &[], &[Backend::Interpreter], &options);
+
+    assert_eq!(results.len(), 1);
+    let (backend, measurement) = &results[0];
+    assert_eq!(*backend, Backend::Interpreter);
+    assert_eq!(measurement.as_ref().unwrap().steps, 1000);
+
+    let report = bench::report(&results);
+    let lines = report.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("backend"));
+    assert!(lines[0].ends_with("steps/s"));
+    assert!(lines[1].starts_with("interpreter"));
+    assert!(lines[1].contains(" 1000 "));
+}
+
+#[test]
+fn test_bench_reports_errors() {
+    let results = vec![(Backend::C, Err("cc failed: no compiler".to_string()))];
+    assert_eq!(
+        bench::report(&results).lines().nth(1),
+        Some("c              cc failed: no compiler")
+    );
+}
+
+#[test]
+fn test_bench_all_backends() {
+    let tree = parse(EXAMPLE);
+    let mut reference = Machine::new(&tree);
+    reference.run(2000);
+    let options = BenchOptions {
+        steps: 2000,
+        work_dir: std::env::temp_dir().join(format!("state_machine_bench_{}", std::process::id())),
+        ..Default::default()
+    };
+    let results = bench::bench(&tree, &[], &Backend::ALL, &options);
+
+    assert_eq!(results.len(), Backend::ALL.len());
+    for (backend, result) in results {
+        match result {
+            Ok(measurement) => assert_eq!(measurement.steps, reference.steps, "{:?}", backend),
+            // Skipped without rustc, cc or clang
+            Err(e) if e.starts_with("Failed to run") => eprintln!("{}, skipping {:?}", e, backend),
+            Err(e) => panic!("{:?}: {}", backend, e),
+        }
+    }
+    std::fs::remove_dir_all(&options.work_dir).unwrap();
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use common::{parse, run_c, CODE, EXAMPLE};
+use state_machine_compiler_rust::cconverter::{CCodegenOptions, ToC};
+use state_machine_compiler_rust::interpreter::Machine;
+use state_machine_compiler_rust::trace::{TraceFormat, TraceLevel, TraceOptions};
+
+#[test]
+fn test_c_dispatch() {
+    let tree = parse(CODE);
+    let code = tree.to_c();
+
+    assert!(code.contains("typedef enum {\n    STATE_a,\n    STATE_b,\n} TapeMachineState;\n"));
+    assert!(code.contains("static const char *const SYMBOL_NAMES[] = {\"0\", \"1\", \"X\"};\n"));
+    // The symbol transition comes first, the star one takes the other symbols
+    assert!(code.contains(
+        "\
+    case STATE_a:
+        switch (symbol) {
+        case SYMBOL_1:
+            machine->index += 1;
+            machine->index -= 1;
+            machine->state = STATE_a;
+            break;
+        default:
+            if (tape_machine_p(machine, SYMBOL_0) != TAPE_MACHINE_OK) return TAPE_MACHINE_OUT_OF_TAPE;
+            machine->index += 1;
+            machine->state = STATE_b;
+            break;
+        }
+"
+    ));
+    assert!(code.contains(
+        "\
+        case SYMBOL_0:
+        case SYMBOL_1:
+            if (tape_machine_p(machine, SYMBOL_1) != TAPE_MACHINE_OK) return TAPE_MACHINE_OUT_OF_TAPE;
+            machine->state = STATE_a;
+            break;
+        default:
+            return TAPE_MACHINE_NO_TRANSITION;
+"
+    ));
+}
+
+#[test]
+fn test_c_program() {
+    let tree = parse(CODE);
+    let code = tree.to_c_with(&CCodegenOptions {
+        trace: TraceOptions {
+            level: TraceLevel::Full,
+            format: TraceFormat::Compact,
+        },
+        ..Default::default()
+    });
+
+    assert!(code.contains("int main(void) {\n"));
+    assert!(code.contains("    printf(\"Enter the number of steps:\\n\");\n"));
+    assert!(code.contains("    *level = TRACE_FULL;\n    *compact = 1;\n"));
+    assert!(code.contains("getenv(\"TAPE_MACHINE_TRACE\")"));
+    assert!(code.contains("fprintf(stderr, \"Invalid state reached\\n\");"));
+}
+
+#[test]
+fn test_c_header_only() {
+    let tree = parse(CODE);
+    let code = tree.to_c_with(&CCodegenOptions {
+        header_only: true,
+        ..Default::default()
+    });
+
+    assert!(code.starts_with("#ifndef TAPE_MACHINE_H\n#define TAPE_MACHINE_H\n"));
+    assert!(code.ends_with("#endif\n"));
+    assert!(code.contains(
+        "static inline int tape_machine_run(TapeMachine *machine, size_t max_steps) {\n"
+    ));
+    assert!(!code.contains("int main(void)"));
+    assert!(!code.contains("getenv"));
+}
+
+#[test]
+fn test_c_program_runs_like_interpreter() {
+    let tree = parse(EXAMPLE);
+    let mut machine = Machine::new(&tree);
+    assert_eq!(machine.run(1000), None);
+    let tape_len = 200;
+    let tape = format!(
+        "{}{}",
+        machine.tape_string(),
+        "X".repeat(tape_len - machine.tape.len())
+    );
+
+    let code = tree.to_c_with(&CCodegenOptions {
+        trace: TraceOptions {
+            level: TraceLevel::None,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let Some(output) = run_c("c_program", &code, &format!("1000\n{}\n", tape_len)) else {
+        return;
+    };
+    assert_eq!(
+        output,
+        format!(
+            "Enter the number of steps:\nEnter the total tape length:\n{}\n=========\n\n{}\n",
+            tape,
+            machine.clean_tape_string()
+        )
+    );
+
+    // The library alone, driven by a main of its own
+    let mut code = tree.to_c_with(&CCodegenOptions {
+        header_only: true,
+        ..Default::default()
+    });
+    code.push_str(&format!(
+        "
+int main(void) {{
+    TapeMachineSymbol tape[{len}];
+    for (size_t i = 0; i < {len}; i++) {{
+        tape[i] = SYMBOL_X;
+    }}
+    TapeMachine machine;
+    tape_machine_init(&machine, tape, {len});
+    int halt = tape_machine_run(&machine, 1000);
+    printf(\"%d %s %zu %zu \", halt, STATE_NAMES[machine.state], machine.index, machine.steps);
+    for (size_t i = 0; i < {len}; i++) {{
+        fputs(SYMBOL_NAMES[tape[i]], stdout);
+    }}
+    putchar('\\n');
+    return 0;
+}}
+",
+        len = tape_len
+    ));
+    let Some(output) = run_c("c_header_only", &code, "") else {
+        return;
+    };
+    assert_eq!(
+        output,
+        format!(
+            "0 {} {} {} {}\n",
+            machine.state_name(),
+            machine.head,
+            machine.steps,
+            tape
+        )
+    );
+}
@@ -0,0 +1,186 @@
+// Helpers shared by the integration tests. Each test crate uses only a part
+// of them.
+#![allow(dead_code)]
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use state_machine_compiler_rust::lexer::Lexer;
+use state_machine_compiler_rust::parser::{ParseTree, Parser};
+
+pub fn parse(code: &str) -> ParseTree {
+    let lexer = Lexer::new(code);
+    let mut parser = Parser::new(lexer);
+    parser.program();
+    parser.tree
+}
+
+// Machine compiled by the code generation tests
+pub const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, *, P(0)-R, b
+    a, 1, R-L-X, a
+    b, 0 | 1, P(1), a
+";
+
+// The machine of the README, writing 0 1 0 1 1 0 1 1 1 ... on every other cell
+pub const EXAMPLE: &str = "
+    STATES: [b], o, q, p, f
+    SYMBOLS: 0, 1, e, x
+    TRANSITIONS:
+    b, *, P(e)-R-P(e)-R-P(0)-R-R-P(0)-L-L, o
+    o, 1, R-P(x)-L-L-L, o
+    o, 0, X, q
+    q, 0 | 1, R-R, q
+    q, X, P(1)-L, p
+    p, x, P(X)-R, q
+    p, e, R, f
+    p, X, L-L, p
+    f, *, R-R, f
+    f, X, P(0)-L-L, o
+";
+
+// Whether `tool` can be run, tests needing it pass without it
+pub fn has_tool(tool: &str) -> bool {
+    if Command::new(tool).arg("--version").output().is_err() {
+        eprintln!("{} not found, skipping", tool);
+        return false;
+    }
+    true
+}
+
+// Write `contents` to `file_name` in a temporary directory of the test `name`
+pub fn write_temp(name: &str, file_name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("state_machine_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(file_name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+// Remove the temporary directory of `path`. Only called once the test has
+// passed, the files are kept on failures to look into them.
+pub fn clean(path: &Path) {
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+// Build `code` saved as `file_name` with `compiler flags -o program source`.
+// Gives None when the compiler is not available.
+pub fn build(
+    name: &str,
+    compiler: &str,
+    flags: &[&str],
+    file_name: &str,
+    code: &str,
+) -> Option<PathBuf> {
+    if !has_tool(compiler) {
+        return None;
+    }
+    let source = write_temp(name, file_name, code.as_bytes());
+    let program = source.with_extension("");
+    let output = Command::new(compiler)
+        .args(flags)
+        .arg("-o")
+        .arg(&program)
+        .arg(&source)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{} failed on {}:\n{}",
+        compiler,
+        source.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(program)
+}
+
+// Run `program` with `args`, `envs` on top of an environment without any
+// trace setting, and `stdin`
+pub fn run(
+    program: impl AsRef<OsStr>,
+    args: &[&str],
+    envs: &[(&str, &str)],
+    stdin: &str,
+) -> Output {
+    let mut child = Command::new(program)
+        .args(args)
+        .env_remove("TAPE_MACHINE_TRACE")
+        .env_remove("TAPE_MACHINE_TRACE_FORMAT")
+        .envs(envs.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// Run `program` like `run`, it must succeed, and give its standard output
+pub fn run_ok(
+    program: impl AsRef<OsStr>,
+    args: &[&str],
+    envs: &[(&str, &str)],
+    stdin: &str,
+) -> String {
+    let output = run(program, args, envs, stdin);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Build the Rust program `code` with rustc and run it with `stdin`. Gives
+// None, and the test passes, when rustc is not available.
+pub fn run_rust(name: &str, code: &str, stdin: &str) -> Option<String> {
+    let program = build_rust(name, code)?;
+    let output = run_ok(&program, &[], &[], stdin);
+    clean(&program);
+    Some(output)
+}
+
+pub fn build_rust(name: &str, code: &str) -> Option<PathBuf> {
+    build(
+        name,
+        "rustc",
+        &["--edition", "2021", "-A", "warnings"],
+        &format!("{}.rs", name),
+        code,
+    )
+}
+
+// Same as `run_rust` for C programs, with the warnings as errors
+pub fn run_c(name: &str, code: &str, stdin: &str) -> Option<String> {
+    let program = build_c(name, code)?;
+    let output = run_ok(&program, &[], &[], stdin);
+    clean(&program);
+    Some(output)
+}
+
+pub fn build_c(name: &str, code: &str) -> Option<PathBuf> {
+    build(
+        name,
+        "cc",
+        &["-std=c99", "-Wall", "-Werror"],
+        &format!("{}.c", name),
+        code,
+    )
+}
+
+// Build LLVM IR with clang, gives None when clang is not available
+pub fn build_llvm(name: &str, ir: &str) -> Option<PathBuf> {
+    build(name, "clang", &["-O2"], &format!("{}.ll", name), ir)
+}
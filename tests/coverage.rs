@@ -0,0 +1,72 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::coverage::Coverage;
+use state_machine_compiler_rust::interpreter::{parse_tape, Halt, Machine};
+use state_machine_compiler_rust::parser::{DotOptions, ParseTree, ToDot};
+
+const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, 0, P(1)-R, a
+    a, 1, P(0)-R, a
+    a, X, L, b
+";
+
+fn run(tree: &ParseTree, coverage: &mut Coverage, input: &str) -> Option<Halt> {
+    let mut machine = Machine::with_tape(tree, parse_tape(tree, input).unwrap());
+    coverage.run(&mut machine, 100)
+}
+
+#[test]
+fn test_hits_over_several_runs() {
+    let tree = parse(CODE);
+    let mut coverage = Coverage::new(&tree);
+
+    assert_eq!(run(&tree, &mut coverage, "000"), Some(Halt::NoTransition));
+    assert_eq!(coverage.hits, vec![3, 0, 1]);
+    assert_eq!(coverage.uncovered(), vec![1]);
+
+    run(&tree, &mut coverage, "1");
+    assert_eq!(coverage.hits, vec![3, 1, 2]);
+    assert_eq!(coverage.covered(), 3);
+    assert_eq!(coverage.percentage(), 100.0);
+}
+
+#[test]
+fn test_report() {
+    let tree = parse(CODE);
+    let mut coverage = Coverage::new(&tree);
+    run(&tree, &mut coverage, "00");
+
+    assert_eq!(
+        coverage.report(&tree),
+        "\
+Transition coverage: 2/3 (66.7%)
+         2  a, 0, P(1)-R, a
+         0  a, 1, P(0)-R, a
+         1  a, X, L, b
+Never applied:
+  a, 1, P(0)-R, a
+"
+    );
+}
+
+#[test]
+fn test_dot_overlay() {
+    let tree = parse(CODE);
+    let mut coverage = Coverage::new(&tree);
+    run(&tree, &mut coverage, "00");
+
+    let dot = tree.to_dot_with(&DotOptions {
+        coverage: Some(coverage),
+        ..Default::default()
+    });
+    assert!(
+        dot.contains("\"a\" -> \"a\" [label=\"[0] / P(1)-R (2)\", color=darkgreen, penwidth=5.0];")
+    );
+    assert!(dot.contains("\"a\" -> \"a\" [label=\"[1] / P(0)-R (0)\", color=red, style=dashed];"));
+    assert!(dot.contains("\"a\" -> \"b\" [label=\"[X] / L (1)\", color=darkgreen, penwidth=3.0];"));
+    assert!(!tree.to_dot().contains("penwidth"));
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::cycles::{CycleDetector, NonHalting, Verdict};
+use state_machine_compiler_rust::interpreter::{parse_tape, Halt, Machine};
+
+fn verdict(code: &str, tape: &str, max_steps: usize) -> Verdict {
+    let tree = parse(code);
+    let tape = parse_tape(&tree, tape).unwrap();
+    let mut machine = Machine::with_tape(&tree, tape);
+    let mut detector = CycleDetector::new(&machine);
+    detector.run(&mut machine, max_steps)
+}
+
+#[test]
+fn test_repeated_configuration() {
+    // Flips the first cell back and forth
+    let code = "
+        STATES: [a], b
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, 0, P(1)-R, b
+        b, *, L, a
+        a, 1, P(0)-R, b
+    ";
+    let non_halting = NonHalting::Cycle {
+        start: 3,
+        length: 4,
+    };
+    assert_eq!(verdict(code, "0", 1000), Verdict::NonHalting(non_halting));
+    assert_eq!(non_halting.length(), 4);
+    assert_eq!(
+        non_halting.to_string(),
+        "the configuration of step 3 comes back every 4 steps"
+    );
+
+    // Visiting a blank cell extends the tape but changes nothing
+    let code = "
+        STATES: [a]
+        SYMBOLS: 0
+        TRANSITIONS:
+        a, *, R-L, a
+    ";
+    assert_eq!(
+        verdict(code, "", 1000),
+        Verdict::NonHalting(NonHalting::Cycle {
+            start: 0,
+            length: 1
+        })
+    );
+}
+
+#[test]
+fn test_translated_cycle() {
+    // Writes 0 1 0 1 ... forever
+    let code = "
+        STATES: [a], b
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, *, P(0), b
+        b, 0, R-P(1), b
+        b, 1, R-P(0), a
+    ";
+    let Verdict::NonHalting(non_halting) = verdict(code, "", 1000) else {
+        panic!("Expected a translated cycle");
+    };
+    assert_eq!(
+        non_halting,
+        NonHalting::TranslatedCycle {
+            start: 2,
+            length: 3,
+            shift: 2
+        }
+    );
+    assert_eq!(
+        non_halting.to_string(),
+        "from step 2 the machine repeats every 3 steps 2 cells further right"
+    );
+
+    // Starts on a written tape, and goes back to the cell behind the head
+    // which is written the same way every time
+    let code = "
+        STATES: [a], b
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, *, P(1)-R-R, b
+        b, X, L-P(0)-R, a
+    ";
+    assert!(matches!(
+        verdict(code, "01", 1000),
+        Verdict::NonHalting(NonHalting::TranslatedCycle { shift: 2, .. })
+    ));
+}
+
+#[test]
+fn test_no_false_cycles() {
+    // Sweeps back and forth over a growing block of 1s
+    let counter = "
+        STATES: [r], l
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        r, 0 | 1, R, r
+        r, X, P(1)-L, l
+        l, 1, L, l
+        l, 0, R, r
+    ";
+    assert_eq!(verdict(counter, "0", 100_000), Verdict::Unknown);
+
+    // Reads the initial tape before reaching the blank cells
+    let code = "
+        STATES: [a]
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, 0 | X, R, a
+    ";
+    assert_eq!(
+        verdict(code, "00000001", 1000),
+        Verdict::Halted(Halt::NoTransition)
+    );
+}
+
+#[test]
+fn test_same_steps_as_machine() {
+    let tree = parse(
+        "
+        STATES: [a], b
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, 0 | 1, R, a
+        a, X, L, b
+        b, 0, P(1)-L, b
+        ",
+    );
+    for tape in ["0000", "0101011"] {
+        let tape = parse_tape(&tree, tape).unwrap();
+        let mut machine = Machine::with_tape(&tree, tape.clone());
+        let halt = machine.run(1000).unwrap();
+
+        let mut detected = Machine::with_tape(&tree, tape);
+        let mut detector = CycleDetector::new(&detected);
+        assert_eq!(detector.run(&mut detected, 1000), Verdict::Halted(halt));
+        assert_eq!(detected.configuration(), machine.configuration());
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::debugger::{Breakpoint, Debugger};
+use state_machine_compiler_rust::interpreter::{parse_tape, Machine};
+
+const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, 0, P(1)-R, a
+    a, 1, R, b
+    b, *, P(0)-R, a
+";
+
+#[test]
+fn test_view() {
+    let tree = parse(CODE);
+    let machine = Machine::with_tape(&tree, parse_tape(&tree, "0101").unwrap());
+    let debugger = Debugger::new(machine, 2, 100);
+
+    assert_eq!(
+        debugger.view(),
+        "Step: 0 State: a Head: 0\nTape: [0] 1 0 ..\nNext: a, 0, P(1)-R, a"
+    );
+}
+
+#[test]
+fn test_step_and_reverse() {
+    let tree = parse(CODE);
+    let machine = Machine::with_tape(&tree, parse_tape(&tree, "0101").unwrap());
+    let mut debugger = Debugger::new(machine, 1, 100);
+
+    let output = debugger.execute("step 3").unwrap();
+    assert!(output.starts_with("Step: 3 State: a Head: 3"));
+    assert_eq!(debugger.machine().tape_string(), "1101");
+
+    // An empty line repeats the last command
+    debugger.execute("r");
+    debugger.execute("");
+    assert_eq!(debugger.machine().steps, 1);
+    assert_eq!(debugger.machine().head, 1);
+    assert_eq!(debugger.machine().state_name(), "a");
+
+    let output = debugger.execute("reverse 5").unwrap();
+    assert!(output.starts_with("No more history to reverse."));
+    assert_eq!(debugger.machine().steps, 0);
+    assert_eq!(debugger.machine().tape_string(), "0101");
+}
+
+#[test]
+fn test_breakpoints() {
+    let tree = parse(CODE);
+    let machine = Machine::with_tape(&tree, parse_tape(&tree, "0101").unwrap());
+    let mut debugger = Debugger::new(machine, 1, 100);
+
+    assert_eq!(
+        debugger.execute("break b").unwrap(),
+        "Breakpoint 1: state b"
+    );
+    assert_eq!(
+        debugger.execute("b a X").unwrap(),
+        "Breakpoint 2: state a reading X"
+    );
+    assert_eq!(
+        debugger.breakpoints(),
+        &[Breakpoint::State(1), Breakpoint::StateSymbol(0, 2)]
+    );
+    assert_eq!(debugger.execute("b c").unwrap(), "State c not defined.");
+
+    let output = debugger.execute("continue").unwrap();
+    assert!(output.starts_with("Breakpoint 1 hit."));
+    assert_eq!(debugger.machine().steps, 2);
+
+    debugger.execute("delete 1");
+    let output = debugger.execute("c").unwrap();
+    assert!(output.starts_with("Breakpoint 1 hit."));
+    assert_eq!(debugger.machine().symbol_name(), "X");
+
+    let output = debugger.execute("c").unwrap();
+    assert!(output.starts_with("Machine halted: no transition for state a and symbol X."));
+}
+
+#[test]
+fn test_run_session() {
+    let tree = parse(CODE);
+    let machine = Machine::new(&tree);
+    let mut debugger = Debugger::new(machine, 1, 100);
+    let mut output = Vec::new();
+
+    debugger
+        .run("s\nq\nprint\n".as_bytes(), &mut output)
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("Machine halted: no transition for state a and symbol X."));
+    // Nothing after quit is executed
+    assert_eq!(output.matches("(tmdb) ").count(), 2);
+}
@@ -0,0 +1,54 @@
+use state_machine_compiler_rust::diagnostics::{
+    closest_match, edit_distance, Diagnostic, MessageFormat, Span,
+};
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("q", "q"), 0);
+    assert_eq!(edit_distance("q", "p"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[test]
+fn test_closest_match() {
+    let states = vec!["start".to_string(), "stop".to_string(), "q".to_string()];
+    assert_eq!(closest_match("stat", &states), Some(&"start".to_string()));
+    assert_eq!(closest_match("sto", &states), Some(&"stop".to_string()));
+    assert_eq!(closest_match("finish", &states), None);
+}
+
+#[test]
+fn test_render_human() {
+    let source = "STATES: [a]\nSYMBOLS: 0\nTRANSITIONS:\na, 0, P(1), b\n";
+    let diagnostic = Diagnostic::error(
+        "Symbol 1 not defined, So cannot be printed.",
+        Span::new(4, 9, 1),
+    )
+    .with_help(Some("did you mean symbol `0`?".to_string()));
+    let expected = "\
+error: Symbol 1 not defined, So cannot be printed.
+ --> machine.txt:4:9
+  |
+4 | a, 0, P(1), b
+  |         ^
+  = help: did you mean symbol `0`?
+";
+    assert_eq!(diagnostic.render("machine.txt", source), expected);
+}
+
+#[test]
+fn test_render_json() {
+    let diagnostic = Diagnostic::warning("State \"b\" is not declared.", Span::new(4, 13, 1));
+    assert_eq!(
+        diagnostic.to_json("dir\\machine.txt"),
+        "{\"severity\":\"warning\",\"message\":\"State \\\"b\\\" is not declared.\",\"file\":\"dir\\\\machine.txt\",\"line\":4,\"column\":13,\"length\":1,\"help\":null}"
+    );
+}
+
+#[test]
+fn test_message_format_from_str() {
+    assert_eq!("human".parse::<MessageFormat>(), Ok(MessageFormat::Human));
+    assert_eq!("json".parse::<MessageFormat>(), Ok(MessageFormat::Json));
+    assert!("xml".parse::<MessageFormat>().is_err());
+}
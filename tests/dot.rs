@@ -0,0 +1,78 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::escape::dot_escape;
+use state_machine_compiler_rust::parser::{DotOptions, ToDot};
+
+const CODE: &str = "
+    STATES: [add0], add1, add2, sub0, sub1, done
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    add0, 0, R, add1
+    add0, 1, P(0)-R, add1
+    add1, *, L, sub0
+    add2, *, L, add2
+    sub0, X, R, sub1
+    sub1, X, R, done
+";
+
+#[test]
+fn test_one_edge_per_transition() {
+    let tree = parse(CODE);
+    let dot = tree.to_dot();
+
+    assert!(dot.contains("\"add0\" -> \"add1\" [label=\"[0] / R\", color=black];"));
+    assert!(dot.contains("\"add0\" -> \"add1\" [label=\"[1] / P(0)-R\", color=black];"));
+    assert!(!dot.contains("subgraph"));
+    assert!(dot.contains("\"done\" [shape=circle, fillcolor=lightblue"));
+}
+
+#[test]
+fn test_merge_edges() {
+    let tree = parse(CODE);
+    let dot = tree.to_dot_with(&DotOptions {
+        merge_edges: true,
+        ..Default::default()
+    });
+
+    assert!(dot.contains("\"add0\" -> \"add1\" [label=\"[0] / R\\n[1] / P(0)-R\", color=black];"));
+    assert_eq!(dot.matches("->").count(), 5);
+}
+
+#[test]
+fn test_halting_states() {
+    let tree = parse(CODE);
+    let dot = tree.to_dot_with(&DotOptions {
+        style_halting: true,
+        ..Default::default()
+    });
+
+    assert!(dot.contains("\"done\" [shape=doubleoctagon, fillcolor=lightcoral"));
+    assert!(dot.contains("\"sub0\" [shape=circle, fillcolor=lightblue"));
+}
+
+#[test]
+fn test_clusters() {
+    let tree = parse(CODE);
+    let dot = tree.to_dot_with(&DotOptions {
+        clusters: true,
+        ..Default::default()
+    });
+
+    let add = dot
+        .find("subgraph \"cluster_add\" { label=\"add\";")
+        .unwrap();
+    let sub = dot
+        .find("subgraph \"cluster_sub\" { label=\"sub\";")
+        .unwrap();
+    assert!(add < dot.find("\"add1\" [").unwrap());
+    assert!(dot.find("\"add1\" [").unwrap() < sub);
+    assert!(!dot.contains("cluster_done"));
+}
+
+#[test]
+fn test_dot_escape() {
+    assert_eq!(dot_escape("a\"b"), "a\\\"b");
+    assert_eq!(dot_escape("a\\b"), "a\\\\b");
+    assert_eq!(dot_escape("a\nb"), "a\\nb");
+}
@@ -0,0 +1,11 @@
+use state_machine_compiler_rust::escape::{quote, quote_list};
+
+#[test]
+fn test_quote() {
+    assert_eq!(quote("q0"), "\"q0\"");
+    assert_eq!(quote("a\"b\\c"), "\"a\\\"b\\\\c\"");
+    assert_eq!(
+        quote_list(&["a".to_string(), "b".to_string()]),
+        "\"a\", \"b\""
+    );
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::graphs::{GraphFormat, ToGraphMl, ToMermaid, ToPlantUml};
+use state_machine_compiler_rust::parser::{Condition, ParseTree, Transition, TransitionStep};
+
+const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, 0 | 1, P(1)-R, a
+    a, *, L, b
+";
+
+#[test]
+fn test_mermaid() {
+    let tree = parse(CODE);
+    assert_eq!(
+        tree.to_mermaid(),
+        "\
+stateDiagram-v2
+    [*] --> a
+    a --> a : [0,1] / P(1)#45;R
+    a --> b : * / L
+"
+    );
+}
+
+#[test]
+fn test_plantuml() {
+    let tree = parse(CODE);
+    assert_eq!(
+        tree.to_plantuml(),
+        "\
+@startuml
+state a
+state b
+[*] --> a
+a --> a : [0,1] / P(1)~-R
+a --> b : * / L
+@enduml
+"
+    );
+}
+
+// Names the parser does not accept, but a tree built by hand may hold
+#[test]
+fn test_diagram_escaping() {
+    let tree = ParseTree {
+        states: vec!["start".to_string(), "go-left \"now\"".to_string()],
+        initial_state: "start".to_string(),
+        symbols: vec!["|".to_string(), "X".to_string()],
+        transitions: vec![Transition {
+            initial_state: "start".to_string(),
+            condition: Condition::OR(vec!["|".to_string()]),
+            steps: vec![TransitionStep::P("|".to_string()), TransitionStep::L],
+            final_state: "go-left \"now\"".to_string(),
+            line: 0,
+        }],
+    };
+
+    assert_eq!(
+        tree.to_mermaid(),
+        "\
+stateDiagram-v2
+    state \"go#45;left #quot;now#quot;\" as state_1
+    [*] --> start
+    start --> state_1 : [#124;] / P(#124;)#45;L
+"
+    );
+    assert_eq!(
+        tree.to_plantuml(),
+        "\
+@startuml
+state start
+state \"go~-left ~\"now~\"\" as state_1
+[*] --> start
+start --> state_1 : [~|] / P(~|)~-L
+@enduml
+"
+    );
+}
+
+#[test]
+fn test_graphml() {
+    let tree = parse(CODE);
+    let graphml = tree.to_graphml();
+
+    assert!(graphml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml"));
+    assert!(graphml.contains("<node id=\"a\"><data key=\"initial\">true</data></node>"));
+    assert!(graphml.contains("<node id=\"b\"><data key=\"initial\">false</data></node>"));
+    assert!(graphml.contains(
+        "<edge id=\"t1\" source=\"a\" target=\"b\"><data key=\"label\">* / L</data></edge>"
+    ));
+    assert!(graphml.ends_with("</graph>\n</graphml>\n"));
+}
+
+#[test]
+fn test_graph_format() {
+    assert_eq!("mermaid".parse::<GraphFormat>(), Ok(GraphFormat::Mermaid));
+    assert_eq!("plantuml".parse::<GraphFormat>(), Ok(GraphFormat::PlantUml));
+    assert_eq!("graphml".parse::<GraphFormat>(), Ok(GraphFormat::GraphMl));
+    assert_eq!("dot".parse::<GraphFormat>(), Ok(GraphFormat::Dot));
+    assert!("svg".parse::<GraphFormat>().is_err());
+    assert_eq!(GraphFormat::PlantUml.extension(), "puml");
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::interpreter::{parse_tape, Halt, Machine, StepResult};
+
+const INVERT: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, 0, P(1)-R, a
+    a, 1, P(0)-R, a
+";
+
+#[test]
+fn test_run_until_no_transition() {
+    let tree = parse(INVERT);
+    let tape = parse_tape(&tree, "0110").unwrap();
+    let mut machine = Machine::with_tape(&tree, tape);
+
+    assert_eq!(machine.run(100), Some(Halt::NoTransition));
+    assert_eq!(machine.steps, 4);
+    assert_eq!(machine.head, 4);
+    assert_eq!(machine.tape_string(), "1001X");
+    assert_eq!(machine.clean_tape_string(), "1001");
+}
+
+#[test]
+fn test_first_matching_transition_wins() {
+    let tree = parse(
+        "
+        STATES: [a], b, c
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, *, P(1), c
+        a, 0, P(0), b
+        ",
+    );
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, "0").unwrap());
+
+    // Symbol transitions take precedence over the star transition
+    assert_eq!(machine.step(), StepResult::Fired(1));
+    assert_eq!(machine.state_name(), "b");
+}
+
+#[test]
+fn test_left_edge_halts_without_changes() {
+    let tree = parse(
+        "
+        STATES: [a]
+        SYMBOLS: 0
+        TRANSITIONS:
+        a, X, P(0)-L, a
+        ",
+    );
+    let mut machine = Machine::new(&tree);
+    let before = machine.configuration();
+
+    assert_eq!(machine.step(), StepResult::Halted(Halt::LeftEdge));
+    assert_eq!(machine.configuration(), before);
+}
+
+#[test]
+fn test_restore_configuration() {
+    let tree = parse(INVERT);
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, "01").unwrap());
+    let start = machine.configuration();
+    machine.run(100);
+
+    machine.restore(start.clone());
+    assert_eq!(machine.configuration(), start);
+    assert_eq!(machine.symbol_name(), "0");
+}
+
+#[test]
+fn test_parse_tape() {
+    let tree = parse(INVERT);
+    assert_eq!(parse_tape(&tree, "01X"), Ok(vec![0, 1, 2]));
+    assert_eq!(parse_tape(&tree, "0, 1, X"), Ok(vec![0, 1, 2]));
+    assert_eq!(parse_tape(&tree, ""), Ok(vec![]));
+    assert_eq!(
+        parse_tape(&tree, "02"),
+        Err("Symbol 2 not defined.".to_string())
+    );
+}
@@ -1,3 +1,4 @@
+use state_machine_compiler_rust::diagnostics::Span;
 use state_machine_compiler_rust::lexer::{Lexer, Token, TokenType};
 
 #[test]
@@ -419,3 +420,30 @@ fn test_keywords_and_identifiers() {
     }
     assert_eq!(result, expected);
 }
+
+#[test]
+fn test_token_spans() {
+    let code = "STATES: [A]\n  # comment\n  A, 0";
+    let mut lexer = Lexer::new(code);
+    let mut spans = Vec::new();
+    while let Some(token) = lexer.get_token() {
+        if token.kind == TokenType::EOF {
+            break;
+        }
+        spans.push((token.text, lexer.span()));
+    }
+    let expected = vec![
+        ("STATES".to_string(), Span::new(1, 1, 6)),
+        (":".to_string(), Span::new(1, 7, 1)),
+        ("[".to_string(), Span::new(1, 9, 1)),
+        ("A".to_string(), Span::new(1, 10, 1)),
+        ("]".to_string(), Span::new(1, 11, 1)),
+        ("\n".to_string(), Span::new(1, 12, 1)),
+        ("\n".to_string(), Span::new(2, 12, 1)),
+        ("A".to_string(), Span::new(3, 3, 1)),
+        (",".to_string(), Span::new(3, 4, 1)),
+        ("0".to_string(), Span::new(3, 6, 1)),
+        ("\n".to_string(), Span::new(3, 7, 1)),
+    ];
+    assert_eq!(spans, expected);
+}
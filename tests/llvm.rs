@@ -0,0 +1,254 @@
+mod common;
+
+use common::{build_llvm, clean, parse, run, run_ok, CODE, EXAMPLE};
+use state_machine_compiler_rust::interpreter::{parse_tape, Machine};
+use state_machine_compiler_rust::llvmconverter::{
+    LlvmCodegenOptions, LlvmDispatch, LlvmOptLevel, LlvmOutput, ToLlvmIr,
+};
+
+#[test]
+fn test_opt_level_from_str() {
+    assert_eq!("0".parse::<LlvmOptLevel>(), Ok(LlvmOptLevel::O0));
+    assert_eq!("3".parse::<LlvmOptLevel>(), Ok(LlvmOptLevel::O3));
+    assert!("4".parse::<LlvmOptLevel>().is_err());
+    assert_eq!(LlvmOptLevel::O2.passes(), "default<O2>");
+}
+
+#[test]
+fn test_optimized_llvm_ir() {
+    let tree = parse(CODE);
+    let unoptimized = tree.try_to_llvm_ir_with(&LlvmCodegenOptions::default());
+    let optimized = tree.try_to_llvm_ir_with(&LlvmCodegenOptions {
+        opt_level: LlvmOptLevel::O2,
+        ..Default::default()
+    });
+
+    // Both pass verification, the loop counters are promoted to registers
+    let unoptimized = unoptimized.unwrap();
+    let optimized = optimized.unwrap();
+    assert!(unoptimized.contains("%current_step_ptr = alloca i32"));
+    assert!(!optimized.contains("%current_step_ptr = alloca i32"));
+    assert!(optimized.contains("define i32 @main("));
+}
+
+#[test]
+fn test_tape_bounds_checks() {
+    let tree = parse(CODE);
+    let ir = tree.to_llvm_ir();
+
+    assert!(ir.contains("c\"tape overflow at step %d\\0A\\00\""));
+    assert!(ir.contains("call void @exit(i32 101)"));
+    // One check per move of the head: R for a, 0 and a, X, R-L for a, 1
+    assert_eq!(ir.matches("icmp ult i32 %move_").count(), 4);
+    assert!(ir.contains("br i1 %is_tape_empty, label %tape_overflow, label %steps_loop"));
+}
+
+#[test]
+fn test_tape_bounds_checks_at_runtime() {
+    // Off the left end on the first step, off the right end of 4 cells on the fourth
+    for (name, code, expected) in [
+        (
+            "overflow_left",
+            "STATES: [a]\nSYMBOLS: 0\nTRANSITIONS:\na, *, L, a\n",
+            0,
+        ),
+        (
+            "overflow_right",
+            "STATES: [a]\nSYMBOLS: 0\nTRANSITIONS:\na, *, R, a\n",
+            3,
+        ),
+    ] {
+        let Some(program) = build_llvm(name, &parse(code).to_llvm_ir()) else {
+            return;
+        };
+        let output = run(&program, &["--steps", "10", "--tape-len", "4"], &[], "");
+        assert_eq!(output.status.code(), Some(101), "{}", name);
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!("tape overflow at step {}\n", expected),
+            "{}",
+            name
+        );
+        clean(&program);
+    }
+}
+
+#[test]
+fn test_llvm_output() {
+    let tree = parse(CODE);
+    let ir_with = |output| {
+        tree.to_llvm_ir_with(&LlvmCodegenOptions {
+            output,
+            ..Default::default()
+        })
+    };
+
+    // The final tape by default, without any trace or debugging print
+    let ir = ir_with(LlvmOutput::default());
+    assert!(ir.contains("define void @print_clean_tape("));
+    assert!(ir.contains("c\"\\0A=========\\0A\\0A\\00\""));
+    assert!(!ir.contains("TAPE_MACHINE_TRACE"));
+    assert!(!ir.contains("All Symbols"));
+    assert!(!ir.contains("Reached end of steps loop."));
+
+    let ir = ir_with(LlvmOutput::Silent);
+    assert!(!ir.contains("print_clean_tape"));
+    assert!(!ir.contains("No transition for State"));
+
+    let ir = ir_with(LlvmOutput::Trace);
+    assert!(ir.contains("TAPE_MACHINE_TRACE"));
+    assert!(ir.contains("define void @print_clean_tape("));
+}
+
+#[test]
+fn test_debug_info() {
+    let tree = parse(CODE);
+    assert_eq!(
+        tree.transitions.iter().map(|t| t.line).collect::<Vec<_>>(),
+        vec![5, 6, 7]
+    );
+
+    let ir = tree.to_llvm_ir_with(&LlvmCodegenOptions {
+        debug_info: Some("/machines/example.tm".into()),
+        ..Default::default()
+    });
+    assert!(ir.contains("!DIFile(filename: \"example.tm\", directory: \"/machines\")"));
+    assert!(ir.contains("!\"Debug Info Version\", i32 3}"));
+    assert!(ir.contains("define i32 @main(i32 %0, ptr %1) !dbg"));
+    for line in [5, 6, 7] {
+        assert!(ir.contains(&format!("!DILocation(line: {},", line)));
+    }
+
+    // Nothing without a machine file
+    let ir = tree.to_llvm_ir();
+    assert!(!ir.contains("!dbg"));
+}
+
+#[test]
+fn test_command_line_arguments() {
+    let ir = parse(CODE).to_llvm_ir();
+    assert!(ir.contains("define i32 @main(i32 %0, ptr %1)"));
+    assert!(ir.contains("define i32 @parse_args("));
+    assert!(ir.contains("define i32 @load_tape("));
+    assert!(ir.contains("Usage: %s [--steps N] [--tape-len N] [TAPE]"));
+    // The prompts are still there for running without arguments
+    assert!(ir.contains("Enter number of steps: "));
+
+    // Symbols 0, 1 and X are at the character codes of their names
+    let table = ir
+        .lines()
+        .find(|line| line.starts_with("@char_symbols = "))
+        .unwrap();
+    assert!(table.contains("[256 x i8]"));
+    assert_eq!(table.matches("\\FF").count(), 253);
+}
+
+#[test]
+fn test_command_line_arguments_at_runtime() {
+    let tree = parse(EXAMPLE);
+    let Some(program) = build_llvm("arguments", &tree.to_llvm_ir()) else {
+        return;
+    };
+    let expected = |tape: &str, tape_len: usize| {
+        let mut machine = Machine::with_tape(&tree, parse_tape(&tree, tape).unwrap());
+        machine.run(500);
+        format!(
+            "{}{}\n=========\n\n{}\n",
+            machine.tape_string(),
+            "X".repeat(tape_len - machine.tape.len()),
+            machine.clean_tape_string()
+        )
+    };
+
+    let output = run_ok(
+        &program,
+        &["--steps", "500", "--tape-len", "120", "1x0e"],
+        &[],
+        "",
+    );
+    assert_eq!(output, expected("1x0e", 120));
+
+    // Without arguments the steps and the tape length are read from stdin
+    let output = run_ok(&program, &[], &[], "500\n120\n");
+    assert_eq!(
+        output,
+        format!(
+            "Enter number of steps: Enter array size: {}",
+            expected("", 120)
+        )
+    );
+
+    let output = run(&program, &["--steps"], &[], "");
+    assert_eq!(output.status.code(), Some(2));
+    clean(&program);
+}
+
+#[test]
+fn test_llvm_dispatch() {
+    assert_eq!("switch".parse::<LlvmDispatch>(), Ok(LlvmDispatch::Switch));
+    assert_eq!(
+        "threaded".parse::<LlvmDispatch>(),
+        Ok(LlvmDispatch::Threaded)
+    );
+    assert!("goto".parse::<LlvmDispatch>().is_err());
+
+    let tree = parse(CODE);
+    let ir = tree.to_llvm_ir();
+    assert!(ir.contains("%current_state_index_ptr = alloca i32"));
+    assert!(ir.contains("switch i32 %current_switch_case_number"));
+
+    let options = LlvmCodegenOptions {
+        dispatch: LlvmDispatch::Threaded,
+        ..Default::default()
+    };
+    let ir = tree.to_llvm_ir_with(&options);
+    assert!(!ir.contains("current_state_index"));
+    assert!(!ir.contains("current_switch_case_number"));
+    // A block per state, the cases branch straight to the next one
+    assert!(ir.contains("\nstate_a:"));
+    assert!(ir.contains("\nstate_b:"));
+    assert!(ir.contains("br label %state_b\n"));
+    assert!(ir.contains("%halted_state_index = phi i32"));
+
+    // Tracing needs the state variable
+    let result = tree.try_to_llvm_ir_with(&LlvmCodegenOptions {
+        output: LlvmOutput::Trace,
+        ..options
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_llvm_dispatch_runs_like_interpreter() {
+    let tree = parse(EXAMPLE);
+    let mut machine = Machine::new(&tree);
+    assert_eq!(machine.run(1000), None);
+    let tape_len = 200;
+    let expected = format!(
+        "{}{}\n=========\n\n{}\n",
+        machine.tape_string(),
+        "X".repeat(tape_len - machine.tape.len()),
+        machine.clean_tape_string()
+    );
+
+    for (name, dispatch) in [
+        ("dispatch_switch", LlvmDispatch::Switch),
+        ("dispatch_threaded", LlvmDispatch::Threaded),
+    ] {
+        let ir = tree.to_llvm_ir_with(&LlvmCodegenOptions {
+            dispatch,
+            ..Default::default()
+        });
+        let Some(program) = build_llvm(name, &ir) else {
+            return;
+        };
+        let output = run_ok(
+            &program,
+            &["--steps", "1000", "--tape-len", &tape_len.to_string()],
+            &[],
+            "",
+        );
+        assert_eq!(output, expected, "{:?}", dispatch);
+        clean(&program);
+    }
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{parse, EXAMPLE};
+use state_machine_compiler_rust::interpreter::{parse_tape, Halt, Machine};
+use state_machine_compiler_rust::macromachine::MacroMachine;
+
+// Adds a 1 at the end of the tape then goes back to the 0 at its start
+const COUNTER: &str = "
+    STATES: [r], l
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    r, 0 | 1, R, r
+    r, X, P(1)-L, l
+    l, 1, L, l
+    l, 0, R, r
+";
+
+#[test]
+fn test_same_configurations_as_machine() {
+    for (code, tape) in [(EXAMPLE, ""), (COUNTER, "0"), (COUNTER, "0111")] {
+        let tree = parse(code);
+        let tape = parse_tape(&tree, tape).unwrap();
+        for block_size in 1..=4 {
+            let mut machine = Machine::with_tape(&tree, tape.clone());
+            let mut macro_machine = MacroMachine::with_tape(&tree, tape.clone(), block_size);
+            for max_steps in [1, 10, 100, 1000, 100_000] {
+                assert_eq!(macro_machine.run(max_steps), machine.run(max_steps));
+                assert_eq!(
+                    macro_machine.configuration(),
+                    machine.configuration(),
+                    "block size {} after {} steps",
+                    block_size,
+                    machine.steps
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resume_from_configuration() {
+    let tree = parse(EXAMPLE);
+    let mut machine = Machine::new(&tree);
+    machine.run(1000);
+    for block_size in 1..=3 {
+        let mut macro_machine =
+            MacroMachine::from_configuration(&tree, machine.configuration(), block_size);
+        assert_eq!(macro_machine.head(), machine.head);
+        assert_eq!(macro_machine.configuration(), machine.configuration());
+
+        let mut resumed = machine.clone();
+        assert_eq!(macro_machine.run(10_000), resumed.run(10_000));
+        assert_eq!(macro_machine.configuration(), resumed.configuration());
+    }
+}
+
+#[test]
+fn test_same_halts_as_machine() {
+    let tree = parse(
+        "
+        STATES: [a], b
+        SYMBOLS: 0, 1
+        TRANSITIONS:
+        a, 0 | 1, R, a
+        a, X, L, b
+        b, 0, P(1)-L, b
+        ",
+    );
+    for (tape, halt) in [
+        ("0000", Halt::LeftEdge),
+        ("0101011", Halt::NoTransition),
+        ("", Halt::LeftEdge),
+    ] {
+        let tape = parse_tape(&tree, tape).unwrap();
+        for block_size in 1..=3 {
+            let mut machine = Machine::with_tape(&tree, tape.clone());
+            let mut macro_machine = MacroMachine::with_tape(&tree, tape.clone(), block_size);
+            assert_eq!(machine.run(1000), Some(halt));
+            assert_eq!(macro_machine.run(1000), Some(halt));
+            assert_eq!(macro_machine.configuration(), machine.configuration());
+        }
+    }
+}
+
+#[test]
+fn test_long_runs() {
+    let tree = parse(COUNTER);
+    let tape = parse_tape(&tree, "0").unwrap();
+    let mut macro_machine = MacroMachine::with_tape(&tree, tape, 1);
+
+    // About 2 n steps to add the n-th 1
+    assert_eq!(macro_machine.run(10_000_000_000), None);
+    assert_eq!(macro_machine.steps, 10_000_000_000);
+    let tape = macro_machine.tape_rle_string();
+    let ones = tape
+        .strip_prefix("0 (1)^")
+        .and_then(|rest| rest.strip_suffix(" X"))
+        .and_then(|rest| rest.parse::<u64>().ok())
+        .expect(&tape);
+    assert!((99_000..101_000).contains(&ones), "{}", tape);
+}
+
+// Write out the runs of a tape given by `MacroMachine::tape_rle_string`
+fn expand(rle: &str) -> String {
+    let mut tape = String::new();
+    let mut chars = rle.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ' ' {
+            continue;
+        }
+        if c != '(' {
+            tape.push(c);
+            continue;
+        }
+        let block = chars.by_ref().take_while(|&c| c != ')').collect::<String>();
+        assert_eq!(chars.next(), Some('^'));
+        let mut count = String::new();
+        while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+            count.push(digit);
+        }
+        tape.push_str(&block.repeat(count.parse().unwrap()));
+    }
+    tape
+}
+
+#[test]
+fn test_tape_rle_string() {
+    let tree = parse(EXAMPLE);
+    let mut machine = Machine::new(&tree);
+    machine.run(10_000);
+    let mut macro_machine = MacroMachine::new(&tree, 2);
+    macro_machine.run(10_000);
+
+    // Every 1 is followed by an empty cell
+    let tape = macro_machine.tape_rle_string();
+    assert!(
+        tape.starts_with("ee (0X)^2 1X0X (1X)^2 0X (1X)^3 0X"),
+        "{}",
+        tape
+    );
+    assert_eq!(expand(&tape), machine.tape_string());
+}
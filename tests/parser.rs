@@ -1,3 +1,4 @@
+use state_machine_compiler_rust::diagnostics::{Severity, Span};
 use state_machine_compiler_rust::lexer::Lexer;
 use state_machine_compiler_rust::parser::Parser;
 
@@ -171,4 +172,107 @@ fn test_invalid_print_symbol() {
     let mut parser = Parser::new(lexer);
     parser.program();
     println!("Parsing Completed");
-}
\ No newline at end of file
+}
+#[test]
+fn test_undeclared_state_warning() {
+    let code = "
+        STATES: [A], B, C, D
+        SYMBOLS: 0, 1, B
+        TRANSITIONS:
+        A, 0 | 1, X-L-L-P(X)-L, B
+        B, * , L , C1
+    ";
+
+    let lexer = Lexer::new(code);
+    let mut parser = Parser::new(lexer);
+    parser.program();
+
+    let diagnostics = parser.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!(diagnostics[0].message, "State C1 is not declared.");
+    assert_eq!(diagnostics[0].span, Span::new(6, 20, 2));
+    assert_eq!(
+        diagnostics[0].help,
+        Some("did you mean state `C`?".to_string())
+    );
+}
+
+#[test]
+fn test_invalid_print_symbol_diagnostic() {
+    let code = "STATES: [A], B\nSYMBOLS: 0, 1\nTRANSITIONS:\nA, 0, P(11), B\n";
+
+    let lexer = Lexer::new(code);
+    let mut parser = Parser::new(lexer);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| parser.program()));
+    assert!(result.is_err());
+
+    let diagnostic = parser.diagnostics().last().unwrap();
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(diagnostic.span, Span::new(4, 9, 2));
+    assert_eq!(
+        diagnostic.help,
+        Some("did you mean symbol `1`?".to_string())
+    );
+}
+
+#[test]
+fn test_try_program_returns_errors() {
+    let code = "STATES: [A], B\nSYMBOLS: 0, 1\nTRANSITIONS:\nA, 0, P(11), B\n";
+    let mut parser = Parser::new(Lexer::new(code));
+    let diagnostic = parser.try_program().unwrap_err();
+    assert_eq!(
+        diagnostic.message,
+        "Symbol 11 not defined, So cannot be printed."
+    );
+    assert_eq!(diagnostic.span, Span::new(4, 9, 2));
+    assert_eq!(parser.diagnostics(), [diagnostic]);
+
+    // Unknown characters are reported by the lexer
+    let mut parser = Parser::new(Lexer::new("STATES: [A] !\n"));
+    let diagnostic = parser.try_program().unwrap_err();
+    assert_eq!(diagnostic.message, "Unknown token: !");
+    assert_eq!(diagnostic.span, Span::new(1, 13, 1));
+
+    let mut parser = Parser::new(Lexer::new(
+        "STATES: [A]\nSYMBOLS: 0\nTRANSITIONS:\nA, 0, R, A\n",
+    ));
+    assert_eq!(parser.try_program(), Ok(()));
+}
+
+#[test]
+fn test_transitions_star_last() {
+    let code = "STATES: [A], B\nSYMBOLS: 0, 1\nTRANSITIONS:\nA, *, R, B\nB, *, L, A\nA, 0, P(1), A\nB, 1 | 0, X, B\n";
+    let mut parser = Parser::new(Lexer::new(code));
+    parser.program();
+    let order: Vec<String> = parser
+        .tree
+        .transitions_star_last()
+        .iter()
+        .map(|transition| transition.to_string())
+        .collect();
+    // Stable, the star transitions keep the order of the source
+    assert_eq!(
+        order,
+        [
+            "A, 0, P(1), A",
+            "B, 1 | 0, X, B",
+            "A, *, R, B",
+            "B, *, L, A"
+        ]
+    );
+}
+
+#[test]
+fn test_transitions_from() {
+    let code = "STATES: [A], B\nSYMBOLS: 0, 1\nTRANSITIONS:\nA, *, R, B\nA, 1, L, C\nA, 0, P(1), A\nB, 1, X, B\n";
+    let mut parser = Parser::new(Lexer::new(code));
+    parser.program();
+    // A, 1 goes to the undeclared state C and is left out, the star
+    // transition only takes X
+    assert_eq!(
+        parser.tree.transitions_from("A"),
+        vec![(2, vec![0]), (0, vec![2])]
+    );
+    assert_eq!(parser.tree.transitions_from("B"), vec![(3, vec![1])]);
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use common::{parse, run_rust, EXAMPLE};
+use state_machine_compiler_rust::interpreter::{parse_tape, Halt, Machine};
+use state_machine_compiler_rust::parser::{ParseTree, RustCodegenOptions};
+use state_machine_compiler_rust::snapshot;
+
+const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, *, P(0)-R, b
+    a, 1, R-L-L, a
+    b, 0 | 1, P(1), a
+";
+
+#[test]
+fn test_library_api() {
+    let tree = parse(CODE);
+    let code = tree.to_rust_code_with(&RustCodegenOptions {
+        library: true,
+        ..Default::default()
+    });
+
+    assert_eq!(code, tree.to_rust_library());
+    assert!(!code.contains("fn main()"));
+    assert!(!code.contains("io::stdin"));
+    assert!(code.contains("pub struct TapeMachine {\n"));
+    assert!(code.contains("    pub fn new(mut tape: Vec<TapeMachineSymbol>) -> Self {\n"));
+    assert!(code.contains("            state: TapeMachineState::a,\n"));
+    assert!(code.contains("    pub fn step(&mut self) -> Result<(), Halt> {\n"));
+    assert!(code.contains("    pub fn run(&mut self, max_steps: usize) -> Option<Halt> {\n"));
+    assert!(code.contains("    pub fn state(&self) -> TapeMachineState {\n"));
+    assert!(code.contains("    pub fn tape(&self) -> &[TapeMachineSymbol] {\n"));
+    assert!(code.contains("    pub fn snapshot(&self) -> String {\n"));
+    assert!(code.contains("    pub fn from_snapshot(snapshot: &str) -> Result<Self, String> {\n"));
+}
+
+#[test]
+fn test_library_state_names() {
+    let tree = parse(CODE);
+    let code = tree.to_rust_library();
+
+    // Snapshots refer to states by name
+    assert!(code.contains("impl TapeMachineState {\n"));
+    assert!(code.contains("            TapeMachineState::b => \"b\",\n"));
+    assert!(code.contains("            \"a\" => Some(TapeMachineState::a),\n"));
+}
+
+#[test]
+fn test_library_transitions() {
+    let tree = parse(CODE);
+    let code = tree.to_rust_library();
+
+    // Star transitions are matched last
+    let star = code.find("(TapeMachineState::a, _) => {").unwrap();
+    let one = code
+        .find("(TapeMachineState::a, TapeMachineSymbol::Symbol1) => {")
+        .unwrap();
+    assert!(one < star);
+    assert!(code.contains(
+        "(TapeMachineState::b, TapeMachineSymbol::Symbol0 | TapeMachineSymbol::Symbol1) => {"
+    ));
+
+    // R-L-L needs the head to be at least one cell away from the left edge
+    assert!(code.contains(
+        "                if self.head < 1 {\n                    return Err(Halt::LeftEdge);\n"
+    ));
+    assert_eq!(code.matches("Err(Halt::LeftEdge)").count(), 1);
+    assert!(code.contains("            _ => return Err(Halt::NoTransition),\n"));
+}
+
+// The library with a `main` running the machine on `tape`, given by symbol
+// names separated by commas
+fn library_program(tree: &ParseTree, tape: &str, max_steps: usize) -> String {
+    let mut code = tree.to_rust_library();
+    code.push_str(&format!(
+        "
+fn main() {{
+    let tape = \"{}\"
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| TapeMachineSymbol::from_name(name).unwrap())
+        .collect();
+    let mut machine = TapeMachine::new(tape);
+    let halt = machine.run({});
+    let tape: String = machine.tape().iter().map(|s| s.as_str()).collect();
+    println!(\"{{:?}} {{:?}} {{}} {{}} {{}}\", halt, machine.state(), machine.head(), machine.steps(), tape);
+}}
+",
+        tape, max_steps
+    ));
+    code
+}
+
+#[test]
+fn test_library_runs_like_interpreter() {
+    for (name, code, tape, halt) in [
+        ("library_example", EXAMPLE, "", None),
+        ("library_left_edge", CODE, "1,0", Some(Halt::LeftEdge)),
+        ("library_no_transition", CODE, "", Some(Halt::NoTransition)),
+    ] {
+        let tree = parse(code);
+        let Some(output) = run_rust(name, &library_program(&tree, tape, 1000), "") else {
+            return;
+        };
+
+        let mut machine = Machine::with_tape(&tree, parse_tape(&tree, tape).unwrap());
+        assert_eq!(machine.run(1000), halt);
+        let expected = format!(
+            "{:?} {} {} {} {}\n",
+            halt,
+            tree.states[machine.state],
+            machine.head,
+            machine.steps,
+            machine.tape_string()
+        );
+        assert_eq!(output, expected, "{}", name);
+    }
+}
+
+#[test]
+fn test_library_snapshots() {
+    let tree = parse(EXAMPLE);
+    let mut machine = Machine::new(&tree);
+    machine.run(100);
+    let at_100 = snapshot::to_text(&tree, &machine.configuration());
+    machine.run(100);
+    let at_200 = snapshot::to_text(&tree, &machine.configuration());
+
+    // Saves step 100, then resumes from the interpreter's snapshot of it
+    let mut code = tree.to_rust_library();
+    code.push_str(&format!(
+        "
+fn main() {{
+    let mut machine = TapeMachine::new(Vec::new());
+    machine.run(100);
+    print!(\"{{}}\", machine.snapshot());
+    let mut resumed = TapeMachine::from_snapshot({:?}).unwrap();
+    resumed.run(100);
+    print!(\"{{}}\", resumed.snapshot());
+    assert!(TapeMachine::from_snapshot(\"state: b\\nhead: 9\\nsteps: 0\\ntape: 0\").is_err());
+}}
+",
+        at_100
+    ));
+    let Some(output) = run_rust("library_snapshots", &code, "") else {
+        return;
+    };
+    assert_eq!(output, format!("{}{}", at_100, at_200));
+}
+
+#[test]
+fn test_program_by_default() {
+    let tree = parse(CODE);
+    let code = tree.to_rust_code();
+
+    assert!(code.contains("fn main() {\n"));
+    assert!(!code.contains("pub struct TapeMachine {"));
+}
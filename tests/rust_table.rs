@@ -0,0 +1,90 @@
+mod common;
+
+use common::{parse, run_rust, CODE, EXAMPLE};
+use state_machine_compiler_rust::interpreter::Machine;
+use state_machine_compiler_rust::parser::{ParseTree, RustCodegenOptions, RustDispatch};
+use state_machine_compiler_rust::trace::{TraceLevel, TraceOptions};
+
+fn table_code(tree: &ParseTree) -> String {
+    tree.to_rust_code_with(&RustCodegenOptions {
+        dispatch: RustDispatch::Table,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_dispatch_from_str() {
+    assert_eq!("match".parse::<RustDispatch>(), Ok(RustDispatch::Match));
+    assert_eq!("table".parse::<RustDispatch>(), Ok(RustDispatch::Table));
+    assert!("switch".parse::<RustDispatch>().is_err());
+}
+
+#[test]
+fn test_transition_table() {
+    let tree = parse(CODE);
+    let code = table_code(&tree);
+
+    assert!(code.contains("const SYMBOL_COUNT: usize = 3;\n"));
+    assert!(code.contains(
+        "static ACTIONS: [Action; 5] = [\n    Action::P(0),\n    Action::R,\n    Action::R,\n    Action::L,\n    Action::P(1),\n];\n"
+    ));
+    assert!(code.contains(
+        "\
+static TRANSITIONS: [Entry; 6] = [
+    Entry { next: 1, start: 0, len: 2 }, // a, 0
+    Entry { next: 0, start: 2, len: 2 }, // a, 1
+    Entry { next: 1, start: 0, len: 2 }, // a, X
+    Entry { next: 0, start: 4, len: 1 }, // b, 0
+    Entry { next: 0, start: 4, len: 1 }, // b, 1
+    Entry { next: NO_TRANSITION, start: 0, len: 0 }, // b, X
+];
+"
+    ));
+}
+
+#[test]
+fn test_table_loop() {
+    let tree = parse(CODE);
+    let code = table_code(&tree);
+
+    assert!(code.contains("    let mut state: usize = 0;\n"));
+    assert!(code.contains("        let entry = TRANSITIONS[state * SYMBOL_COUNT + symbol];\n"));
+    assert!(!code.contains("match (tape_machine.state"));
+    // Same stdin driven program as the match dispatch
+    assert!(code.contains("fn main() {\n"));
+    assert!(tree.to_rust_code().contains("match (tape_machine.state"));
+}
+
+#[test]
+fn test_table_program_runs_like_interpreter() {
+    let tree = parse(EXAMPLE);
+    let mut machine = Machine::new(&tree);
+    assert_eq!(machine.run(1000), None);
+    // The program starts on a blank tape of the given length
+    let tape_len = 200;
+    let expected = format!(
+        "Enter the number of steps:\nEnter the total tape length:\n{}{}\n=========\n\n{}\n",
+        machine.tape_string(),
+        "X".repeat(tape_len - machine.tape.len()),
+        machine.clean_tape_string()
+    );
+
+    // Same output with both dispatches
+    for (name, dispatch) in [
+        ("program_match", RustDispatch::Match),
+        ("program_table", RustDispatch::Table),
+    ] {
+        let code = tree.to_rust_code_with(&RustCodegenOptions {
+            trace: TraceOptions {
+                level: TraceLevel::None,
+                ..Default::default()
+            },
+            dispatch,
+            ..Default::default()
+        });
+        let Some(output) = run_rust(name, &code, &format!("1000\n{}\n", tape_len)) else {
+            return;
+        };
+        assert_eq!(output, expected, "{:?}", dispatch);
+    }
+}
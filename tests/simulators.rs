@@ -0,0 +1,150 @@
+mod common;
+
+use common::{clean, has_tool, parse, run_ok, write_temp, CODE, EXAMPLE};
+use state_machine_compiler_rust::interpreter::{parse_tape, Machine};
+use state_machine_compiler_rust::jsconverter::ToJavaScript;
+use state_machine_compiler_rust::pythonconverter::ToPython;
+
+#[test]
+fn test_python_simulator() {
+    let tree = parse(CODE);
+    let code = tree.to_python();
+
+    assert!(code.contains("STATES = (\"a\", \"b\",)\nSYMBOLS = (\"0\", \"1\", \"X\",)\n"));
+    assert!(code.contains("        self._state = \"a\"\n"));
+    // The star transition comes last, like in the generated Rust code
+    assert!(code.contains(
+        "\
+        if state == \"a\" and symbol in (\"1\",):
+            self._r()
+            self._l()
+            next_state = \"a\"
+        elif state == \"b\" and symbol in (\"0\", \"1\",):
+            self._p(\"1\")
+            next_state = \"a\"
+        elif state == \"a\":
+            self._p(\"0\")
+            self._r()
+            next_state = \"b\"
+        else:
+            return Halt.NO_TRANSITION
+"
+    ));
+    assert!(code.contains("    def run(self, max_steps):\n"));
+}
+
+#[test]
+fn test_javascript_simulator() {
+    let tree = parse(CODE);
+    let code = tree.to_javascript();
+
+    assert!(code.contains("export const SYMBOLS = Object.freeze([\"0\", \"1\", \"X\"]);\n"));
+    assert!(code.contains("export class TapeMachine {\n"));
+    assert!(code.contains(
+        "    \
+    if (state === \"a\" && (symbol === \"1\")) {
+      this.#r();
+      this.#l();
+      next = \"a\";
+    } else if (state === \"b\" && (symbol === \"0\" || symbol === \"1\")) {
+      this.#p(\"1\");
+      next = \"a\";
+    } else if (state === \"a\") {
+      this.#p(\"0\");
+      this.#r();
+      next = \"b\";
+    } else {
+      return Halt.NO_TRANSITION;
+    }
+"
+    ));
+    assert!(code.contains("  run(maxSteps) {\n"));
+}
+
+#[test]
+fn test_left_reach() {
+    let tree = parse(CODE);
+    assert_eq!(tree.transitions[0].left_reach(), 0);
+    assert_eq!(tree.transitions[1].left_reach(), 0);
+
+    // R-L-L ends one cell left of where it started
+    let tree = parse("STATES: [a]\nSYMBOLS: 0\nTRANSITIONS:\na, *, R-L-L, a\n");
+    assert_eq!(tree.transitions[0].left_reach(), 1);
+}
+
+// Machines and tapes run by the simulators: no halt, no transition and the
+// left edge
+const RUNS: [(&str, &str); 4] = [
+    (EXAMPLE, ""),
+    (CODE, ""),
+    (CODE, "10"),
+    (
+        "STATES: [a]\nSYMBOLS: 0\nTRANSITIONS:\na, 0, R, a\na, X, L-L-L, a\n",
+        "00",
+    ),
+];
+
+// The halt, state, head, steps and tape of the interpreter after 1000 steps,
+// in the format printed by the simulator drivers
+fn expected(code: &str, tape: &str) -> String {
+    let tree = parse(code);
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, tape).unwrap());
+    let halt = machine.run(1000);
+    format!(
+        "{} {} {} {} {}\n",
+        halt.map(|halt| format!("{:?}", halt))
+            .unwrap_or("None".to_string()),
+        machine.state_name(),
+        machine.head,
+        machine.steps,
+        machine.tape_string()
+    )
+}
+
+#[test]
+fn test_python_simulator_runs_like_interpreter() {
+    if !has_tool("python3") {
+        return;
+    }
+    for (index, (code, tape)) in RUNS.into_iter().enumerate() {
+        let module = write_temp(
+            "python",
+            "tape_machine.py",
+            parse(code).to_python().as_bytes(),
+        );
+        let driver = format!(
+            "import sys\nsys.path.insert(0, {:?})\nfrom tape_machine import TapeMachine\n\
+             machine = TapeMachine({:?})\nhalt = machine.run(1000)\n\
+             print(halt.value if halt else None, machine.state, machine.head, machine.steps, machine.tape_string())\n",
+            module.parent().unwrap().to_str().unwrap(),
+            tape
+        );
+        let output = run_ok("python3", &["-c", &driver], &[], "");
+        assert_eq!(output, expected(code, tape), "run {}", index);
+        clean(&module);
+    }
+}
+
+#[test]
+fn test_javascript_simulator_runs_like_interpreter() {
+    if !has_tool("node") {
+        return;
+    }
+    for (index, (code, tape)) in RUNS.into_iter().enumerate() {
+        let module = write_temp(
+            "javascript",
+            "tape_machine.mjs",
+            parse(code).to_javascript().as_bytes(),
+        );
+        let driver = format!(
+            "import {{ TapeMachine }} from {:?};\n\
+             const machine = new TapeMachine({:?});\nconst halt = machine.run(1000);\n\
+             console.log(halt ?? \"None\", machine.state, machine.head, machine.steps, machine.tapeString());\n",
+            module.to_str().unwrap(),
+            tape
+        );
+        let output = run_ok("node", &["--input-type=module", "-e", &driver], &[], "");
+        assert_eq!(output, expected(code, tape), "run {}", index);
+        clean(&module);
+    }
+}
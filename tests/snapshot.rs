@@ -0,0 +1,84 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::interpreter::{parse_tape, Configuration, Machine};
+use state_machine_compiler_rust::snapshot;
+
+const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, 0, P(1)-R, a
+    a, 1, R-R, b
+    b, *, P(0)-L, a
+";
+
+#[test]
+fn test_snapshot_text() {
+    let tree = parse(CODE);
+    let configuration = Configuration {
+        state: 1,
+        head: 2,
+        tape: parse_tape(&tree, "01X1").unwrap(),
+        steps: 42,
+    };
+    let text = snapshot::to_text(&tree, &configuration);
+
+    assert_eq!(text, "state: b\nhead: 2\nsteps: 42\ntape: 0,1,X,1\n");
+    assert_eq!(snapshot::from_text(&tree, &text), Ok(configuration.clone()));
+    assert_eq!(
+        snapshot::from_text(
+            &tree,
+            "# saved by hand\n\ntape: 0, 1, X, 1\nsteps: 42\nhead: 2\nstate: b\n"
+        ),
+        Ok(configuration)
+    );
+}
+
+#[test]
+fn test_invalid_snapshots() {
+    let tree = parse(CODE);
+    for (text, error) in [
+        ("state: c\nhead: 0\nsteps: 0\ntape: 0", "Unknown state: c"),
+        ("state: a\nhead: -1\nsteps: 0\ntape: 0", "Invalid head: "),
+        (
+            "state: a\nhead: 0\nsteps: 0\ntape: 0,2",
+            "Unknown symbol: 2",
+        ),
+        ("state: a\nhead: 0\nsteps: 0", "Missing tape"),
+        (
+            "state: a\nhead: 0\nsteps: 0\ntape: 0\ncolor: red",
+            "Unknown key: color",
+        ),
+        ("state a", "Invalid line: state a"),
+        (
+            "state: a\nhead: 4\nsteps: 0\ntape: 0,1",
+            "The head is past the end of the tape",
+        ),
+    ] {
+        let result = snapshot::from_text(&tree, text);
+        assert!(
+            result.as_ref().is_err_and(|e| e.starts_with(error)),
+            "{:?} for {:?}",
+            result,
+            text
+        );
+    }
+}
+
+#[test]
+fn test_resume_run() {
+    let tree = parse(CODE);
+    let tape = parse_tape(&tree, "0010110").unwrap();
+    let mut machine = Machine::with_tape(&tree, tape.clone());
+    machine.run(5);
+
+    let text = snapshot::to_text(&tree, &machine.configuration());
+    let configuration = snapshot::from_text(&tree, &text).unwrap();
+    let mut resumed = Machine::from_configuration(&tree, configuration);
+    let halt = resumed.run(100);
+
+    let mut uninterrupted = Machine::with_tape(&tree, tape);
+    assert_eq!(uninterrupted.run(105), halt);
+    assert_eq!(resumed.configuration(), uninterrupted.configuration());
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::parse;
+use state_machine_compiler_rust::escape::xml_escape;
+use state_machine_compiler_rust::interpreter::{parse_tape, Machine};
+use state_machine_compiler_rust::spacetime::{record_run, to_svg, to_text, DiagramFormat};
+
+const CODE: &str = "
+    STATES: [a], b
+    SYMBOLS: 0, 1
+    TRANSITIONS:
+    a, 0, P(1)-R, a
+    a, 1, P(0)-R, a
+    a, X, L, b
+";
+
+#[test]
+fn test_record_run() {
+    let tree = parse(CODE);
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, "01").unwrap());
+    let rows = record_run(&mut machine, 100);
+
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[0].steps, 0);
+    assert_eq!(rows[3].state, 1);
+
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, "01").unwrap());
+    assert_eq!(record_run(&mut machine, 1).len(), 2);
+}
+
+#[test]
+fn test_text_diagram() {
+    let tree = parse(CODE);
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, "01").unwrap());
+    let rows = record_run(&mut machine, 100);
+
+    assert_eq!(
+        to_text(&tree, &rows),
+        "\
+0  a [0] 1  X
+1  a  1 [1] X
+2  a  1  0 [X]
+3  b  1 [0] X
+"
+    );
+}
+
+#[test]
+fn test_svg_diagram() {
+    let tree = parse(CODE);
+    let mut machine = Machine::with_tape(&tree, parse_tape(&tree, "01").unwrap());
+    let rows = record_run(&mut machine, 100);
+    let svg = to_svg(&tree, &rows);
+
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.ends_with("</svg>\n"));
+    // One highlighted head cell per row
+    assert_eq!(svg.matches("fill=\"#ffd54f\"").count(), 4);
+    assert!(svg.contains(">3 b</text>"));
+}
+
+#[test]
+fn test_diagram_format_and_escape() {
+    assert_eq!("text".parse::<DiagramFormat>(), Ok(DiagramFormat::Text));
+    assert_eq!("svg".parse::<DiagramFormat>(), Ok(DiagramFormat::Svg));
+    assert!("png".parse::<DiagramFormat>().is_err());
+    assert_eq!(xml_escape("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use common::{build_rust, clean, parse, run_ok, CODE, EXAMPLE};
+use state_machine_compiler_rust::interpreter::Machine;
+#[cfg(feature = "llvm")]
+use state_machine_compiler_rust::llvmconverter::{LlvmCodegenOptions, LlvmOutput, ToLlvmIr};
+use state_machine_compiler_rust::parser::RustCodegenOptions;
+use state_machine_compiler_rust::trace::{TraceFormat, TraceLevel, TraceOptions};
+
+#[test]
+fn test_trace_options_from_str() {
+    assert_eq!("none".parse::<TraceLevel>(), Ok(TraceLevel::None));
+    assert_eq!(
+        "transitions".parse::<TraceLevel>(),
+        Ok(TraceLevel::Transitions)
+    );
+    assert_eq!("full".parse::<TraceLevel>(), Ok(TraceLevel::Full));
+    assert!("all".parse::<TraceLevel>().is_err());
+    assert_eq!("human".parse::<TraceFormat>(), Ok(TraceFormat::Human));
+    assert_eq!("compact".parse::<TraceFormat>(), Ok(TraceFormat::Compact));
+    assert!("csv".parse::<TraceFormat>().is_err());
+}
+
+#[test]
+fn test_rust_code_trace_defaults() {
+    let tree = parse(CODE);
+
+    let code = tree.to_rust_code();
+    assert!(code.contains("std::env::var(\"TAPE_MACHINE_TRACE\")"));
+    assert!(code.contains("        _ => TraceLevel::Transitions,\n"));
+    assert!(code.contains("        _ => false,\n"));
+
+    let code = tree.to_rust_code_with(&RustCodegenOptions {
+        trace: TraceOptions {
+            level: TraceLevel::Full,
+            format: TraceFormat::Compact,
+        },
+        ..Default::default()
+    });
+    assert!(code.contains("        _ => TraceLevel::Full,\n"));
+    assert!(code.contains("        _ => true,\n"));
+}
+
+#[test]
+#[cfg(feature = "llvm")]
+fn test_llvm_ir_trace() {
+    let tree = parse(CODE);
+
+    let ir = tree.to_llvm_ir_with(&LlvmCodegenOptions {
+        trace: TraceOptions {
+            level: TraceLevel::None,
+            format: TraceFormat::Human,
+        },
+        output: LlvmOutput::Trace,
+        ..Default::default()
+    });
+    assert!(ir.contains("c\"TAPE_MACHINE_TRACE\\00\""));
+    assert!(ir.contains("c\"TAPE_MACHINE_TRACE_FORMAT\\00\""));
+    assert!(ir.contains("define void @print_tape("));
+    assert!(ir.contains("define void @print_head_marker("));
+    assert!(!ir.contains("Current step: %d"));
+}
+
+#[test]
+fn test_rust_program_trace_env_vars() {
+    let tree = parse(EXAMPLE);
+    let Some(program) = build_rust("trace_program", &tree.to_rust_code()) else {
+        return;
+    };
+    let (steps, tape_len) = (5, 20);
+    let run = |level: &str, format: &str| {
+        run_ok(
+            &program,
+            &[],
+            &[
+                ("TAPE_MACHINE_TRACE", level),
+                ("TAPE_MACHINE_TRACE_FORMAT", format),
+            ],
+            &format!("{}\n{}\n", steps, tape_len),
+        )
+    };
+
+    // The trace comes between the prompts and the final tape
+    let mut machine = Machine::new(&tree);
+    let mut compact = Vec::new();
+    let mut full = Vec::new();
+    for step in 0..steps {
+        let line = format!(
+            "{}\t{}\t{}\t{}",
+            step,
+            machine.state_name(),
+            machine.head,
+            machine.symbol_name()
+        );
+        let tape = machine.tape_string();
+        full.push(format!(
+            "{}\t{}{}",
+            line,
+            tape,
+            "X".repeat(tape_len - tape.len())
+        ));
+        compact.push(line);
+        machine.step();
+    }
+    let tape = machine.tape_string();
+    let end = format!(
+        "{}{}\n=========\n\n{}\n",
+        tape,
+        "X".repeat(tape_len - tape.len()),
+        machine.clean_tape_string()
+    );
+    let prompts = "Enter the number of steps:\nEnter the total tape length:\n";
+
+    assert_eq!(run("none", "compact"), format!("{}{}", prompts, end));
+    assert_eq!(
+        run("transitions", "compact"),
+        format!("{}{}\n{}", prompts, compact.join("\n"), end)
+    );
+    assert_eq!(
+        run("full", "compact"),
+        format!("{}{}\n{}", prompts, full.join("\n"), end)
+    );
+
+    // Human readable, a line for the step and one for the next state, the
+    // tape and the head marker in between with full
+    let trace_lines = |output: String| output.lines().count() - 6;
+    assert_eq!(trace_lines(run("none", "human")), 0);
+    assert_eq!(trace_lines(run("transitions", "human")), 2 * steps);
+    let output = run("full", "human");
+    assert_eq!(trace_lines(output.clone()), 4 * steps);
+    assert!(output.contains("\nStep: 0 State: b Symbol: SymbolX\nXXXXXXXXXXXXXXXXXXXX\n^\n"));
+    clean(&program);
+}
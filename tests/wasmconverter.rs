@@ -0,0 +1,130 @@
+mod common;
+
+use common::{clean, has_tool, parse, run_ok, write_temp, CODE, EXAMPLE};
+use state_machine_compiler_rust::interpreter::Machine;
+use state_machine_compiler_rust::wasmconverter::ToWasm;
+
+#[test]
+fn test_wasm_header_and_exports() {
+    let tree = parse(CODE);
+    let wasm = tree.to_wasm();
+
+    assert_eq!(&wasm[..8], b"\0asm\x01\x00\x00\x00");
+    // Export section: the memory, then the functions in order
+    let exports =
+        b"\x05\x06memory\x02\x00\x04init\x00\x00\x04step\x00\x01\x05state\x00\x02\x04head\x00\x03";
+    assert!(wasm.windows(exports.len()).any(|w| w == exports));
+    // No import section, the module runs without any host function
+    assert_eq!(wasm[8], 0x01);
+    assert!(!wasm.windows(6).any(|w| w == b"printf"));
+}
+
+#[test]
+fn test_wat_dispatch() {
+    let tree = parse(CODE);
+    let wat = tree.to_wat();
+
+    assert!(wat.starts_with("(module\n  ;; states: a, b\n  ;; symbols: 0, 1, X\n"));
+    assert!(wat.contains("  (func (export \"init\") (param $tape_ptr i32) (param $tape_len i32)\n"));
+    // The symbol transition is tested first, the star one takes the other symbols
+    assert!(wat.contains(
+        "\
+        ;; a, 1, R-L-X, a
+        local.get 0
+        i32.const 1
+        i32.eq
+        if
+"
+    ));
+    assert!(wat.find(";; a, 1, R-L-X, a").unwrap() < wat.find(";; a, *, P(0)-R, b").unwrap());
+    assert!(wat.contains(
+        "\
+        ;; b, 0 | 1, P(1), a
+        local.get 0
+        i32.const 0
+        i32.eq
+        local.get 0
+        i32.const 1
+        i32.eq
+        i32.or
+        if
+"
+    ));
+}
+
+#[test]
+fn test_wasm_large_constants() {
+    // State indices from 64 need two bytes of signed LEB128
+    let states: Vec<String> = (0..70).map(|i| format!("s{}", i)).collect();
+    let mut code = format!(
+        "STATES: [s0], {}\nSYMBOLS: 0\nTRANSITIONS:\n",
+        states[1..].join(", ")
+    );
+    for i in 0..69 {
+        code.push_str(&format!("s{}, *, R, s{}\n", i, i + 1));
+    }
+    let tree = parse(&code);
+    let wasm = tree.to_wasm();
+
+    // i32.const 64, global.set 0
+    assert!(wasm.windows(5).any(|w| w == [0x41, 0xc0, 0x00, 0x24, 0x00]));
+    assert!(tree.to_wat().contains("i32.const 69\n"));
+}
+
+// Loads the module given as argument, runs it for at most 1000 steps on a
+// blank tape of 200 cells and prints the steps, state, head and tape
+const NODE_DRIVER: &str = "
+import { readFileSync } from 'node:fs';
+const bytes = readFileSync(process.argv[2]);
+const { instance } = await WebAssembly.instantiate(bytes);
+const { memory, init, step, state, head } = instance.exports;
+const blank = Number(process.argv[3]);
+const tape = new Uint8Array(memory.buffer, 0, 200);
+tape.fill(blank);
+init(0, 200);
+let steps = 0;
+while (steps < 1000 && step() === 0) {
+    steps++;
+}
+console.log(steps, state(), head(), tape.join(','));
+";
+
+#[test]
+fn test_wasm_runs_like_interpreter() {
+    if !has_tool("node") {
+        return;
+    }
+    let tree = parse(EXAMPLE);
+    let mut machine = Machine::new(&tree);
+    assert_eq!(machine.run(1000), None);
+    let blank = machine.blank();
+    let mut tape = machine.tape.clone();
+    tape.resize(200, blank);
+
+    let module = write_temp("wasm", "state_machine.wasm", &tree.to_wasm());
+    let driver = write_temp("wasm", "driver.mjs", NODE_DRIVER.as_bytes());
+    let output = run_ok(
+        "node",
+        &[
+            driver.to_str().unwrap(),
+            module.to_str().unwrap(),
+            &blank.to_string(),
+        ],
+        &[],
+        "",
+    );
+    assert_eq!(
+        output,
+        format!(
+            "{} {} {} {}\n",
+            machine.steps,
+            machine.state,
+            machine.head,
+            tape.iter()
+                .map(|symbol| symbol.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        )
+    );
+    clean(&module);
+}